@@ -0,0 +1,323 @@
+//! Iterators over arrangements of items.
+//!
+//! Every iterator yields arrays, whose length is a const parameter, and
+//! keeps nothing but the indices it is at, so none of them allocate.
+
+use std::{array, iter::FusedIterator};
+
+/// Every order of `items`, in lexicographic order of their positions.
+pub fn permutations<T: Clone, const N: usize>(items: &[T; N]) -> Permutations<'_, T, N> {
+    Permutations {
+        items,
+        indices: array::from_fn(|i| i),
+        done: false,
+    }
+}
+
+/// [`permutations`] of a slice, which must have `N` items.
+pub fn permutations_of<T: Clone, const N: usize>(items: &[T]) -> Option<Permutations<'_, T, N>> {
+    if items.len() != N {
+        return None;
+    }
+
+    Some(Permutations {
+        items,
+        indices: array::from_fn(|i| i),
+        done: false,
+    })
+}
+
+/// Every order of `items`, where each one swaps two items of the last.
+/// This is Heap's algorithm.
+pub fn heap_permutations<T: Clone, const N: usize>(items: [T; N]) -> HeapPermutations<T, N> {
+    HeapPermutations {
+        items,
+        counters: [0; N],
+        i: 0,
+        started: false,
+    }
+}
+
+/// [`heap_permutations`] of a slice, which must have `N` items.
+pub fn heap_permutations_of<T: Clone, const N: usize>(
+    items: &[T],
+) -> Option<HeapPermutations<T, N>> {
+    if items.len() != N {
+        return None;
+    }
+
+    Some(heap_permutations(array::from_fn(|i| items[i].clone())))
+}
+
+/// Every choice of `K` items from `items`, in the order they come in.
+pub fn combinations<T: Clone, const K: usize>(items: &[T]) -> Combinations<'_, T, K> {
+    Combinations {
+        items,
+        indices: array::from_fn(|i| i),
+        done: K > items.len(),
+    }
+}
+
+/// Every array of `N` items from `items`, with repeats.
+pub fn product<T: Clone, const N: usize>(items: &[T]) -> Product<'_, T, N> {
+    Product {
+        items,
+        indices: [0; N],
+        done: N > 0 && items.is_empty(),
+    }
+}
+
+/// Every array of `N` items from `items` that never goes back, such as
+/// the digits of numbers that never decrease.
+pub fn non_decreasing<T: Clone, const N: usize>(items: &[T]) -> NonDecreasing<'_, T, N> {
+    NonDecreasing {
+        items,
+        indices: [0; N],
+        done: N > 0 && items.is_empty(),
+    }
+}
+
+fn pick<T: Clone, const N: usize>(items: &[T], indices: &[usize; N]) -> [T; N] {
+    indices.map(|i| items[i].clone())
+}
+
+pub struct Permutations<'a, T, const N: usize> {
+    /// Exactly `N` items.
+    items: &'a [T],
+    indices: [usize; N],
+    done: bool,
+}
+
+impl<T: Clone, const N: usize> Iterator for Permutations<'_, T, N> {
+    type Item = [T; N];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let item = pick(self.items, &self.indices);
+
+        // The last point where the indices go up.
+        let indices = &mut self.indices;
+        match (1..N).rev().find(|&i| indices[i - 1] < indices[i]) {
+            Some(i) => {
+                let j = (i..N).rev().find(|&j| indices[i - 1] < indices[j]).unwrap();
+                indices.swap(i - 1, j);
+                indices[i..].reverse();
+            }
+            None => self.done = true,
+        }
+
+        Some(item)
+    }
+}
+
+impl<T: Clone, const N: usize> FusedIterator for Permutations<'_, T, N> {}
+
+pub struct HeapPermutations<T, const N: usize> {
+    items: [T; N],
+    counters: [usize; N],
+    i: usize,
+    started: bool,
+}
+
+impl<T: Clone, const N: usize> Iterator for HeapPermutations<T, N> {
+    type Item = [T; N];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            return Some(self.items.clone());
+        }
+
+        while self.i < N {
+            let i = self.i;
+
+            if self.counters[i] < i {
+                let j = if i % 2 == 1 { self.counters[i] } else { 0 };
+                self.items.swap(j, i);
+                self.counters[i] += 1;
+                self.i = 0;
+
+                return Some(self.items.clone());
+            }
+
+            self.counters[i] = 0;
+            self.i += 1;
+        }
+
+        None
+    }
+}
+
+impl<T: Clone, const N: usize> FusedIterator for HeapPermutations<T, N> {}
+
+pub struct Combinations<'a, T, const K: usize> {
+    items: &'a [T],
+    indices: [usize; K],
+    done: bool,
+}
+
+impl<T: Clone, const K: usize> Iterator for Combinations<'_, T, K> {
+    type Item = [T; K];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let item = pick(self.items, &self.indices);
+
+        // The last index that can still move up, leaving room for the ones after it.
+        let (indices, len) = (&mut self.indices, self.items.len());
+        match (0..K).rev().find(|&i| indices[i] < len - K + i) {
+            Some(i) => {
+                indices[i] += 1;
+                for j in i + 1..K {
+                    indices[j] = indices[j - 1] + 1;
+                }
+            }
+            None => self.done = true,
+        }
+
+        Some(item)
+    }
+}
+
+impl<T: Clone, const K: usize> FusedIterator for Combinations<'_, T, K> {}
+
+pub struct Product<'a, T, const N: usize> {
+    items: &'a [T],
+    indices: [usize; N],
+    done: bool,
+}
+
+impl<T: Clone, const N: usize> Iterator for Product<'_, T, N> {
+    type Item = [T; N];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let item = pick(self.items, &self.indices);
+
+        let (indices, len) = (&mut self.indices, self.items.len());
+        match (0..N).rev().find(|&i| indices[i] + 1 < len) {
+            Some(i) => {
+                indices[i] += 1;
+                indices[i + 1..].fill(0);
+            }
+            None => self.done = true,
+        }
+
+        Some(item)
+    }
+}
+
+impl<T: Clone, const N: usize> FusedIterator for Product<'_, T, N> {}
+
+pub struct NonDecreasing<'a, T, const N: usize> {
+    items: &'a [T],
+    indices: [usize; N],
+    done: bool,
+}
+
+impl<T: Clone, const N: usize> Iterator for NonDecreasing<'_, T, N> {
+    type Item = [T; N];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let item = pick(self.items, &self.indices);
+
+        let (indices, len) = (&mut self.indices, self.items.len());
+        match (0..N).rev().find(|&i| indices[i] + 1 < len) {
+            Some(i) => {
+                let index = indices[i] + 1;
+                indices[i..].fill(index);
+            }
+            None => self.done = true,
+        }
+
+        Some(item)
+    }
+}
+
+impl<T: Clone, const N: usize> FusedIterator for NonDecreasing<'_, T, N> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permutations_in_order() {
+        let all = permutations(&['a', 'b', 'c']).collect::<Vec<_>>();
+        assert_eq!(
+            all,
+            [
+                ['a', 'b', 'c'],
+                ['a', 'c', 'b'],
+                ['b', 'a', 'c'],
+                ['b', 'c', 'a'],
+                ['c', 'a', 'b'],
+                ['c', 'b', 'a'],
+            ]
+        );
+
+        assert_eq!(permutations(&[0; 0]).count(), 1);
+        assert_eq!(permutations(&[0, 1, 2, 3, 4]).count(), 120);
+    }
+
+    #[test]
+    fn permutations_of_slices() {
+        let items = vec![3, 1, 2];
+
+        let all = permutations_of::<_, 3>(&items).unwrap().collect::<Vec<_>>();
+        assert_eq!(all, permutations(&[3, 1, 2]).collect::<Vec<_>>());
+        assert!(permutations_of::<_, 2>(&items).is_none());
+        assert!(permutations_of::<_, 4>(&items).is_none());
+
+        let mut all = heap_permutations_of::<_, 3>(&items)
+            .unwrap()
+            .collect::<Vec<_>>();
+        all.sort_unstable();
+        assert_eq!(all, permutations(&[1, 2, 3]).collect::<Vec<_>>());
+        assert!(heap_permutations_of::<_, 2>(&items).is_none());
+    }
+
+    #[test]
+    fn heap_permutations_swap_once() {
+        let mut all = heap_permutations([0, 1, 2, 3, 4]).collect::<Vec<_>>();
+
+        for pair in all.windows(2) {
+            let differ = (0..5).filter(|&i| pair[0][i] != pair[1][i]).count();
+            assert_eq!(differ, 2);
+        }
+
+        all.sort_unstable();
+        assert_eq!(all, permutations(&[0, 1, 2, 3, 4]).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn combinations_and_products() {
+        let all = combinations::<_, 2>(&[1, 2, 3, 4]).collect::<Vec<_>>();
+        assert_eq!(all, [[1, 2], [1, 3], [1, 4], [2, 3], [2, 4], [3, 4]]);
+        assert_eq!(combinations::<_, 5>(&[1, 2, 3, 4]).count(), 0);
+        assert_eq!(combinations::<i32, 0>(&[]).count(), 1);
+
+        let all = product::<_, 2>(&[0, 1]).collect::<Vec<_>>();
+        assert_eq!(all, [[0, 0], [0, 1], [1, 0], [1, 1]]);
+        assert_eq!(product::<i32, 3>(&[]).count(), 0);
+
+        let all = non_decreasing::<_, 2>(&[0, 1, 2]).collect::<Vec<_>>();
+        assert_eq!(all, [[0, 0], [0, 1], [0, 2], [1, 1], [1, 2], [2, 2]]);
+        assert_eq!(
+            non_decreasing::<_, 6>(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]).count(),
+            5005
+        );
+    }
+}
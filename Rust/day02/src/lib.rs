@@ -0,0 +1,42 @@
+#[derive(Eq, PartialEq)]
+enum Opcode {
+    Add,
+    Mul,
+    Halt,
+}
+
+impl From<usize> for Opcode {
+    fn from(n: usize) -> Self {
+        match n {
+            1 => Self::Add,
+            2 => Self::Mul,
+            99 => Self::Halt,
+            _ => panic!("Invalid opcode: {}", n),
+        }
+    }
+}
+
+pub fn run(program: &mut [usize], noun: usize, verb: usize) -> usize {
+    let mut ip = 0;
+    program[1] = noun;
+    program[2] = verb;
+
+    loop {
+        let opcode = Opcode::from(program[ip]);
+        let (lhs_place, rhs_place, result_place) = match opcode {
+            Opcode::Halt => break,
+            Opcode::Add | Opcode::Mul => (program[ip + 1], program[ip + 2], program[ip + 3]),
+        };
+
+        let result = match opcode {
+            Opcode::Add => program[lhs_place] + program[rhs_place],
+            Opcode::Mul => program[lhs_place] * program[rhs_place],
+            Opcode::Halt => unreachable!(),
+        };
+
+        program[result_place] = result;
+        ip += 4;
+    }
+
+    program[0]
+}
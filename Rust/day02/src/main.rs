@@ -1,73 +1,46 @@
 static PUZZLE: &'static str = include_str!(r"..\..\..\Inputs\day02.txt");
 
-fn parse(s: &str) -> Vec<usize> {
-    s.split(",")
-        .map(|w| w.parse::<usize>().unwrap())
-        .collect::<Vec<_>>()
-}
-
-#[derive(Eq, PartialEq)]
-enum Opcode {
-    Add,
-    Mul,
-    Halt,
-}
+use std::convert::TryFrom;
 
-impl From<usize> for Opcode {
-    fn from(n: usize) -> Self {
-        match n {
-            1 => Self::Add,
-            2 => Self::Mul,
-            99 => Self::Halt,
-            _ => panic!(format!("Invalid opcode: {}", n)),
-        }
-    }
-}
-
-fn run(program: &mut [usize], noun: usize, verb: usize) -> usize {
-    let mut ip = 0;
-    program[1] = noun;
-    program[2] = verb;
-
-    loop {
-        let opcode = Opcode::from(program[ip]);
-        let (lhs_place, rhs_place, result_place) = match opcode {
-            Opcode::Halt => break,
-            Opcode::Add | Opcode::Mul => (program[ip + 1], program[ip + 2], program[ip + 3]),
-        };
-
-        let result = match opcode {
-            Opcode::Add => program[lhs_place] + program[rhs_place],
-            Opcode::Mul => program[lhs_place] * program[rhs_place],
-            Opcode::Halt => unreachable!(),
-        };
-
-        program[result_place] = result;
-        ip += 4;
-    }
+use day02::run;
+use intcode::{
+    program::Program,
+    symbolic::{Constraint, End, Executor},
+};
 
-    program[0]
+fn parse(s: &str) -> Vec<usize> {
+    Program::parse(s)
+        .expect("Invalid program")
+        .words()
+        .iter()
+        .map(|&word| usize::try_from(word).expect("Negative word"))
+        .collect()
 }
 
 fn part1(s: &str) -> usize {
     run(&mut parse(s), 12, 2)
 }
 
-fn part2(s: &str) -> usize {
-    const MAGIC_NUMBER: usize = 19690720;
-    let program = parse(s);
-
-    let mut clone = Vec::with_capacity(program.len());
+fn part2(s: &str) -> isize {
+    const MAGIC_NUMBER: isize = 19690720;
+    let program = Program::parse(s).expect("Invalid program");
 
-    (0..=99)
-        .flat_map(|noun| (0..=99).map(move |verb| (noun, verb)))
-        .find(|(noun, verb)| {
-            clone.clear();
-            clone.extend_from_slice(&program);
+    let mut executor = Executor::new(program.words());
+    let noun = executor.symbol(0..=99);
+    let verb = executor.symbol(0..=99);
+    executor.set(1, noun);
+    executor.set(2, verb);
 
-            run(&mut clone, *noun, *verb) == MAGIC_NUMBER
+    executor
+        .explore()
+        .iter()
+        .find_map(|path| match &path.end {
+            End::Halted(memory) => {
+                executor.solve(path, &[Constraint::equal(&memory[0], &MAGIC_NUMBER.into())])
+            }
+            _ => None,
         })
-        .map(|(noun, verb)| 100 * noun + verb)
+        .map(|values| 100 * values[noun.index()] + values[verb.index()])
         .unwrap()
 }
 
@@ -77,3 +50,33 @@ fn main() {
 
     println!("Part 1: {}\nPart 2: {}", p1, p2);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use intcode::{
+        future::{sink::Stdout, stream::empty, FutureExt},
+        machine::Machine,
+        program::Patch,
+        search::Search,
+    };
+
+    #[test]
+    fn brute_force_agrees() {
+        let program = Program::parse(PUZZLE).expect("Invalid program");
+        let candidates = (0..=99).flat_map(|noun| (0..=99).map(move |verb| (noun, verb)));
+
+        let found = Search::new().find_first(candidates, |&(noun, verb)| {
+            let patches = [Patch::new(1, noun), Patch::new(2, verb)];
+            let memory = program.clone().patched(&patches).ok()?.into_vec();
+
+            let mut machine = Machine::new(memory, empty(), Stdout::new());
+            machine.execute().ok()?;
+            Some(machine.into_memory()[0]).filter(|&out| out == 19690720)
+        });
+
+        let ((noun, verb), _) = found.unwrap();
+        assert_eq!(100 * noun + verb, part2(PUZZLE));
+    }
+}
@@ -4,41 +4,30 @@ const END: [u8; 6] = [6, 1, 2, 5, 6, 4];
 mod group;
 use group::Grouped;
 
-macro_rules! multi_for {
-    ([$name:ident $(,$names:ident)*] in [$e:expr $(,$es:expr)*] $b:block) => {
-        for $name in $e {
-            multi_for!([$($names),*] in [$($es),*] $b);
-        }
-    };
-
-    ([] in [] $b:block) => {$b}
-}
-
-fn loopy<F: Fn(u8, u8, u8, u8, u8, u8) -> bool>(func: F) -> usize {
-    let mut valids = 0;
-
-    multi_for! {
-        [a, b, c, d, e, f] in [0..10, a..10, b..10, c..10, d..10, e..10] {
-            if [a, b, c, d, e, f] < BEGIN {
-                continue;
-            }
-            if [a, b, c, d, e, f] > END {
-                return valids;
-            }
-            if func(a, b, c, d, e, f) {
-                valids += 1;
-            }
-        }
-    };
-    valids
+use combinatorics::non_decreasing;
+
+/// Counts the passwords between `begin` and `end` whose digits never
+/// decrease, and that `func` accepts.
+fn loopy<F: Fn(&[u8]) -> bool, const N: usize>(begin: [u8; N], end: [u8; N], func: F) -> usize {
+    const DIGITS: [u8; 10] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+
+    non_decreasing(&DIGITS)
+        .skip_while(|digits| *digits < begin)
+        .take_while(|digits| *digits <= end)
+        .filter(|digits| func(digits))
+        .count()
 }
 
 fn part1() -> usize {
-    loopy(|a, b, c, d, e, f| a == b || b == c || c == d || d == e || e == f)
+    loopy(BEGIN, END, |digits| {
+        digits.windows(2).any(|pair| pair[0] == pair[1])
+    })
 }
 
 fn part2() -> usize {
-    loopy(|a, b, c, d, e, f| Grouped::groups(&[a, b, c, d, e, f]).any(|group| group.len() == 2))
+    loopy(BEGIN, END, |digits| {
+        Grouped::groups(digits).any(|group| group.len() == 2)
+    })
 }
 
 fn main() {
@@ -0,0 +1,178 @@
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ParameterMode {
+    Position,
+    Immediate,
+}
+
+impl ParameterMode {
+    const fn from(mode: isize) -> Self {
+        [Self::Position, Self::Immediate][mode as usize]
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Opcode {
+    Add,
+    Mul,
+    Save,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    Halt,
+}
+
+pub enum ExecuteResult {
+    Continue,
+    Output(isize),
+    Exit,
+}
+
+const fn modes_of(n: isize) -> [ParameterMode; 3] {
+    [
+        ParameterMode::from(n / 100 % 10),
+        ParameterMode::from(n / 1000 % 10),
+        ParameterMode::from(n / 10_000 % 10),
+    ]
+}
+
+fn read_value(program: &[isize], value: isize, mode: ParameterMode) -> isize {
+    match mode {
+        ParameterMode::Immediate => value,
+        ParameterMode::Position => {
+            assert!(value > -1);
+            program[value as usize]
+        }
+    }
+}
+
+impl Opcode {
+    fn operands(&self) -> usize {
+        match self {
+            Self::Add => 3,
+            Self::Mul => 3,
+            Self::Save => 1,
+            Self::Output => 1,
+            Self::JumpIfTrue => 2,
+            Self::JumpIfFalse => 2,
+            Self::LessThan => 3,
+            Self::Equals => 3,
+            Self::Halt => 1,
+        }
+    }
+
+    pub fn execute(&self, ip: &mut isize, program: &mut [isize], sysid: isize) -> ExecuteResult {
+        let mut result = ExecuteResult::Continue;
+
+        let _ip = *ip as usize;
+        let modes = modes_of(program[_ip]);
+
+        match self {
+            Opcode::Add => {
+                let lhs = program[_ip + 1];
+                let rhs = program[_ip + 2];
+                let write_place = program[_ip + 3] as usize;
+
+                program[write_place] =
+                    read_value(program, lhs, modes[0]) + read_value(program, rhs, modes[1]);
+            }
+
+            Opcode::Mul => {
+                let lhs = program[_ip + 1];
+                let rhs = program[_ip + 2];
+                let write_place = program[_ip + 3] as usize;
+
+                program[write_place] =
+                    read_value(program, lhs, modes[0]) * read_value(program, rhs, modes[1]);
+            }
+            Opcode::Save => {
+                let write_place = program[_ip + 1] as usize;
+                program[write_place] = sysid;
+            }
+            Opcode::Output => {
+                result = ExecuteResult::Output(read_value(program, program[_ip + 1], modes[0]));
+            }
+            Opcode::JumpIfTrue => {
+                let checkme = program[_ip + 1];
+
+                if read_value(program, checkme, modes[0]) != 0 {
+                    let jmp = program[_ip + 2];
+                    let jmp = read_value(program, jmp, modes[1]);
+                    *ip = jmp;
+                    return ExecuteResult::Continue;
+                }
+            }
+            Opcode::JumpIfFalse => {
+                let checkme = program[_ip + 1];
+
+                if read_value(program, checkme, modes[0]) == 0 {
+                    let jmp = program[_ip + 2];
+                    let jmp = read_value(program, jmp, modes[1]);
+                    *ip = jmp;
+                    return ExecuteResult::Continue;
+                }
+            }
+            Opcode::LessThan => {
+                let lhs = program[_ip + 1];
+                let rhs = program[_ip + 2];
+                let write_place = program[_ip + 3] as usize;
+
+                if read_value(program, lhs, modes[0]) < read_value(program, rhs, modes[1]) {
+                    program[write_place] = 1;
+                } else {
+                    program[write_place] = 0;
+                }
+            }
+            Opcode::Equals => {
+                let lhs = program[_ip + 1];
+                let rhs = program[_ip + 2];
+                let write_place = program[_ip + 3] as usize;
+
+                if read_value(program, lhs, modes[0]) == read_value(program, rhs, modes[1]) {
+                    program[write_place] = 1;
+                } else {
+                    program[write_place] = 0;
+                }
+            }
+            Opcode::Halt => result = ExecuteResult::Exit,
+        }
+
+        *ip += self.operands() as isize + 1;
+
+        result
+    }
+}
+
+impl From<isize> for Opcode {
+    fn from(n: isize) -> Self {
+        match n {
+            1 => Self::Add,
+            2 => Self::Mul,
+            3 => Self::Save,
+            4 => Self::Output,
+            5 => Self::JumpIfTrue,
+            6 => Self::JumpIfFalse,
+            7 => Self::LessThan,
+            8 => Self::Equals,
+            99 => Self::Halt,
+            n => panic!("Invalid opcode: {}", n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_modes() {
+        assert_eq!(
+            modes_of(1002),
+            [
+                ParameterMode::Position,
+                ParameterMode::Immediate,
+                ParameterMode::Position
+            ]
+        );
+    }
+}
@@ -0,0 +1,25 @@
+//! Compiles the puzzle input to Rust, see `intcode::transpile`.
+
+use std::{env, fs, path::Path};
+
+use intcode::{program::Program, transpile::transpile};
+
+fn main() {
+    let manifest = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let input = Path::new(&manifest)
+        .join("..")
+        .join("..")
+        .join("Inputs")
+        .join("day07.txt");
+    println!("cargo:rerun-if-changed={}", input.display());
+
+    // The amplifiers are compiled from the input, so there is nothing to
+    // build without it.
+    let program = Program::load(&input).unwrap_or_else(|e| {
+        eprintln!("Couldn't compile the amplifiers: {}", e);
+        std::process::exit(1);
+    });
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("amplifier.rs");
+    fs::write(out, transpile(program.words()).to_string()).unwrap();
+}
@@ -1,127 +1,55 @@
-static PUZZLE: &'static str = include_str!(r"..\..\..\Inputs\day07.txt");
+use combinatorics::permutations;
+use intcode::{pipeline::Pipeline, search::Search};
 
-fn parse_input(s: &str) -> Vec<isize> {
-    s.split(",").map(|w| w.parse().unwrap()).collect::<Vec<_>>()
+/// The puzzle input, compiled to Rust by the build script.
+#[allow(dead_code)]
+mod amplifier {
+    include!(concat!(env!("OUT_DIR"), "/amplifier.rs"));
 }
 
-use intcode::{
-    channel::Channel,
-    future::{
-        sink::Stdout,
-        stream::{once, Stream, StreamExt},
-        FutureExt, Poll,
-    },
-    machine::Machine,
-};
-
-use std::cmp;
-
-fn run_settings(program: Vec<isize>, settings: &[isize]) -> isize {
-    let mut signal = 0;
-
-    for setting in settings {
-        let mut stdout = Stdout::new();
-
-        let mut machine = Machine::new(
-            program.clone(),
-            once(*setting).chain(once(signal)),
-            &mut stdout,
-        );
-        let _ = machine.execute();
-
-        signal = stdout.into_inner().unwrap();
-    }
-
-    signal
-}
-
-macro_rules! multi_for {
-    ([$name:ident $(,$names:ident)*] in [$e:expr $(,$es:expr)*] $b:block) => {
-        for $name in $e {
-            multi_for!([$($names),*] in [$($es),*] $b);
-        }
-    };
-
-    ([] in [] $b:block) => {$b}
+/// The inputs of every amplifier: its setting, and a signal of 0 for the
+/// first one.
+fn inputs(settings: &[isize]) -> Vec<Vec<isize>> {
+    let mut inputs = settings.iter().map(|&s| vec![s]).collect::<Vec<_>>();
+    inputs[0].push(0);
+    inputs
 }
 
-macro_rules! filter {
-    ($name:ident $(,$names:ident)*) => {
-        false $(|| $name == $names)* || filter!($($names),*)
-    };
-    () => {false}
+fn run_settings_compiled(settings: &[isize]) -> isize {
+    Pipeline::chain(inputs(settings))
+        .run_with(|_, input, output| amplifier::new(input, output))
+        .last(settings.len() - 1)
+        .unwrap()
 }
 
-fn part1(s: &str) -> isize {
-    let program = parse_input(s);
-
-    let mut highest = 0;
-    multi_for! {
-        [a, b, c, d, e] in [0..=4, 0..=4, 0..=4, 0..=4, 0..=4] {
-            if filter!(a, b, c, d, e) { continue }
-            let output = run_settings(program.clone(), &[a, b, c, d, e]);
-            highest = cmp::max(highest, output);
-        }
-    }
+/// The highest signal `run` gets out of any order of `settings`.
+fn highest<const N: usize>(settings: [isize; N], run: fn(&[isize]) -> isize) -> isize {
+    let (_, highest) = Search::new()
+        .best(permutations(&settings), |order| Some(run(order)))
+        .unwrap();
 
     highest
 }
 
-fn channeled_run(program: Vec<isize>, seq: [isize; 5]) -> isize {
-    match seq {
-        [a, b, c, d, e] => {
-            let c_a = Channel::empty();
-            let c_b = Channel::empty();
-            let c_c = Channel::empty();
-            let c_d = Channel::empty();
-            let c_e = Channel::empty();
-
-            let (a_transmit, b_recv) = c_a.split();
-            let (b_transmit, c_recv) = c_b.split();
-            let (c_transmit, d_recv) = c_c.split();
-            let (d_transmit, e_recv) = c_d.split();
-            let (e_transmit, mut a_recv) = c_e.split();
-
-            let a = Machine::new(
-                program.clone(),
-                once(a).chain(once(0)).chain(a_recv),
-                a_transmit,
-            );
-            let b = Machine::new(program.clone(), once(b).chain(b_recv), b_transmit);
-            let c = Machine::new(program.clone(), once(c).chain(c_recv), c_transmit);
-            let d = Machine::new(program.clone(), once(d).chain(d_recv), d_transmit);
-            let e = Machine::new(program.clone(), once(e).chain(e_recv), e_transmit);
-
-            let mut joined = a.join5(b, c, d, e);
-
-            let _ = joined.execute();
-            match a_recv.poll_next() {
-                Poll::Ready(Some(elem)) => return elem,
-                _ => panic!(),
-            }
-        }
-    }
+fn part1() -> isize {
+    highest([0, 1, 2, 3, 4], run_settings_compiled)
 }
 
-fn part2(s: &str) -> isize {
-    let program = parse_input(s);
-
-    let mut highest = 0;
-
-    multi_for! {
-        [a, b, c, d, e] in [5..=9, 5..=9, 5..=9, 5..=9, 5..=9] {
-            if filter!(a, b, c, d, e) { continue }
-            let output = channeled_run(program.clone(), [a, b, c, d, e]);
-            highest = cmp::max(highest, output);
-        }
-    }
+/// Runs the amplifiers in a feedback loop, and returns the last signal.
+fn channeled_run_compiled(seq: &[isize]) -> isize {
+    Pipeline::ring(inputs(seq))
+        .run_with(|_, input, output| amplifier::new(input, output))
+        .last(seq.len() - 1)
+        .unwrap()
+}
 
-    highest
+fn part2() -> isize {
+    highest([5, 6, 7, 8, 9], channeled_run_compiled)
 }
 
 fn main() {
-    let p1 = part1(PUZZLE);
-    let p2 = part2(PUZZLE);
+    let p1 = part1();
+    let p2 = part2();
     println!("Part 1: {}\nPart 2: {}", p1, p2);
 }
 
@@ -129,12 +57,34 @@ fn main() {
 mod tests {
     use super::*;
 
+    use intcode::{image::Image, program::Program};
+
+    static PUZZLE: &'static str = include_str!(r"..\..\..\Inputs\day07.txt");
+
+    fn parse_input(s: &str) -> Image {
+        Program::parse(s).expect("Invalid program").into()
+    }
+
+    fn run_settings(program: &Image, settings: &[isize]) -> isize {
+        Pipeline::chain(inputs(settings))
+            .run(program.clone())
+            .last(settings.len() - 1)
+            .unwrap()
+    }
+
+    fn channeled_run(program: &Image, seq: &[isize]) -> isize {
+        Pipeline::ring(inputs(seq))
+            .run(program.clone())
+            .last(seq.len() - 1)
+            .unwrap()
+    }
+
     #[test]
     fn assert_me() {
         let out = run_settings(
-            vec![
+            &Image::new(vec![
                 3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
-            ],
+            ]),
             &[4, 3, 2, 1, 0],
         );
 
@@ -144,14 +94,29 @@ mod tests {
     #[test]
     fn assert_channeled_run() {
         let out = channeled_run(
-            vec![
+            &Image::new(vec![
                 3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001,
                 54, -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53,
                 55, 53, 4, 53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
-            ],
-            [9, 7, 8, 5, 6],
+            ]),
+            &[9, 7, 8, 5, 6],
         );
 
         assert_eq!(out, 18216);
     }
+
+    #[test]
+    fn compiled_matches_interpreter() {
+        let program = parse_input(PUZZLE);
+
+        for settings in permutations(&[0, 1, 2, 3, 4]) {
+            assert_eq!(
+                run_settings_compiled(&settings),
+                run_settings(&program, &settings)
+            );
+
+            let seq = settings.map(|setting| setting + 5);
+            assert_eq!(channeled_run_compiled(&seq), channeled_run(&program, &seq));
+        }
+    }
 }
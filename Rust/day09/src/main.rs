@@ -6,11 +6,12 @@ use intcode::{
         FutureExt,
         stream::{once},
         sink::{Stdout},
-    }
+    },
+    program::Program,
 };
 
 fn parse_input(s: &str) -> Vec<isize> {
-    s.split(",").map(|w| w.parse().unwrap()).collect::<Vec<_>>()
+    Program::parse(s).expect("Invalid program").into_vec()
 }
 
 fn part1(v: Vec<isize>) -> isize {
@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum Mode {
     Position,
@@ -38,7 +40,7 @@ impl From<isize> for Op {
             8 => Self::Equals,
             9 => Self::AdjustBase,
             99 => Self::Halt,
-            n => panic!(format!("Invalid opcode: {}", n)),
+            n => panic!("Invalid opcode: {}", n),
         }
     }
 }
@@ -82,6 +84,12 @@ impl RunError {
     }
 }
 
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.s)
+    }
+}
+
 #[derive(Debug)]
 pub struct Machine {
     ip: usize,
@@ -183,7 +191,7 @@ pub trait Intcode: Read + Write + Memory {
         Opcode::parse(self.read(self.ip()))
     }
 
-    fn add(&mut self, modes: &[Mode]) -> Poll<isize> {
+    fn add(&mut self, modes: &[Mode]) -> Poll<'_, isize> {
         let i1 = self.read_arg(1, modes[0]);
         let i2 = self.read_arg(2, modes[1]);
         self.write_arg(3, i1 + i2, modes[2]);
@@ -192,7 +200,7 @@ pub trait Intcode: Read + Write + Memory {
         Poll::Running
     }
 
-    fn mul(&mut self, modes: &[Mode]) -> Poll<isize> {
+    fn mul(&mut self, modes: &[Mode]) -> Poll<'_, isize> {
         let i1 = self.read_arg(1, modes[0]);
         let i2 = self.read_arg(2, modes[1]);
         self.write_arg(3, i1 * i2, modes[2]);
@@ -201,7 +209,7 @@ pub trait Intcode: Read + Write + Memory {
         Poll::Running
     }
 
-    fn jump_if_false(&mut self, modes: &[Mode]) -> Poll<isize> {
+    fn jump_if_false(&mut self, modes: &[Mode]) -> Poll<'_, isize> {
         let cond = self.read_arg(1, modes[0]);
         let ip = self.read_arg(2, modes[1]);
 
@@ -214,7 +222,7 @@ pub trait Intcode: Read + Write + Memory {
         Poll::Running
     }
 
-    fn jump_if_true(&mut self, modes: &[Mode]) -> Poll<isize> {
+    fn jump_if_true(&mut self, modes: &[Mode]) -> Poll<'_, isize> {
         let cond = self.read_arg(1, modes[0]);
         let ip = self.read_arg(2, modes[1]);
 
@@ -227,7 +235,7 @@ pub trait Intcode: Read + Write + Memory {
         Poll::Running
     }
 
-    fn less_than(&mut self, modes: &[Mode]) -> Poll<isize> {
+    fn less_than(&mut self, modes: &[Mode]) -> Poll<'_, isize> {
         let i1 = self.read_arg(1, modes[0]);
         let i2 = self.read_arg(2, modes[1]);
 
@@ -238,7 +246,7 @@ pub trait Intcode: Read + Write + Memory {
         Poll::Running
     }
 
-    fn equals(&mut self, modes: &[Mode]) -> Poll<isize> {
+    fn equals(&mut self, modes: &[Mode]) -> Poll<'_, isize> {
         let i1 = self.read_arg(1, modes[0]);
         let i2 = self.read_arg(2, modes[1]);
 
@@ -250,7 +258,7 @@ pub trait Intcode: Read + Write + Memory {
         Poll::Running
     }
 
-    fn save(&mut self, modes: &[Mode]) -> Poll<isize> {
+    fn save(&mut self, modes: &[Mode]) -> Poll<'_, isize> {
         let offset = match modes[0] {
             Mode::Relative => self.base() + self.read(self.ip() + 1),
             Mode::Position => self.read(self.ip() + 1),
@@ -262,17 +270,17 @@ pub trait Intcode: Read + Write + Memory {
         out
     }
 
-    fn output(&mut self, modes: &[Mode]) -> Poll<isize> {
+    fn output(&mut self, modes: &[Mode]) -> Poll<'_, isize> {
         let out = Poll::Output(self.read_arg(1, modes[0]));
         self.set_ip(self.ip() + 2);
         out
     }
 
-    fn halt(&self) -> Poll<isize> {
+    fn halt(&self) -> Poll<'_, isize> {
         Poll::Exit
     }
 
-    fn adjust_base(&mut self, modes: &[Mode]) -> Poll<isize> {
+    fn adjust_base(&mut self, modes: &[Mode]) -> Poll<'_, isize> {
         let new_base = self.read_arg(1, modes[0]);
         self.set_base(self.base() + new_base);
         self.set_ip(self.ip() + 2);
@@ -280,7 +288,7 @@ pub trait Intcode: Read + Write + Memory {
         Poll::Running
     }
 
-    fn step(&mut self) -> Poll<isize> {
+    fn step(&mut self) -> Poll<'_, isize> {
         let Opcode { opcode, modes } = self.opcode();
         match opcode {
             Op::Add => self.add(&modes),
@@ -329,17 +337,3 @@ impl Machine {
         }
     }
 }
-
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-
-struct Robot {
-    brain: Machine,
-    facing: Direction,
-    x: isize,
-    y: isize,
-}
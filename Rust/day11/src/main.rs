@@ -1,21 +1,20 @@
 static PUZZLE: &'static str = include_str!(r"..\..\..\Inputs\day11.txt");
 
-mod intcode;
-use intcode::Machine;
-
 mod robot;
 use robot::{Cell, Color, Robot};
 
 use std::collections::HashMap;
 
+use intcode::program::Program;
+
 fn parse_input(s: &str) -> Vec<isize> {
-    s.split(",").map(|w| w.parse().unwrap()).collect::<Vec<_>>()
+    Program::parse(s).expect("Invalid program").into_vec()
 }
 
 fn part1(program: Vec<isize>) -> usize {
     let mut machine = Robot::new(program, HashMap::new());
 
-    while !machine.step() {}
+    while !machine.step().unwrap_or_else(|e| panic!("{}", e)) {}
 
     machine
         .visited
@@ -33,7 +32,7 @@ fn part2(program: Vec<isize>) {
 
     let mut machine = Robot::new(program, map);
 
-    while !machine.step() {}
+    while !machine.step().unwrap_or_else(|e| panic!("{}", e)) {}
 
     let (max_x, max_y) = machine
         .visited
@@ -1,7 +1,10 @@
-use super::intcode::{Intcode, Poll};
-use super::Machine;
+use intcode::{
+    future::frame::{Decode, Framer},
+    machine::{Machine, MachineError},
+    resume::{PendingInput, PendingOutput, Resume},
+};
 
-use std::collections::HashMap;
+use std::{collections::HashMap, convert::TryFrom, fmt};
 
 #[derive(Copy, Clone, Debug)]
 pub enum Color {
@@ -10,14 +13,6 @@ pub enum Color {
 }
 
 impl Color {
-    fn from_int(n: isize) -> Self {
-        match n {
-            0 => Self::Black,
-            1 => Self::White,
-            n => panic!(format!("Invalid color: {}", n)),
-        }
-    }
-
     fn to_int(self) -> isize {
         match self {
             Self::Black => 0,
@@ -26,6 +21,18 @@ impl Color {
     }
 }
 
+impl TryFrom<isize> for Color {
+    type Error = InvalidOutput;
+
+    fn try_from(n: isize) -> Result<Self, Self::Error> {
+        match n {
+            0 => Ok(Self::Black),
+            1 => Ok(Self::White),
+            n => Err(InvalidOutput::Color(n)),
+        }
+    }
+}
+
 enum Direction {
     Up,
     Down,
@@ -34,36 +41,103 @@ enum Direction {
 }
 
 impl Direction {
-    fn from_int(n: isize) -> Self {
+    fn turn(&mut self, turn: Turn) {
+        *self = match (turn, &self) {
+            (Turn::Left, Self::Up) | (Turn::Right, Self::Down) => Self::Left,
+            (Turn::Left, Self::Left) | (Turn::Right, Self::Right) => Self::Down,
+            (Turn::Left, Self::Down) | (Turn::Right, Self::Up) => Self::Right,
+            (Turn::Left, Self::Right) | (Turn::Right, Self::Left) => Self::Up,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Turn {
+    Left,
+    Right,
+}
+
+impl TryFrom<isize> for Turn {
+    type Error = InvalidOutput;
+
+    fn try_from(n: isize) -> Result<Self, Self::Error> {
         match n {
-            0 => Self::Left,
-            1 => Self::Right,
-            n => panic!(format!("Invalid direction: {}", n)),
+            0 => Ok(Self::Left),
+            1 => Ok(Self::Right),
+            n => Err(InvalidOutput::Turn(n)),
         }
     }
+}
+
+#[derive(Debug)]
+pub enum InvalidOutput {
+    Color(isize),
+    Turn(isize),
+}
 
-    fn turn_left(&mut self) {
-        *self = match self {
-            Self::Up => Self::Left,
-            Self::Left => Self::Down,
-            Self::Down => Self::Right,
-            Self::Right => Self::Up,
+impl fmt::Display for InvalidOutput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Color(n) => write!(f, "Not a valid color: {}", n),
+            Self::Turn(n) => write!(f, "Not a valid turn: {}", n),
         }
     }
+}
+
+/// What the brain outputs, two words at a time: the color to paint the
+/// current panel, and which way to turn before moving on.
+struct Output {
+    color: Color,
+    turn: Turn,
+}
+
+impl Decode<isize, 2> for Output {
+    type Error = InvalidOutput;
+
+    fn decode([color, turn]: [isize; 2]) -> Result<Self, Self::Error> {
+        Ok(Self {
+            color: Color::try_from(color)?,
+            turn: Turn::try_from(turn)?,
+        })
+    }
+}
+
+/// Why the robot stopped before its brain halted.
+#[derive(Debug)]
+pub enum RobotError {
+    Machine(MachineError),
+    Output(InvalidOutput),
+    /// The brain halted with `words` words of an output of `expected`.
+    Incomplete {
+        words: usize,
+        expected: usize,
+    },
+}
 
-    fn turn_right(&mut self) {
-        *self = match self {
-            Self::Up => Self::Right,
-            Self::Right => Self::Down,
-            Self::Down => Self::Left,
-            Self::Left => Self::Up,
+impl fmt::Display for RobotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Machine(e) => write!(f, "The brain failed: {:?}", e),
+            Self::Output(e) => write!(f, "{}", e),
+            Self::Incomplete { words, expected } => write!(
+                f,
+                "The brain halted partway through an output: {} of {} words",
+                words, expected
+            ),
         }
     }
 }
 
-enum Output {
-    Paint,
-    Turn,
+impl From<MachineError> for RobotError {
+    fn from(e: MachineError) -> Self {
+        Self::Machine(e)
+    }
+}
+
+impl From<InvalidOutput> for RobotError {
+    fn from(e: InvalidOutput) -> Self {
+        Self::Output(e)
+    }
 }
 
 #[derive(Debug)]
@@ -78,10 +152,12 @@ impl Cell {
     }
 }
 
+type Brain = Machine<isize, PendingInput<isize>, PendingOutput<isize>>;
+
 pub struct Robot {
-    brain: Machine,
+    brain: Brain,
+    framer: Framer<isize, 2>,
     facing: Direction,
-    expecting: Output,
     x: isize,
     y: isize,
     pub visited: HashMap<(isize, isize), Cell>,
@@ -90,63 +166,62 @@ pub struct Robot {
 impl Robot {
     pub fn new(program: Vec<isize>, visited: HashMap<(isize, isize), Cell>) -> Self {
         Self {
-            brain: Machine::new(program),
+            brain: Machine::resumable(program),
+            framer: Framer::new(),
             facing: Direction::Up,
-            expecting: Output::Paint,
             x: 0,
             y: 0,
             visited,
         }
     }
 
-    pub fn step(&mut self) -> bool {
+    /// Runs the brain until it needs or gives a word, and acts on it.
+    /// Returns whether the brain halted.
+    pub fn step(&mut self) -> Result<bool, RobotError> {
         let current_color = self
             .visited
             .get(&(self.x, self.y))
             .map(|cell| cell.color)
             .unwrap_or(Color::Black);
 
-        let s = self.brain.step();
-        //dbg!(&s);
-        match s {
-            Poll::Running => {}
-            Poll::Output(o) => {
-                match self.expecting {
-                    Output::Paint => {
-                        let color = Color::from_int(o);
-                        let mut entry = self.visited.entry((self.x, self.y)).or_insert(Cell {
-                            color: Color::Black,
-                            visits: 0,
-                        });
-
-                        entry.color = color;
-                        entry.visits += 1;
-                        self.expecting = Output::Turn;
-                    }
-                    Output::Turn => {
-                        let direction = Direction::from_int(o);
-
-                        match direction {
-                            Direction::Left => self.facing.turn_left(),
-                            Direction::Right => self.facing.turn_right(),
-                            _ => panic!("No up or down possible as a turn"),
-                        }
-
-                        match self.facing {
-                            Direction::Up => self.y += 1,
-                            Direction::Down => self.y -= 1,
-                            Direction::Left => self.x -= 1,
-                            Direction::Right => self.x += 1,
-                        }
-                        //self.visited.entry((self.x, self.y)).or_insert(Cell { color: Color::Black, visits: 1});
-                        self.expecting = Output::Paint
-                    }
+        match self.brain.resume() {
+            Resume::Output(word) => {
+                if let Some(words) = self.framer.push(word) {
+                    let output = Output::decode(words)?;
+                    self.paint(output.color);
+                    self.facing.turn(output.turn);
+                    self.advance();
                 }
             }
-            Poll::Input(i) => *i = current_color.to_int(),
-            Poll::Exit => return true,
+            Resume::NeedInput(slot) => slot.provide(current_color.to_int()),
+            Resume::Halted => {
+                return match self.framer.pending() {
+                    0 => Ok(true),
+                    words => Err(RobotError::Incomplete { words, expected: 2 }),
+                };
+            }
+            Resume::Error(e) => return Err(e.into()),
         }
 
-        return false;
+        Ok(false)
+    }
+
+    fn paint(&mut self, color: Color) {
+        let entry = self
+            .visited
+            .entry((self.x, self.y))
+            .or_insert_with(|| Cell::new(Color::Black));
+
+        entry.color = color;
+        entry.visits += 1;
+    }
+
+    fn advance(&mut self) {
+        match self.facing {
+            Direction::Up => self.y += 1,
+            Direction::Down => self.y -= 1,
+            Direction::Left => self.x -= 1,
+            Direction::Right => self.x += 1,
+        }
     }
 }
@@ -0,0 +1,335 @@
+//! The cabinet as a game to play without a terminal, one move at a time,
+//! for bots and tests.
+
+use intcode::{
+    future::frame::{Decode, Framer},
+    machine::{Machine, MachineError},
+    program::{Patch, PatchError, Program},
+    resume::{PendingInput, PendingOutput, Resume},
+};
+
+use std::{convert::TryFrom, fmt};
+
+use crate::drawer::{InvalidTile, Output, Tile};
+
+/// The memory of the cabinet: `program`, and room to work in.
+pub fn padded(program: Program) -> Vec<isize> {
+    let mut memory = program.into_vec();
+    memory.extend_from_slice(&[0; 512]);
+    memory
+}
+
+/// The memory of the cabinet, set to play for free.
+pub fn free_play(program: Program) -> Result<Vec<isize>, PatchError> {
+    // Insert 2 quarters to play for free.
+    let program = program.patched(&[Patch::new(0, 2)])?;
+    Ok(padded(program))
+}
+
+/// Why a game stopped before it was over.
+#[derive(Debug)]
+pub enum ArcadeError {
+    Patch(PatchError),
+    Machine(MachineError),
+    Output(InvalidTile),
+    /// A tile was drawn off the screen.
+    OffScreen {
+        x: isize,
+        y: isize,
+    },
+    /// The cabinet halted with `words` words of an output of `expected`.
+    Incomplete {
+        words: usize,
+        expected: usize,
+    },
+}
+
+impl fmt::Display for ArcadeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Patch(e) => write!(f, "{}", e),
+            Self::Machine(e) => write!(f, "The cabinet failed: {:?}", e),
+            Self::Output(e) => write!(f, "{}", e),
+            Self::OffScreen { x, y } => write!(f, "Drew a tile off the screen: ({}, {})", x, y),
+            Self::Incomplete { words, expected } => write!(
+                f,
+                "The cabinet halted partway through an output: {} of {} words",
+                words, expected
+            ),
+        }
+    }
+}
+
+impl From<PatchError> for ArcadeError {
+    fn from(e: PatchError) -> Self {
+        Self::Patch(e)
+    }
+}
+
+impl From<MachineError> for ArcadeError {
+    fn from(e: MachineError) -> Self {
+        Self::Machine(e)
+    }
+}
+
+impl From<InvalidTile> for ArcadeError {
+    fn from(e: InvalidTile) -> Self {
+        Self::Output(e)
+    }
+}
+
+/// Which way the joystick is pushed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    Left,
+    Neutral,
+    Right,
+}
+
+impl Action {
+    fn to_int(self) -> isize {
+        match self {
+            Self::Left => -1,
+            Self::Neutral => 0,
+            Self::Right => 1,
+        }
+    }
+}
+
+/// What the screen shows.
+#[derive(Clone, Debug, Default)]
+pub struct Observation {
+    /// The tiles, by row and then by column.
+    pub tiles: Vec<Vec<Tile>>,
+    pub score: isize,
+    pub ball: Option<(usize, usize)>,
+    pub paddle: Option<(usize, usize)>,
+}
+
+impl Observation {
+    pub fn blocks(&self) -> usize {
+        self.tiles
+            .iter()
+            .flatten()
+            .filter(|&&tile| tile == Tile::Block)
+            .count()
+    }
+
+    fn draw(&mut self, x: usize, y: usize, tile: Tile) {
+        if self.tiles.len() <= y {
+            self.tiles.resize(y + 1, Vec::new());
+        }
+
+        let row = &mut self.tiles[y];
+        if row.len() <= x {
+            row.resize(x + 1, Tile::Empty);
+        }
+        row[x] = tile;
+
+        match tile {
+            Tile::Ball => self.ball = Some((x, y)),
+            Tile::HorizontalPaddle => self.paddle = Some((x, y)),
+            _ => {}
+        }
+    }
+}
+
+/// How far across or down the screen a tile can be.
+const SCREEN_SIZE: usize = 1024;
+
+/// Where `(x, y)` is on the screen, if it is on it.
+fn on_screen(x: isize, y: isize) -> Result<(usize, usize), ArcadeError> {
+    match (usize::try_from(x), usize::try_from(y)) {
+        (Ok(column), Ok(row)) if column < SCREEN_SIZE && row < SCREEN_SIZE => Ok((column, row)),
+        _ => Err(ArcadeError::OffScreen { x, y }),
+    }
+}
+
+type Cabinet = Machine<isize, PendingInput<isize>, PendingOutput<isize>>;
+
+/// A game on the cabinet, set to play for free.
+pub struct Arcade {
+    program: Program,
+    cabinet: Cabinet,
+    framer: Framer<isize, 3>,
+    observation: Observation,
+    done: bool,
+}
+
+impl Arcade {
+    /// Starts a game, and runs it until it asks for the first move.
+    pub fn new(program: &Program) -> Result<Self, ArcadeError> {
+        let memory = free_play(program.clone())?;
+
+        let mut arcade = Self {
+            cabinet: Machine::resumable(memory.clone()),
+            program: Program::new(memory),
+            framer: Framer::new(),
+            observation: Observation::default(),
+            done: false,
+        };
+        arcade.run()?;
+        Ok(arcade)
+    }
+
+    /// Starts the game over.
+    pub fn reset(&mut self) -> Result<&Observation, ArcadeError> {
+        self.cabinet.reset_from(&self.program);
+        self.framer = Framer::new();
+        self.observation = Observation::default();
+        self.done = false;
+
+        self.run()?;
+        Ok(&self.observation)
+    }
+
+    pub fn observe(&self) -> &Observation {
+        &self.observation
+    }
+
+    /// Whether the game is over, because the ball was missed, every
+    /// block is broken, or the cabinet failed.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Makes a move, and runs the game until it asks for the next one.
+    /// Returns what the screen shows then, how much the score went up,
+    /// and whether the game is over.
+    pub fn step(&mut self, action: Action) -> Result<(&Observation, isize, bool), ArcadeError> {
+        let score = self.observation.score;
+
+        if !self.done {
+            // The cabinet asks again for the move it stopped on.
+            if let Resume::NeedInput(slot) = self.cabinet.resume() {
+                slot.provide(action.to_int());
+            }
+            self.run()?;
+        }
+
+        let delta = self.observation.score - score;
+        Ok((&self.observation, delta, self.done))
+    }
+
+    /// Runs the cabinet until it asks for a move, or stops. A cabinet
+    /// that fails ends the game.
+    fn run(&mut self) -> Result<(), ArcadeError> {
+        let result = self.run_until_input();
+        if result.is_err() {
+            self.done = true;
+        }
+        result
+    }
+
+    fn run_until_input(&mut self) -> Result<(), ArcadeError> {
+        loop {
+            match self.cabinet.resume() {
+                Resume::Output(word) => {
+                    let words = match self.framer.push(word) {
+                        Some(words) => words,
+                        None => continue,
+                    };
+
+                    match Output::decode(words)? {
+                        Output::Tile { x, y, tile } => {
+                            let (column, row) = on_screen(x, y)?;
+                            self.observation.draw(column, row, tile)
+                        }
+                        Output::Score(score) => self.observation.score = score,
+                    }
+                }
+                Resume::NeedInput(_) => return Ok(()),
+                Resume::Halted => {
+                    self.done = true;
+                    return match self.framer.pending() {
+                        0 => Ok(()),
+                        words => Err(ArcadeError::Incomplete { words, expected: 3 }),
+                    };
+                }
+                Resume::Error(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A program that starts with an instruction that still works once
+    /// the quarters are inserted into its opcode.
+    fn game(words: &[isize]) -> Program {
+        let mut memory = vec![1, 40, 40, 40];
+        memory.extend_from_slice(words);
+        Program::new(memory)
+    }
+
+    #[test]
+    fn steps_until_halted() {
+        // Draws a paddle at (0, 0) and a block at (2, 0), reads a move,
+        // and makes the move the score.
+        let program = game(&[
+            104, 0, 104, 0, 104, 3, 104, 2, 104, 0, 104, 2, 3, 41, 104, -1, 104, 0, 4, 41, 99,
+        ]);
+
+        let mut arcade = Arcade::new(&program).unwrap();
+        let observation = arcade.observe();
+        assert_eq!(observation.paddle, Some((0, 0)));
+        assert_eq!(observation.ball, None);
+        assert_eq!(observation.blocks(), 1);
+        assert!(!arcade.is_done());
+
+        let (observation, delta, done) = arcade.step(Action::Right).unwrap();
+        assert_eq!((observation.score, delta, done), (1, 1, true));
+
+        // The game is over, so moves don't do anything.
+        let (observation, delta, done) = arcade.step(Action::Left).unwrap();
+        assert_eq!((observation.score, delta, done), (1, 0, true));
+
+        let observation = arcade.reset().unwrap();
+        assert_eq!(observation.score, 0);
+        assert!(!arcade.is_done());
+    }
+
+    #[test]
+    fn errors_end_the_game() {
+        assert!(matches!(
+            Arcade::new(&Program::new(Vec::new())),
+            Err(ArcadeError::Patch(_))
+        ));
+
+        // Draws a tile that doesn't exist.
+        let program = game(&[3, 41, 104, 0, 104, 0, 104, 7, 99]);
+        let mut arcade = Arcade::new(&program).unwrap();
+        assert!(matches!(
+            arcade.step(Action::Neutral),
+            Err(ArcadeError::Output(_))
+        ));
+        assert!(arcade.is_done());
+
+        // Draws a wall left of the screen, and one far below it.
+        for &(x, y) in &[(-2, 0), (0, isize::MAX)] {
+            let program = game(&[104, x, 104, y, 104, 1, 99]);
+            assert!(matches!(
+                Arcade::new(&program),
+                Err(ArcadeError::OffScreen { x: a, y: b }) if (a, b) == (x, y)
+            ));
+        }
+
+        // Halts partway through drawing a tile.
+        let program = game(&[104, 0, 104, 0, 99]);
+        assert!(matches!(
+            Arcade::new(&program),
+            Err(ArcadeError::Incomplete {
+                words: 2,
+                expected: 3
+            })
+        ));
+
+        let program = game(&[42]);
+        assert!(matches!(
+            Arcade::new(&program),
+            Err(ArcadeError::Machine(_))
+        ));
+    }
+}
@@ -1,17 +1,9 @@
-use intcode::future::{sink::Sink, Poll};
+use intcode::future::{frame::Decode, sink::Sink, Poll};
 
-use std::cell::Cell;
+use std::{collections::HashMap, convert::TryFrom, fmt};
 
-#[derive(Eq, Ord, PartialEq, PartialOrd, Clone, Hash, Copy)]
-enum Expecting {
-    X,
-    Y,
-    Id,
-    ScoreBoard,
-}
-
-#[derive(Eq, Ord, PartialEq, PartialOrd, Clone, Hash, Copy)]
-enum Tile {
+#[derive(Eq, Ord, PartialEq, PartialOrd, Clone, Hash, Copy, Debug)]
+pub enum Tile {
     Empty,
     Wall,
     Block,
@@ -19,36 +11,61 @@ enum Tile {
     Ball,
 }
 
-impl From<isize> for Tile {
-    fn from(n: isize) -> Self {
+#[derive(Debug)]
+pub struct InvalidTile(isize);
+
+impl fmt::Display for InvalidTile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Not a valid tile: {}", self.0)
+    }
+}
+
+impl TryFrom<isize> for Tile {
+    type Error = InvalidTile;
+
+    fn try_from(n: isize) -> Result<Self, Self::Error> {
         match n {
-            0 => Self::Empty,
-            1 => Self::Wall,
-            2 => Self::Block,
-            3 => Self::HorizontalPaddle,
-            4 => Self::Ball,
-            n => panic!(format!("Not a valid tile: {} !", n)),
+            0 => Ok(Self::Empty),
+            1 => Ok(Self::Wall),
+            2 => Ok(Self::Block),
+            3 => Ok(Self::HorizontalPaddle),
+            4 => Ok(Self::Ball),
+            n => Err(InvalidTile(n)),
         }
     }
 }
 
-use std::collections::HashMap;
+/// What the cabinet outputs, three words at a time.
+#[derive(Clone, Copy)]
+pub enum Output {
+    Tile { x: isize, y: isize, tile: Tile },
+    Score(isize),
+}
 
+impl Decode<isize, 3> for Output {
+    type Error = InvalidTile;
+
+    fn decode([x, y, value]: [isize; 3]) -> Result<Self, Self::Error> {
+        match (x, y) {
+            (-1, 0) => Ok(Self::Score(value)),
+            _ => Ok(Self::Tile {
+                x,
+                y,
+                tile: Tile::try_from(value)?,
+            }),
+        }
+    }
+}
+
+#[derive(Default)]
 pub struct Drawer {
     map: HashMap<(isize, isize), Tile>,
-
-    x: isize,
-    y: isize,
-    expects: Expecting,
 }
 
 impl Drawer {
     pub fn new() -> Self {
         Self {
             map: HashMap::new(),
-            x: 0,
-            y: 0,
-            expects: Expecting::X,
         }
     }
 
@@ -57,90 +74,30 @@ impl Drawer {
     }
 }
 
-impl Sink<isize> for Drawer {
+impl Sink<Output> for Drawer {
     type Error = ();
 
     fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
         Poll::Ready(Ok(()))
     }
 
-    fn send(&mut self, value: isize) -> Result<(), Self::Error> {
-        match self.expects {
-            Expecting::X => {
-                self.x = value;
-                self.expects = Expecting::Y;
-                Ok(())
-            }
-
-            Expecting::Y => {
-                self.y = value;
-                self.expects = Expecting::Id;
-                Ok(())
-            }
-
-            Expecting::Id => {
-                let tile = Tile::from(value);
-
-                *self.map.entry((self.x, self.y)).or_insert(tile) = tile;
-                self.expects = Expecting::X;
-                Ok(())
-            }
-            _ => unreachable!(),
-        }
-    }
-}
-
-const WIDTH: usize = 40;
-const HEIGHT: usize = 40;
-
-pub struct Grid {
-    grid: [[Tile; WIDTH]; HEIGHT],
-}
-
-impl Grid {
-    fn new() -> Self {
-        Self {
-            grid: [[Tile::Empty; WIDTH]; HEIGHT],
+    fn send(&mut self, output: Output) -> Result<(), Self::Error> {
+        if let Output::Tile { x, y, tile } = output {
+            self.map.insert((x, y), tile);
         }
-    }
-}
 
-impl Grid {
-    fn display(&self) {
-        for row in self.grid.iter() {
-            for cell in row.iter() {
-                match cell {
-                    Tile::Block => print!("b"),
-                    Tile::Empty => print!(" "),
-                    Tile::Wall => print!("w"),
-                    Tile::HorizontalPaddle => print!("_"),
-                    Tile::Ball => print!("o"),
-                };
-            }
-            println!()
-        }
+        Ok(())
     }
 }
 
-pub struct FancyDrawer<'a> {
-    grid: Grid,
-    x: isize,
-    y: isize,
-    expects: Expecting,
-    should_display: &'a Cell<bool>,
+#[derive(Default)]
+pub struct FancyDrawer {
     score: isize,
 }
 
-impl<'a> FancyDrawer<'a> {
-    pub fn new(should_display: &'a Cell<bool>) -> Self {
-        Self {
-            grid: Grid::new(),
-            x: 0,
-            y: 0,
-            expects: Expecting::X,
-            should_display,
-            score: 0,
-        }
+impl FancyDrawer {
+    pub fn new() -> Self {
+        Self { score: 0 }
     }
 
     pub fn score(&self) -> isize {
@@ -148,49 +105,18 @@ impl<'a> FancyDrawer<'a> {
     }
 }
 
-impl<'a> Sink<isize> for FancyDrawer<'a> {
+impl Sink<Output> for FancyDrawer {
     type Error = ();
 
     fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
         Poll::Ready(Ok(()))
     }
 
-    fn send(&mut self, value: isize) -> Result<(), Self::Error> {
-        match self.expects {
-            Expecting::X => {
-                self.x = value;
-                self.expects = Expecting::Y;
-                Ok(())
-            }
-            Expecting::Y => {
-                self.y = value;
-
-                match (self.x, self.y) {
-                    (-1, 0) => self.expects = Expecting::ScoreBoard,
-                    _ => self.expects = Expecting::Id,
-                }
-
-                Ok(())
-            }
-            Expecting::Id => {
-                let tile = Tile::from(value);
-                self.grid.grid[self.y as usize][self.x as usize] = tile;
-                match tile {
-                    Tile::HorizontalPaddle | Tile::Ball => {
-                        if self.should_display.get() {
-                            self.grid.display()
-                        }
-                    }
-                    _ => {}
-                }
-                self.expects = Expecting::X;
-                Ok(())
-            }
-            Expecting::ScoreBoard => {
-                self.score = value;
-                self.expects = Expecting::X;
-                Ok(())
-            }
+    fn send(&mut self, output: Output) -> Result<(), Self::Error> {
+        if let Output::Score(score) = output {
+            self.score = score;
         }
+
+        Ok(())
     }
 }
@@ -1,67 +1,107 @@
-use intcode::future::{stream::Stream, Poll};
-
-use std::{
-    cell::Cell,
-    io::BufRead,
+use intcode::future::{
+    io::{ReadError, ReadErrorKind, ReadWords},
+    stream::Stream,
+    Poll,
 };
 
-pub struct JoyStick<'a, R: BufRead> {
-    input: R,
-    buffer: String,
-    autoinput: String,
-    saved: String,
+use std::{cell::Cell, io::BufRead};
+
+/// The moves to play before asking for any.
+const AUTOINPUT: &str = "nnnnnnnnnnnnnnnrrrrrrrrrrrrrrnnnnnnnnnnnnnnnnnnnnlllllllllllnrrrnrrrrnllllnlllnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrlnrrnrrrrrrnnnlnnnnnllllllnnrnnnnnnnnnnnnllnrrnnnnnnrrrrnlllnrrrnnnnnnnnlnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrnllnnnnnnnnnnrrrnnnnnnnnnnnnnnnnnnnnnlllllllnnnnnnnnnnnnnnrnnnnnnnnnllllllllllllllrnnnnnnnlllllnllllllnnnnnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrnnnnnnnlllllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrrrrrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnllllllllllllnnnnnnnnnnnnnnrrrrrrnnnnnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnlllrrnnrnnnnnnnnnnnnnllllllllllllllllllnllllllllrrnnrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnlllllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnlllllllllllllllrrnlnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrnnnnnnnnnnnnnnnnnnnnlnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrnnnnnnnnnnnnnnnnnnlnnnnnnnlnnnnllllllllllllrnnrrrnnnnnllnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrnnnnnnnnnnnnnnnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnllllllllllllllrrrrrrrrrrrrrnnnnnnnnnnnnnnnnnnrnnnnnllllnnnnnnnnnnnnllllllllllnrrrrrrrrrrrrrrlnnnnnnnnnnnnnnnnrnnnnnnlllllllnnnnnnlllllnnnnllnnnnnnnrrrrrrrrrrrrrllnnnnnnnnnrrrrrlllllllllllllllllnnnnnnnnnnnnrnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrnnnlllllllllllllllllllrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrlllllllnnnnnnnnnnnnlllnnnnnnnnrnrrrnnnnnnnnnnnrrrrrnnnnnnnrrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnlllllllllllllllnrnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrnnnnnnnnnnnnnnnnnnnnnnnllllnnnnnrrnnnnnnnnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrnnnnnnllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnllnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrnnnnnnnnnnnnnnnnnnnllnnnnnnnlllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrnnnnnnnrrrnnnnnnnnnnnnnnnnnnnlllnnnnnnnnnlnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrnnnrnnnnnnnnnnnnnnnnnnllllllllnnnrnlnnnnnnnnnnnrrrrrrrrnnnnnnrrrrrrnnnnnnnllllllllllllllllllllllnrrrnnnnnnnnnnnnnrrrrrrrrrrrrrrrrrrrnnnnnnnnnnllllllllllllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrnnrrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnlnlnnnnnnnnlnnnnnnnnnlllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnlnnnllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrnnnnrrrrrlnnnnnnlllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrnnnnnnnnnnlllllllllnnnlllllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrrnnnnnnnnnnnnlllllllllllllllnnnnnlnnnnnnnnnnnnnrrrrrrrrrrrrrrnnrrnnnnnnnnnnnllllllllllllnnnnlllnlnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrnnnnnnnnnnnnnnllllllllllllllllllnrnnnnnnnnrrrrrrrrrrrrrrrrrrrrrrrlllllllllllllllllllllllnnnnllllllnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrrrrrrrrrrrrrrllllllllllllllllllllllnnnnnnnlnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrrnnnnnnnnnnnlllllllllllllllllllrrnnnnnnnnrrrrrrrrrrnnrnnrrrrrrrrrrrnnnnnllllllllllllllllllllllnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrnnnnnnnnnnnnnnnnnnnnnnnlllllllllllnnnnnnnnnnnnnnnnnnrrrrrnnnrrrrrrnnnnnnnnnnnlllllllllllllllllnnnrnnnnnnnnnnrrrrrrrrrrrnnrrrrrrrrrrnnnnnllllllllllllllllllllllnnnnnnnnnrrrrrrrrrrrrrrrrnnnrrrrrrrrlnlllllllllllllllllllllllnnnllllnnnnnrrrrrrrrrrrrrrrrrrrrrrrrrrrrrllllllllllllllllllllllllllllllnrrrrrrrrrrrrrrrrrrrrrrrrrrrrrrrrnnllllllllllllllllllll";
+
+fn parse_move(word: &str) -> Option<isize> {
+    match word {
+        "n" => Some(0),
+        "l" => Some(-1),
+        "r" => Some(1),
+        _ => None,
+    }
+}
+
+pub struct JoyStick<'a, R> {
+    input: ReadWords<R>,
+    autoinput: Vec<isize>,
     should_display: &'a Cell<bool>,
 }
 
 impl<'a, R: BufRead> JoyStick<'a, R> {
     pub fn new(input: R, should_display: &'a Cell<bool>) -> Self {
         Self {
-            input,
-            buffer: String::with_capacity(10),
-            saved: String::new(),
+            input: ReadWords::new(input, parse_move),
             should_display,
-            autoinput: "nnnnnnnnnnnnnnnrrrrrrrrrrrrrrnnnnnnnnnnnnnnnnnnnnlllllllllllnrrrnrrrrnllllnlllnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrlnrrnrrrrrrnnnlnnnnnllllllnnrnnnnnnnnnnnnllnrrnnnnnnrrrrnlllnrrrnnnnnnnnlnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrnllnnnnnnnnnnrrrnnnnnnnnnnnnnnnnnnnnnlllllllnnnnnnnnnnnnnnrnnnnnnnnnllllllllllllllrnnnnnnnlllllnllllllnnnnnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrnnnnnnnlllllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrrrrrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnllllllllllllnnnnnnnnnnnnnnrrrrrrnnnnnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnlllrrnnrnnnnnnnnnnnnnllllllllllllllllllnllllllllrrnnrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnlllllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnlllllllllllllllrrnlnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrnnnnnnnnnnnnnnnnnnnnlnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrnnnnnnnnnnnnnnnnnnlnnnnnnnlnnnnllllllllllllrnnrrrnnnnnllnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrnnnnnnnnnnnnnnnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnllllllllllllllrrrrrrrrrrrrrnnnnnnnnnnnnnnnnnnrnnnnnllllnnnnnnnnnnnnllllllllllnrrrrrrrrrrrrrrlnnnnnnnnnnnnnnnnrnnnnnnlllllllnnnnnnlllllnnnnllnnnnnnnrrrrrrrrrrrrrllnnnnnnnnnrrrrrlllllllllllllllllnnnnnnnnnnnnrnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrnnnlllllllllllllllllllrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrlllllllnnnnnnnnnnnnlllnnnnnnnnrnrrrnnnnnnnnnnnrrrrrnnnnnnnrrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnlllllllllllllllnrnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrnnnnnnnnnnnnnnnnnnnnnnnllllnnnnnrrnnnnnnnnnnrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrnnnnnnllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnllnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrnnnnnnnnnnnnnnnnnnnllnnnnnnnlllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrnnnnnnnrrrnnnnnnnnnnnnnnnnnnnlllnnnnnnnnnlnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrnnnrnnnnnnnnnnnnnnnnnnllllllllnnnrnlnnnnnnnnnnnrrrrrrrrnnnnnnrrrrrrnnnnnnnllllllllllllllllllllllnrrrnnnnnnnnnnnnnrrrrrrrrrrrrrrrrrrrnnnnnnnnnnllllllllllllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrnnrrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnnnnnlnlnnnnnnnnlnnnnnnnnnlllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrnnnnnnnnnnnnnnnnnnnnnnnnnlnnnllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrnnnnrrrrrlnnnnnnlllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrnnnnnnnnnnlllllllllnnnlllllllnnnnnnnnnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrrnnnnnnnnnnnnlllllllllllllllnnnnnlnnnnnnnnnnnnnrrrrrrrrrrrrrrnnrrnnnnnnnnnnnllllllllllllnnnnlllnlnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrnnnnnnnnnnnnnnllllllllllllllllllnrnnnnnnnnrrrrrrrrrrrrrrrrrrrrrrrlllllllllllllllllllllllnnnnllllllnnnnnnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrrrrrrrrrrrrrrllllllllllllllllllllllnnnnnnnlnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrrnnnnnnnnnnnlllllllllllllllllllrrnnnnnnnnrrrrrrrrrrnnrnnrrrrrrrrrrrnnnnnllllllllllllllllllllllnnnnnnnnnnnnnnnnnrrrrrrrrrrrrrrrrnnnnnnnnnnnnnnnnnnnnnnnlllllllllllnnnnnnnnnnnnnnnnnnrrrrrnnnrrrrrrnnnnnnnnnnnlllllllllllllllllnnnrnnnnnnnnnnrrrrrrrrrrrnnrrrrrrrrrrnnnnnllllllllllllllllllllllnnnnnnnnnrrrrrrrrrrrrrrrrnnnrrrrrrrrlnlllllllllllllllllllllllnnnllllnnnnnrrrrrrrrrrrrrrrrrrrrrrrrrrrrrllllllllllllllllllllllllllllllnrrrrrrrrrrrrrrrrrrrrrrrrrrrrrrrrnnllllllllllllllllllll".chars().rev().collect::<String>(),
+            autoinput: AUTOINPUT
+                .bytes()
+                .rev()
+                .map(|c| match c {
+                    b'n' => 0,
+                    b'l' => -1,
+                    b'r' => 1,
+                    _ => unreachable!(),
+                })
+                .collect(),
         }
     }
 
-    fn read_char(&mut self) -> char {
+    /// Why the player stopped, if reading their moves failed.
+    pub fn error(&self) -> Option<&ReadError> {
+        self.input.error()
+    }
+}
+
+impl<'a, R: BufRead> Stream for JoyStick<'a, R> {
+    type Item = isize;
+    fn poll_next(&mut self) -> Poll<Option<isize>> {
         if self.autoinput.len() == 1 {
             self.should_display.set(true);
         }
-        match self.autoinput.pop() {
-            Some(c) => c,
-            None => {
-                let c = loop {
-                    println!("Provide n, l, or r");
-                    let _ = self.input.read_line(&mut self.buffer);
-                    match self.buffer.as_str().trim() {
-                        "n" => break 'n',
-                        "l" => break 'l',
-                        "r" => break 'r',
-                        _ => self.buffer.clear(),
-                    };
-                };
 
-                self.saved.push(c);
-                self.buffer.clear();
-                c
+        if let Some(output) = self.autoinput.pop() {
+            return Poll::Ready(Some(output));
+        }
+
+        loop {
+            if !self.input.has_buffered() {
+                println!("Provide n, l, or r");
+            }
+
+            match self.input.poll_next() {
+                Poll::Ready(None) => match self.input.error() {
+                    // Skips anything that isn't a move, and asks again.
+                    Some(ReadError {
+                        kind: ReadErrorKind::InvalidWord(word),
+                        ..
+                    }) => {
+                        println!("Not a move: {}", word);
+                        self.input.take_error();
+                    }
+                    _ => return Poll::Ready(None),
+                },
+                poll => return poll,
             }
         }
     }
 }
 
-impl<'a, R: BufRead> Stream for JoyStick<'a, R> {
-    type Item = isize;
-    fn poll_next(&mut self) -> Poll<Option<isize>> {
-        let output = match self.read_char() {
-            'n' => 0,
-            'l' => -1,
-            'r' => 1,
-            _ => panic!(),
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_invalid_moves() {
+        let should_display = Cell::new(false);
+        let mut joystick = JoyStick::new("l x\n\nq r\nn".as_bytes(), &should_display);
 
-        self.buffer.clear();
+        for _ in 0..AUTOINPUT.len() {
+            assert!(matches!(joystick.poll_next(), Poll::Ready(Some(_))));
+        }
+        assert!(should_display.get());
+
+        let mut moves = Vec::new();
+        while let Poll::Ready(Some(value)) = joystick.poll_next() {
+            moves.push(value);
+        }
 
-        Poll::Ready(Some(output))
+        assert_eq!(moves, vec![-1, 1, 0]);
+        assert!(joystick.error().is_none());
     }
 }
@@ -0,0 +1,2 @@
+pub mod arcade;
+pub mod drawer;
@@ -2,57 +2,128 @@ static PUZZLE: &'static str = include_str!(r"..\..\..\Inputs\day13.txt");
 
 use std::{
     cell::Cell,
+    cmp::Ordering,
+    env,
     io::{stdin, BufReader},
 };
 
 use intcode::{
-    future::{stream, FutureExt},
+    future::{frame::Framed, stream, FutureExt},
     machine::Machine,
+    program::Program,
 };
 
-mod drawer;
-use drawer::{Drawer, FancyDrawer};
+use day13::{
+    arcade::{free_play, padded, Action, Arcade, Observation},
+    drawer::{Drawer, FancyDrawer},
+};
 
 mod joystick;
 use joystick::JoyStick;
 
-fn parse_input(s: &str) -> Vec<isize> {
-    s.split(",").map(|w| w.parse().unwrap()).collect::<Vec<_>>()
+mod screen;
+use screen::Screen;
+
+fn parse_input(s: &str) -> Program {
+    Program::parse(s).expect("Invalid program")
 }
 
 fn part1(program: Vec<isize>) -> usize {
     let mut drawer = Drawer::new();
-    let mut machine = Machine::new(program, stream::empty(), &mut drawer);
+    let output = Framed::new(&mut drawer);
+    let mut machine = Machine::new(program, stream::empty(), output);
 
     machine.execute().expect("Machine failed to run!");
 
     drawer.blocks()
 }
 
-fn part2(mut program: Vec<isize>) -> isize {
-    program[0] = 2;
+/// Keeps the paddle under the ball.
+fn follow_ball(observation: &Observation) -> Action {
+    match (observation.ball, observation.paddle) {
+        (Some((ball, _)), Some((paddle, _))) => match ball.cmp(&paddle) {
+            Ordering::Less => Action::Left,
+            Ordering::Equal => Action::Neutral,
+            Ordering::Greater => Action::Right,
+        },
+        _ => Action::Neutral,
+    }
+}
+
+fn part2(program: &Program) -> isize {
+    let mut arcade = Arcade::new(program).unwrap_or_else(|e| panic!("{}", e));
+
+    let mut done = arcade.is_done();
+    while !done {
+        let action = follow_ball(arcade.observe());
+        done = arcade.step(action).unwrap_or_else(|e| panic!("{}", e)).2;
+    }
 
+    arcade.observe().score
+}
+
+/// Plays the game from stdin, after the moves that are built in.
+fn play(program: Vec<isize>) -> isize {
     let should_display = Cell::new(false);
-    let mut fancy_drawer = FancyDrawer::new(&should_display);
+    let mut fancy_drawer = FancyDrawer::new();
 
     let stdin = stdin();
     let stdin = stdin.lock();
     let bufreader = BufReader::new(stdin);
     let mut joystick = JoyStick::new(bufreader, &should_display);
 
-    let mut machine = Machine::new(program, &mut joystick, &mut fancy_drawer);
+    let result = Machine::with_hooks(
+        program,
+        &mut joystick,
+        Framed::new(&mut fancy_drawer),
+        Screen::new(&should_display),
+    )
+    .execute();
 
-    machine.execute().expect("Machine failed to run!");
+    if let Some(e) = joystick.error() {
+        eprintln!("Failed to read a move at {}", e);
+    }
+    result.expect("Machine failed to run!");
 
     fancy_drawer.score()
 }
 
 fn main() {
-    let mut program = parse_input(PUZZLE);
-    program.extend_from_slice(&[0; 512]);
+    let program = parse_input(PUZZLE);
+
+    if env::args().any(|arg| arg == "--play") {
+        let memory = free_play(program).expect("Failed to patch program");
+        println!("Score: {}", play(memory));
+        return;
+    }
 
-    let p1 = part1(program.clone());
-    let p2 = part2(program);
+    let p1 = part1(padded(program.clone()));
+    let p2 = part2(&program);
 
     println!("Part 1: {}\nPart2: {}", p1, p2);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follow_ball_clears_the_board() {
+        let program = parse_input(PUZZLE);
+        let mut arcade = Arcade::new(&program).unwrap();
+        assert_eq!(arcade.observe().blocks(), part1(padded(program)));
+
+        let mut score = 0;
+        while !arcade.is_done() {
+            let action = follow_ball(arcade.observe());
+            let (observation, delta, _) = arcade.step(action).unwrap();
+            score += delta;
+            assert_eq!(observation.score, score);
+        }
+        assert_eq!(arcade.observe().blocks(), 0);
+
+        let observation = arcade.reset().unwrap();
+        assert_eq!(observation.score, 0);
+        assert!(observation.blocks() > 0 && !arcade.is_done());
+    }
+}
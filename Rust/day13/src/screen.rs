@@ -0,0 +1,78 @@
+use intcode::{
+    future::frame::{Decode, Framer},
+    hooks::Hooks,
+};
+
+use std::cell::Cell;
+
+use day13::drawer::{Output, Tile};
+
+const WIDTH: usize = 40;
+const HEIGHT: usize = 40;
+
+struct Grid {
+    grid: [[Tile; WIDTH]; HEIGHT],
+}
+
+impl Grid {
+    fn new() -> Self {
+        Self {
+            grid: [[Tile::Empty; WIDTH]; HEIGHT],
+        }
+    }
+}
+
+impl Grid {
+    fn display(&self) {
+        for row in self.grid.iter() {
+            for cell in row.iter() {
+                match cell {
+                    Tile::Block => print!("b"),
+                    Tile::Empty => print!(" "),
+                    Tile::Wall => print!("w"),
+                    Tile::HorizontalPaddle => print!("_"),
+                    Tile::Ball => print!("o"),
+                };
+            }
+            println!()
+        }
+    }
+}
+
+/// Renders the cabinet's screen whenever the paddle or the ball moves,
+/// by watching the machine's output.
+pub struct Screen<'a> {
+    grid: Grid,
+    framer: Framer<isize, 3>,
+    should_display: &'a Cell<bool>,
+}
+
+impl<'a> Screen<'a> {
+    pub fn new(should_display: &'a Cell<bool>) -> Self {
+        Self {
+            grid: Grid::new(),
+            framer: Framer::new(),
+            should_display,
+        }
+    }
+}
+
+impl<'a> Hooks<isize> for Screen<'a> {
+    fn on_output(&mut self, value: &isize) {
+        let words = match self.framer.push(*value) {
+            Some(words) => words,
+            None => return,
+        };
+
+        // The drawer reports invalid tiles.
+        if let Ok(Output::Tile { x, y, tile }) = Output::decode(words) {
+            self.grid.grid[y as usize][x as usize] = tile;
+            match tile {
+                Tile::HorizontalPaddle | Tile::Ball if self.should_display.get() => {
+                    self.grid.display()
+                }
+                _ => {}
+            }
+        }
+    }
+}
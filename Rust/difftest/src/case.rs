@@ -0,0 +1,315 @@
+use std::fmt;
+
+use crate::rng::Rng;
+
+/// Operands refer to the data region that is placed behind the code,
+/// so a generated program never writes into its own instructions.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Operand {
+    Immediate(isize),
+    /// An index into the data region.
+    Position(usize),
+    /// An offset from the relative base.
+    Relative(usize),
+}
+
+/// Jump targets are instruction indices. A target equal to the number of
+/// instructions jumps to the final `halt`, and one at or before the jump
+/// itself makes a loop, which the interpreters' step limit bounds.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Instr {
+    Add(Operand, Operand, Operand),
+    Mul(Operand, Operand, Operand),
+    Save(Operand),
+    Output(Operand),
+    JumpIfTrue(Operand, usize),
+    JumpIfFalse(Operand, usize),
+    LessThan(Operand, Operand, Operand),
+    Equals(Operand, Operand, Operand),
+    AdjustBase(isize),
+}
+
+impl Instr {
+    fn len(&self) -> usize {
+        match self {
+            Self::Add(..) | Self::Mul(..) | Self::LessThan(..) | Self::Equals(..) => 4,
+            Self::JumpIfTrue(..) | Self::JumpIfFalse(..) => 3,
+            Self::Save(_) | Self::Output(_) | Self::AdjustBase(_) => 2,
+        }
+    }
+
+    fn operands(&self) -> Vec<Operand> {
+        match *self {
+            Self::Add(a, b, c) | Self::Mul(a, b, c) => vec![a, b, c],
+            Self::LessThan(a, b, c) | Self::Equals(a, b, c) => vec![a, b, c],
+            Self::JumpIfTrue(a, _) | Self::JumpIfFalse(a, _) => vec![a],
+            Self::Save(a) | Self::Output(a) => vec![a],
+            Self::AdjustBase(n) => vec![Operand::Immediate(n)],
+        }
+    }
+
+    pub(crate) fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Self::Add(a, b, c) | Self::Mul(a, b, c) => vec![a, b, c],
+            Self::LessThan(a, b, c) | Self::Equals(a, b, c) => vec![a, b, c],
+            Self::JumpIfTrue(a, _) | Self::JumpIfFalse(a, _) => vec![a],
+            Self::Save(a) | Self::Output(a) => vec![a],
+            Self::AdjustBase(_) => vec![],
+        }
+    }
+
+    pub(crate) fn target_mut(&mut self) -> Option<&mut usize> {
+        match self {
+            Self::JumpIfTrue(_, target) | Self::JumpIfFalse(_, target) => Some(target),
+            _ => None,
+        }
+    }
+}
+
+/// What a case needs from an interpreter in order to run it.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Features {
+    pub io: bool,
+    pub jumps: bool,
+    pub compare: bool,
+    pub relative: bool,
+    pub immediate: bool,
+    pub negative: bool,
+}
+
+/// A generated program, together with the inputs to run it on.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Case {
+    pub instrs: Vec<Instr>,
+    pub data: Vec<isize>,
+    pub inputs: Vec<isize>,
+}
+
+/// The kind of program to generate, so that the interpreters
+/// that only know a few opcodes still get exercised.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Profile {
+    /// `add`, `mul` and `halt` in position mode only.
+    Day02,
+    /// Everything but the relative base.
+    Day05,
+    Full,
+}
+
+const SLOTS: usize = 8;
+const MAX_INSTRS: usize = 12;
+
+impl Case {
+    pub fn generate(rng: &mut Rng) -> Self {
+        let profile = match rng.below(3) {
+            0 => Profile::Day02,
+            1 => Profile::Day05,
+            _ => Profile::Full,
+        };
+
+        Self::generate_with(rng, profile)
+    }
+
+    pub fn generate_with(rng: &mut Rng, profile: Profile) -> Self {
+        let count = 1 + rng.below(MAX_INSTRS);
+        let mut instrs = Vec::with_capacity(count);
+
+        let value = |rng: &mut Rng| match profile {
+            Profile::Day02 => rng.range(0, 20),
+            _ => rng.range(-20, 20),
+        };
+
+        let read = |rng: &mut Rng| match (profile, rng.below(3)) {
+            (Profile::Day02, _) | (_, 0) => Operand::Position(rng.below(SLOTS)),
+            (Profile::Full, 1) => Operand::Relative(rng.below(SLOTS)),
+            _ => Operand::Immediate(value(rng)),
+        };
+
+        // Writing in immediate mode is an error, but a rare one.
+        let write = |rng: &mut Rng| match profile {
+            Profile::Day02 => Operand::Position(rng.below(SLOTS)),
+            _ if rng.one_in(50) => Operand::Immediate(value(rng)),
+            Profile::Full if rng.one_in(2) => Operand::Relative(rng.below(SLOTS)),
+            _ => Operand::Position(rng.below(SLOTS)),
+        };
+
+        for idx in 0..count {
+            let kinds = match profile {
+                Profile::Day02 => 2,
+                Profile::Day05 => 8,
+                Profile::Full => 9,
+            };
+
+            // Mostly forward, so that most programs still get to the end.
+            let target = if rng.one_in(4) {
+                rng.below(idx + 1)
+            } else {
+                idx + 1 + rng.below(count - idx)
+            };
+
+            let instr = match rng.below(kinds) {
+                0 => Instr::Add(read(rng), read(rng), write(rng)),
+                1 => Instr::Mul(read(rng), read(rng), write(rng)),
+                2 => Instr::Save(write(rng)),
+                3 => Instr::Output(read(rng)),
+                4 => Instr::JumpIfTrue(read(rng), target),
+                5 => Instr::JumpIfFalse(read(rng), target),
+                6 => Instr::LessThan(read(rng), read(rng), write(rng)),
+                7 => Instr::Equals(read(rng), read(rng), write(rng)),
+                _ => Instr::AdjustBase(rng.range(-2, 3)),
+            };
+
+            instrs.push(instr);
+        }
+
+        let data = (0..SLOTS).map(|_| value(rng)).collect();
+
+        let saves = instrs
+            .iter()
+            .filter(|instr| matches!(instr, Instr::Save(_)))
+            .count();
+
+        // Sometimes leave out the last input, to see how running dry is handled.
+        let inputs = match saves {
+            0 => vec![],
+            n if rng.one_in(10) => (1..n).map(|_| value(rng)).collect(),
+            n => (0..n).map(|_| value(rng)).collect(),
+        };
+
+        Self {
+            instrs,
+            data,
+            inputs,
+        }
+    }
+
+    fn uses_relative(&self) -> bool {
+        self.instrs.iter().any(|instr| {
+            matches!(instr, Instr::AdjustBase(_))
+                || instr
+                    .operands()
+                    .iter()
+                    .any(|op| matches!(op, Operand::Relative(_)))
+        })
+    }
+
+    /// How far the relative base can drift away from the data region.
+    fn margin(&self) -> usize {
+        self.instrs
+            .iter()
+            .map(|instr| match instr {
+                Instr::AdjustBase(n) => n.unsigned_abs(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    pub fn features(&self) -> Features {
+        let mut features = Features {
+            relative: self.uses_relative(),
+            negative: self.words().iter().any(|word| *word < 0),
+            ..Features::default()
+        };
+
+        for instr in self.instrs.iter() {
+            match instr {
+                Instr::Save(_) | Instr::Output(_) => features.io = true,
+                Instr::JumpIfTrue(..) | Instr::JumpIfFalse(..) => features.jumps = true,
+                Instr::LessThan(..) | Instr::Equals(..) => features.compare = true,
+                _ => {}
+            }
+
+            if instr
+                .operands()
+                .iter()
+                .any(|op| matches!(op, Operand::Immediate(_)))
+            {
+                features.immediate = true;
+            }
+        }
+
+        features
+    }
+
+    /// Lays out the program as intcode: an optional prologue that points the
+    /// relative base at the data, the instructions, a final `halt`, and the data.
+    pub fn words(&self) -> Vec<isize> {
+        let prologue = if self.uses_relative() { 2 } else { 0 };
+        let margin = self.margin();
+
+        let mut addrs = Vec::with_capacity(self.instrs.len() + 1);
+        let mut addr = prologue;
+        for instr in self.instrs.iter() {
+            addrs.push(addr);
+            addr += instr.len();
+        }
+        addrs.push(addr);
+
+        let data_start = addr + 1;
+
+        let encode = |op: Operand| match op {
+            Operand::Immediate(n) => (1, n),
+            Operand::Position(slot) => (0, (data_start + slot) as isize),
+            Operand::Relative(offset) => (2, (offset + margin) as isize),
+        };
+
+        let mut words = Vec::new();
+        if prologue != 0 {
+            words.extend_from_slice(&[109, data_start as isize]);
+        }
+
+        for instr in self.instrs.iter() {
+            let (opcode, target) = match instr {
+                Instr::Add(..) => (1, None),
+                Instr::Mul(..) => (2, None),
+                Instr::Save(_) => (3, None),
+                Instr::Output(_) => (4, None),
+                Instr::JumpIfTrue(_, target) => (5, Some(addrs[*target])),
+                Instr::JumpIfFalse(_, target) => (6, Some(addrs[*target])),
+                Instr::LessThan(..) => (7, None),
+                Instr::Equals(..) => (8, None),
+                Instr::AdjustBase(_) => (9, None),
+            };
+
+            let operands = instr.operands().into_iter().map(encode).collect::<Vec<_>>();
+            let modes = operands
+                .iter()
+                .rev()
+                .fold(0, |modes, (mode, _)| modes * 10 + mode);
+
+            let modes = match target {
+                Some(_) => modes + 10,
+                None => modes,
+            };
+
+            words.push(opcode + modes * 100);
+            words.extend(operands.iter().map(|(_, word)| word));
+            words.extend(target.map(|addr| addr as isize));
+        }
+
+        words.push(99);
+        words.extend_from_slice(&self.data);
+        words.extend((0..2 * margin).map(|_| 0));
+
+        words
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let words = self
+            .words()
+            .iter()
+            .map(|word| word.to_string())
+            .collect::<Vec<_>>();
+
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|word| word.to_string())
+            .collect::<Vec<_>>();
+
+        writeln!(f, "program: {}", words.join(","))?;
+        write!(f, "inputs:  [{}]", inputs.join(","))
+    }
+}
@@ -0,0 +1,256 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use intcode::{
+    future::{sink::Sink, stream::Stream, Future, Poll},
+    machine::{Machine, MachineError},
+};
+
+use crate::case::{Case, Features};
+
+/// Interpreters that have no way to stop a program from
+/// spinning are only given this many instructions.
+pub const STEP_LIMIT: usize = 10_000;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum End {
+    /// The program halted, leaving its memory like this.
+    Halted(Vec<isize>),
+    /// The interpreter reported an error, or panicked.
+    Error,
+    StepLimit,
+}
+
+/// Everything observable about a single run.
+/// Memory is only compared when the program halts, as interpreters
+/// are free to fail halfway through an instruction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Outcome {
+    pub outputs: Vec<isize>,
+    pub end: End,
+}
+
+pub trait Interpreter {
+    fn name(&self) -> &'static str;
+
+    fn supports(&self, features: Features) -> bool;
+
+    fn run(&self, words: &[isize], inputs: &[isize]) -> Outcome;
+}
+
+/// Runs `f`, turning any panic into an `End::Error`.
+fn guarded<F: FnOnce(&mut Vec<isize>) -> End>(f: F) -> Outcome {
+    let mut outputs = Vec::new();
+
+    let end = match panic::catch_unwind(AssertUnwindSafe(|| f(&mut outputs))) {
+        Ok(end) => end,
+        Err(_) => End::Error,
+    };
+
+    Outcome { outputs, end }
+}
+
+/// `day02::run`: `add`, `mul` and `halt`, on unsigned words.
+pub struct Day02;
+
+impl Interpreter for Day02 {
+    fn name(&self) -> &'static str {
+        "day02"
+    }
+
+    fn supports(&self, features: Features) -> bool {
+        features
+            == Features {
+                io: false,
+                jumps: false,
+                compare: false,
+                relative: false,
+                immediate: false,
+                negative: false,
+            }
+    }
+
+    fn run(&self, words: &[isize], _: &[isize]) -> Outcome {
+        guarded(|_| {
+            let mut memory = words.iter().map(|word| *word as usize).collect::<Vec<_>>();
+
+            // `run` patches the noun and verb, so patch in what is already there.
+            let (noun, verb) = (memory[1], memory[2]);
+            day02::run(&mut memory, noun, verb);
+
+            End::Halted(memory.into_iter().map(|word| word as isize).collect())
+        })
+    }
+}
+
+/// `day05::Opcode::execute`: everything but the relative base.
+pub struct Day05;
+
+impl Interpreter for Day05 {
+    fn name(&self) -> &'static str {
+        "day05"
+    }
+
+    fn supports(&self, features: Features) -> bool {
+        !features.relative
+    }
+
+    fn run(&self, words: &[isize], inputs: &[isize]) -> Outcome {
+        use day05::{ExecuteResult, Opcode};
+
+        guarded(|outputs| {
+            let mut memory = words.to_vec();
+            let mut inputs = inputs.iter();
+            let mut ip = 0;
+
+            for _ in 0..STEP_LIMIT {
+                let opcode = Opcode::from(memory[ip as usize] % 100);
+
+                // `execute` takes the input up front, so hand it the next one.
+                let input = match opcode {
+                    Opcode::Save => match inputs.next() {
+                        Some(input) => *input,
+                        None => return End::Error,
+                    },
+                    _ => 0,
+                };
+
+                match opcode.execute(&mut ip, &mut memory, input) {
+                    ExecuteResult::Continue => {}
+                    ExecuteResult::Output(value) => outputs.push(value),
+                    ExecuteResult::Exit => return End::Halted(memory),
+                }
+            }
+
+            End::StepLimit
+        })
+    }
+}
+
+/// day11's trait based `Intcode::step`.
+pub struct Day11;
+
+impl Interpreter for Day11 {
+    fn name(&self) -> &'static str {
+        "day11"
+    }
+
+    fn supports(&self, _: Features) -> bool {
+        true
+    }
+
+    fn run(&self, words: &[isize], inputs: &[isize]) -> Outcome {
+        use day11::intcode::{Intcode, Machine, Memory, Poll};
+
+        guarded(|outputs| {
+            let mut machine = Machine::new(words.to_vec());
+            let mut inputs = inputs.iter();
+
+            for _ in 0..STEP_LIMIT {
+                match machine.step() {
+                    Poll::Running => {}
+                    Poll::Output(value) => outputs.push(value),
+                    Poll::Input(slot) => match inputs.next() {
+                        Some(input) => *slot = *input,
+                        None => return End::Error,
+                    },
+                    Poll::Exit => return End::Halted(machine.memory().to_vec()),
+                }
+            }
+
+            End::StepLimit
+        })
+    }
+}
+
+struct Inputs<'a>(std::slice::Iter<'a, isize>);
+
+impl<'a> Stream for Inputs<'a> {
+    type Item = isize;
+
+    fn poll_next(&mut self) -> Poll<Option<Self::Item>> {
+        Poll::Ready(self.0.next().copied())
+    }
+}
+
+struct Outputs<'a>(&'a mut Vec<isize>);
+
+impl<'a> Sink<isize> for Outputs<'a> {
+    type Error = ();
+
+    fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn send(&mut self, value: isize) -> Result<(), Self::Error> {
+        self.0.push(value);
+        Ok(())
+    }
+}
+
+/// The `intcode` crate's `Machine`.
+pub struct IntcodeMachine;
+
+impl Interpreter for IntcodeMachine {
+    fn name(&self) -> &'static str {
+        "intcode"
+    }
+
+    fn supports(&self, _: Features) -> bool {
+        true
+    }
+
+    fn run(&self, words: &[isize], inputs: &[isize]) -> Outcome {
+        guarded(|outputs| {
+            let mut machine = Machine::new(words.to_vec(), Inputs(inputs.iter()), Outputs(outputs))
+                .detect_loops(16);
+
+            for _ in 0..STEP_LIMIT {
+                match machine.poll() {
+                    Poll::Running => {}
+                    Poll::Ready(Ok(())) => return End::Halted(machine.memory().to_vec()),
+                    // Looping without I/O, the others will run into the step limit too.
+                    Poll::Ready(Err(MachineError::InfiniteLoop { .. })) => return End::StepLimit,
+                    Poll::Ready(Err(_)) => return End::Error,
+                }
+            }
+
+            End::StepLimit
+        })
+    }
+}
+
+pub fn all() -> Vec<Box<dyn Interpreter>> {
+    vec![
+        Box::new(Day02),
+        Box::new(Day05),
+        Box::new(Day11),
+        Box::new(IntcodeMachine),
+    ]
+}
+
+/// The outcome of running a case on every interpreter that supports it.
+pub struct Run {
+    pub outcomes: Vec<(&'static str, Outcome)>,
+}
+
+impl Run {
+    pub fn new(interpreters: &[Box<dyn Interpreter>], case: &Case) -> Self {
+        let features = case.features();
+        let words = case.words();
+
+        let outcomes = interpreters
+            .iter()
+            .filter(|interpreter| interpreter.supports(features))
+            .map(|interpreter| (interpreter.name(), interpreter.run(&words, &case.inputs)))
+            .collect();
+
+        Self { outcomes }
+    }
+
+    pub fn diverges(&self) -> bool {
+        match self.outcomes.split_first() {
+            Some(((_, first), rest)) => rest.iter().any(|(_, outcome)| outcome != first),
+            None => false,
+        }
+    }
+}
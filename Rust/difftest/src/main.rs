@@ -0,0 +1,238 @@
+//! Differential fuzzer for the intcode interpreters in this workspace.
+//!
+//! Generates random programs and inputs from a seed, runs them on every
+//! interpreter that knows the opcodes used, and reports the smallest program
+//! on which they disagree.
+//!
+//! Usage: `difftest [--seed N] [--iterations N]`
+
+mod case;
+mod interpreters;
+mod rng;
+mod shrink;
+
+use std::{env, panic, process};
+
+use case::Case;
+use interpreters::{Interpreter, Run};
+use rng::Rng;
+
+const DEFAULT_SEED: u64 = 2019;
+const DEFAULT_ITERATIONS: usize = 10_000;
+
+fn parse_args() -> Result<(u64, usize), String> {
+    let mut seed = DEFAULT_SEED;
+    let mut iterations = DEFAULT_ITERATIONS;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", arg))?;
+
+        match arg.as_str() {
+            "--seed" => {
+                seed = value
+                    .parse()
+                    .map_err(|_| format!("Invalid seed: {}", value))?
+            }
+            "--iterations" => {
+                iterations = value
+                    .parse()
+                    .map_err(|_| format!("Invalid iteration count: {}", value))?
+            }
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+
+    Ok((seed, iterations))
+}
+
+/// Runs `iterations` generated cases, and returns the smallest divergence, if any.
+fn fuzz(interpreters: &[Box<dyn Interpreter>], seed: u64, iterations: usize) -> Option<Case> {
+    let mut rng = Rng::new(seed);
+    let mut smallest: Option<Case> = None;
+
+    for _ in 0..iterations {
+        let case = Case::generate(&mut rng);
+
+        if !Run::new(interpreters, &case).diverges() {
+            continue;
+        }
+
+        let case = shrink::shrink(case, |case| Run::new(interpreters, case).diverges());
+
+        match &smallest {
+            Some(best) if best.words().len() <= case.words().len() => {}
+            _ => smallest = Some(case),
+        }
+    }
+
+    smallest
+}
+
+fn main() {
+    let (seed, iterations) = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+
+    // Interpreters report most errors by panicking, which is expected here.
+    panic::set_hook(Box::new(|_| {}));
+
+    let interpreters = interpreters::all();
+
+    match fuzz(&interpreters, seed, iterations) {
+        None => println!("No divergence in {} cases (seed {})", iterations, seed),
+        Some(case) => {
+            println!("Divergence found (seed {}):\n{}\n", seed, case);
+
+            for (name, outcome) in Run::new(&interpreters, &case).outcomes {
+                println!("{:>8}: {:?}", name, outcome);
+            }
+
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use case::{Instr, Operand, Profile};
+    use interpreters::{End, Outcome};
+
+    #[test]
+    fn generation_is_reproducible() {
+        let first = (0..100)
+            .scan(Rng::new(7), |rng, _| Some(Case::generate(rng)))
+            .collect::<Vec<_>>();
+        let second = (0..100)
+            .scan(Rng::new(7), |rng, _| Some(Case::generate(rng)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn day02_programs_run_everywhere() {
+        let mut rng = Rng::new(1);
+        let case = Case::generate_with(&mut rng, Profile::Day02);
+
+        assert_eq!(Run::new(&interpreters::all(), &case).outcomes.len(), 4);
+    }
+
+    #[test]
+    fn interpreters_agree_on_comparisons() {
+        // Outputs 1 if the input is equal to 8, from day05.
+        let case = Case {
+            instrs: vec![
+                Instr::Save(Operand::Position(0)),
+                Instr::Equals(
+                    Operand::Position(0),
+                    Operand::Immediate(8),
+                    Operand::Position(1),
+                ),
+                Instr::Output(Operand::Position(1)),
+            ],
+            data: vec![0, 0],
+            inputs: vec![8],
+        };
+
+        let run = Run::new(&interpreters::all(), &case);
+
+        assert!(!run.diverges());
+        assert_eq!(run.outcomes.len(), 3);
+        assert_eq!(run.outcomes[0].1.outputs, vec![1]);
+    }
+
+    #[test]
+    fn loops_end_at_the_step_limit() {
+        let jumps_back = |case: &Case| {
+            case.instrs
+                .iter()
+                .enumerate()
+                .any(|(idx, instr)| match *instr {
+                    Instr::JumpIfTrue(_, target) | Instr::JumpIfFalse(_, target) => target <= idx,
+                    _ => false,
+                })
+        };
+
+        let mut rng = Rng::new(7);
+        assert!((0..100).any(|_| jumps_back(&Case::generate(&mut rng))));
+
+        // Outputs 1 and then spins forever.
+        let case = Case {
+            instrs: vec![
+                Instr::Output(Operand::Immediate(1)),
+                Instr::JumpIfTrue(Operand::Immediate(1), 1),
+            ],
+            data: vec![],
+            inputs: vec![],
+        };
+
+        let run = Run::new(&interpreters::all(), &case);
+
+        assert!(!run.diverges());
+        assert_eq!(run.outcomes.len(), 3);
+        assert_eq!(run.outcomes[0].1.outputs, vec![1]);
+        assert_eq!(run.outcomes[0].1.end, End::StepLimit);
+    }
+
+    #[test]
+    fn shrinks_to_the_divergent_instruction() {
+        // day05 ignores the mode of the parameter it writes to.
+        let case = Case {
+            instrs: vec![
+                Instr::Output(Operand::Immediate(5)),
+                Instr::Add(
+                    Operand::Immediate(1),
+                    Operand::Immediate(2),
+                    Operand::Immediate(9),
+                ),
+                Instr::Mul(
+                    Operand::Position(0),
+                    Operand::Position(1),
+                    Operand::Position(2),
+                ),
+            ],
+            data: vec![3, 4, 5],
+            inputs: vec![],
+        };
+
+        let interpreters = interpreters::all();
+        assert!(Run::new(&interpreters, &case).diverges());
+
+        let shrunk = shrink::shrink(case, |case| Run::new(&interpreters, case).diverges());
+
+        assert_eq!(
+            shrunk.instrs,
+            vec![Instr::Add(
+                Operand::Immediate(0),
+                Operand::Immediate(0),
+                Operand::Immediate(0)
+            )]
+        );
+
+        let run = Run::new(&interpreters, &shrunk);
+        assert!(run.outcomes.iter().any(|(name, outcome)| *name == "day05"
+            && matches!(
+                outcome,
+                Outcome {
+                    end: End::Halted(_),
+                    ..
+                }
+            )));
+    }
+
+    #[test]
+    fn fuzzing_finds_the_immediate_write() {
+        let case = fuzz(&interpreters::all(), DEFAULT_SEED, 2_000).unwrap();
+
+        assert_eq!(case.instrs.len(), 1);
+    }
+}
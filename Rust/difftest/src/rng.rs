@@ -0,0 +1,38 @@
+/// A small xorshift64* generator, so runs are reproducible
+/// from a seed without pulling in any dependencies.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on 0, so mix the seed first.
+        let state = (seed ^ 0x9E37_79B9_7F4A_7C15).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        Self {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in `lo..hi`.
+    pub fn range(&mut self, lo: isize, hi: isize) -> isize {
+        assert!(lo < hi);
+        lo + (self.next_u64() % (hi - lo) as u64) as isize
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        self.range(0, n as isize) as usize
+    }
+
+    /// `true` with a chance of 1 in `n`.
+    pub fn one_in(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+}
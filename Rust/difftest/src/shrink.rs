@@ -0,0 +1,95 @@
+use crate::case::{Case, Operand};
+
+/// Smaller is better: first fewer words, then smaller numbers.
+fn size(case: &Case) -> (usize, usize, isize) {
+    let words = case.words();
+    let magnitude = words
+        .iter()
+        .chain(case.inputs.iter())
+        .map(|word| word.abs())
+        .sum();
+
+    (words.len(), case.inputs.len(), magnitude)
+}
+
+/// Every case that is one simplification away from `case`.
+fn candidates(case: &Case) -> Vec<Case> {
+    let mut candidates = Vec::new();
+
+    for idx in 0..case.instrs.len() {
+        let mut smaller = case.clone();
+        smaller.instrs.remove(idx);
+
+        // Jumps past the removed instruction now land one instruction earlier.
+        for instr in smaller.instrs.iter_mut() {
+            if let Some(target) = instr.target_mut() {
+                if *target > idx {
+                    *target -= 1;
+                }
+            }
+        }
+
+        candidates.push(smaller);
+    }
+
+    for idx in 0..case.instrs.len() {
+        let count = case.instrs[idx].clone().operands_mut().len();
+        for n in 0..count {
+            let mut simpler = case.clone();
+            let op = simpler.instrs[idx]
+                .operands_mut()
+                .into_iter()
+                .nth(n)
+                .unwrap();
+
+            *op = match *op {
+                Operand::Immediate(0) | Operand::Position(0) | Operand::Relative(0) => continue,
+                Operand::Immediate(_) => Operand::Immediate(0),
+                Operand::Position(_) => Operand::Position(0),
+                Operand::Relative(_) => Operand::Relative(0),
+            };
+
+            candidates.push(simpler);
+        }
+    }
+
+    for idx in 0..case.data.len() {
+        if case.data[idx] != 0 {
+            let mut simpler = case.clone();
+            simpler.data[idx] = 0;
+            candidates.push(simpler);
+        }
+    }
+
+    if !case.inputs.is_empty() {
+        let mut fewer = case.clone();
+        fewer.inputs.pop();
+        candidates.push(fewer);
+    }
+
+    for idx in 0..case.inputs.len() {
+        if case.inputs[idx] != 0 {
+            let mut simpler = case.clone();
+            simpler.inputs[idx] = 0;
+            candidates.push(simpler);
+        }
+    }
+
+    candidates
+}
+
+/// Greedily simplifies `case` for as long as `interesting` keeps holding.
+pub fn shrink<F: Fn(&Case) -> bool>(mut case: Case, interesting: F) -> Case {
+    'outer: loop {
+        let current = size(&case);
+
+        for candidate in candidates(&case) {
+            if size(&candidate) < current && interesting(&candidate) {
+                case = candidate;
+                continue 'outer;
+            }
+        }
+
+        return case;
+    }
+}
@@ -0,0 +1,20 @@
+//! Generates the C header into `OUT_DIR`. With `INTCODE_FFI_UPDATE_HEADER`
+//! set, it also updates the one that is checked in, `include/intcode.h`.
+
+use std::{env, path::Path};
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=INTCODE_FFI_UPDATE_HEADER");
+
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let header = cbindgen::generate(&dir).expect("couldn't generate the header");
+
+    let out = env::var("OUT_DIR").unwrap();
+    header.write_to_file(Path::new(&out).join("intcode.h"));
+
+    if env::var_os("INTCODE_FFI_UPDATE_HEADER").is_some() {
+        header.write_to_file(Path::new(&dir).join("include/intcode.h"));
+    }
+}
@@ -0,0 +1,149 @@
+#ifndef INTCODE_H
+#define INTCODE_H
+
+/* Generated by build.rs with cbindgen. Don't edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Why `intcode_run` returned.
+ */
+typedef enum IntcodeStop {
+  /**
+   * The machine hasn't run yet.
+   */
+  INTCODE_STOP_NOT_RUN,
+  INTCODE_STOP_HALTED,
+  /**
+   * The machine waits on an input.
+   */
+  INTCODE_STOP_NEED_INPUT,
+  /**
+   * The machine ran every step it was given.
+   */
+  INTCODE_STOP_BUDGET,
+  /**
+   * The machine failed, and `intcode_error` says why.
+   */
+  INTCODE_STOP_ERROR,
+} IntcodeStop;
+
+/**
+ * A machine, with its inputs and outputs.
+ */
+typedef struct IntcodeMachine IntcodeMachine;
+
+/**
+ * Makes a machine with a copy of the `len` words at `words`.
+ * Free it with `intcode_free`.
+ *
+ * # Safety
+ *
+ * `words` must point to `len` words, or be null if `len` is 0.
+ */
+struct IntcodeMachine *intcode_new(const ptrdiff_t *words, size_t len);
+
+/**
+ * Frees a machine. Null is ignored.
+ *
+ * # Safety
+ *
+ * `machine` must come from `intcode_new`, and not be freed already.
+ */
+void intcode_free(struct IntcodeMachine *machine);
+
+/**
+ * Queues an input, after the ones that weren't read yet.
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine from `intcode_new`.
+ */
+void intcode_push_input(struct IntcodeMachine *machine, ptrdiff_t value);
+
+/**
+ * Takes the oldest output into `value`. Returns false, and leaves `value`
+ * alone, if there is none.
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine from `intcode_new`, and `value` must
+ * be valid to write.
+ */
+bool intcode_pop_output(struct IntcodeMachine *machine, ptrdiff_t *value);
+
+/**
+ * The number of outputs that weren't taken yet.
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine from `intcode_new`.
+ */
+size_t intcode_outputs(const struct IntcodeMachine *machine);
+
+/**
+ * Runs the machine for at most `budget` steps, and returns why it
+ * stopped. A step is an instruction, or an attempt at one that waits on
+ * an input.
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine from `intcode_new`.
+ */
+enum IntcodeStop intcode_run(struct IntcodeMachine *machine, size_t budget);
+
+/**
+ * Why the last `intcode_run` returned.
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine from `intcode_new`.
+ */
+enum IntcodeStop intcode_stop_reason(const struct IntcodeMachine *machine);
+
+/**
+ * Writes why the machine failed to `buf`, as a string that is cut off to
+ * fit in `len` bytes, and returns the length of all of it. Returns 0, and
+ * writes nothing, if the last run didn't fail.
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine from `intcode_new`, and `buf` must
+ * point to `len` bytes, or be null if `len` is 0.
+ */
+size_t intcode_error(const struct IntcodeMachine *machine, char *buf, size_t len);
+
+/**
+ * The number of words of memory.
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine from `intcode_new`.
+ */
+size_t intcode_memory_len(const struct IntcodeMachine *machine);
+
+/**
+ * Reads the word at `addr` into `value`. Returns false, and leaves
+ * `value` alone, if `addr` is out of bounds.
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine from `intcode_new`, and `value` must
+ * be valid to write.
+ */
+bool intcode_read(const struct IntcodeMachine *machine, size_t addr, ptrdiff_t *value);
+
+/**
+ * Writes `value` at `addr`. Returns false if `addr` is out of bounds.
+ *
+ * # Safety
+ *
+ * `machine` must be a live machine from `intcode_new`.
+ */
+bool intcode_write(struct IntcodeMachine *machine, size_t addr, ptrdiff_t value);
+
+#endif  /* INTCODE_H */
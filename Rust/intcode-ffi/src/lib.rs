@@ -0,0 +1,343 @@
+//! The intcode machine behind a C ABI, for hosts in other languages.
+//!
+//! A host makes a machine with `intcode_new`, gives it inputs with
+//! `intcode_push_input`, and runs it with `intcode_run` until it halts,
+//! fails, waits on an input, or runs out of steps. Outputs queue up until
+//! `intcode_pop_output` takes them. The declarations are in
+//! `include/intcode.h`, which the build script updates when
+//! `INTCODE_FFI_UPDATE_HEADER` is set.
+
+use std::{collections::VecDeque, os::raw::c_char, ptr, slice};
+
+use intcode::{
+    future::{stream::Stream, Future, Poll},
+    machine::{Machine, MachineError},
+};
+
+/// Why `intcode_run` returned.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum IntcodeStop {
+    /// The machine hasn't run yet.
+    NotRun,
+    Halted,
+    /// The machine waits on an input.
+    NeedInput,
+    /// The machine ran every step it was given.
+    Budget,
+    /// The machine failed, and `intcode_error` says why.
+    Error,
+}
+
+/// The inputs a machine hasn't read yet.
+#[derive(Default)]
+struct Inputs {
+    queue: VecDeque<isize>,
+    /// Whether the machine asked for an input when there was none.
+    starved: bool,
+}
+
+impl Stream for Inputs {
+    type Item = isize;
+
+    fn poll_next(&mut self) -> Poll<Option<Self::Item>> {
+        match self.queue.pop_front() {
+            Some(value) => Poll::Ready(Some(value)),
+            None => {
+                self.starved = true;
+                Poll::Running
+            }
+        }
+    }
+}
+
+/// A machine, with its inputs and outputs.
+pub struct IntcodeMachine {
+    machine: Machine<isize, Inputs, VecDeque<isize>>,
+    stop: IntcodeStop,
+    error: Option<MachineError>,
+}
+
+impl IntcodeMachine {
+    fn run(&mut self, budget: usize) -> IntcodeStop {
+        self.error = None;
+
+        for _ in 0..budget {
+            match self.machine.poll() {
+                Poll::Ready(Ok(())) => return IntcodeStop::Halted,
+                Poll::Ready(Err(e)) => {
+                    self.error = Some(e);
+                    return IntcodeStop::Error;
+                }
+                Poll::Running if self.machine.reader().starved => {
+                    self.machine.reader_mut().starved = false;
+                    return IntcodeStop::NeedInput;
+                }
+                Poll::Running => {}
+            }
+        }
+
+        IntcodeStop::Budget
+    }
+}
+
+/// Makes a machine with a copy of the `len` words at `words`.
+/// Free it with `intcode_free`.
+///
+/// # Safety
+///
+/// `words` must point to `len` words, or be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_new(words: *const isize, len: usize) -> *mut IntcodeMachine {
+    let memory = match len {
+        0 => Vec::new(),
+        _ => slice::from_raw_parts(words, len).to_vec(),
+    };
+
+    Box::into_raw(Box::new(IntcodeMachine {
+        machine: Machine::new(memory, Inputs::default(), VecDeque::new()),
+        stop: IntcodeStop::NotRun,
+        error: None,
+    }))
+}
+
+/// Frees a machine. Null is ignored.
+///
+/// # Safety
+///
+/// `machine` must come from `intcode_new`, and not be freed already.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_free(machine: *mut IntcodeMachine) {
+    if !machine.is_null() {
+        drop(Box::from_raw(machine));
+    }
+}
+
+/// Queues an input, after the ones that weren't read yet.
+///
+/// # Safety
+///
+/// `machine` must be a live machine from `intcode_new`.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_push_input(machine: *mut IntcodeMachine, value: isize) {
+    (*machine).machine.reader_mut().queue.push_back(value);
+}
+
+/// Takes the oldest output into `value`. Returns false, and leaves `value`
+/// alone, if there is none.
+///
+/// # Safety
+///
+/// `machine` must be a live machine from `intcode_new`, and `value` must
+/// be valid to write.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_pop_output(
+    machine: *mut IntcodeMachine,
+    value: *mut isize,
+) -> bool {
+    match (*machine).machine.writer_mut().pop_front() {
+        Some(output) => {
+            *value = output;
+            true
+        }
+        None => false,
+    }
+}
+
+/// The number of outputs that weren't taken yet.
+///
+/// # Safety
+///
+/// `machine` must be a live machine from `intcode_new`.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_outputs(machine: *const IntcodeMachine) -> usize {
+    (*machine).machine.writer().len()
+}
+
+/// Runs the machine for at most `budget` steps, and returns why it
+/// stopped. A step is an instruction, or an attempt at one that waits on
+/// an input.
+///
+/// # Safety
+///
+/// `machine` must be a live machine from `intcode_new`.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_run(machine: *mut IntcodeMachine, budget: usize) -> IntcodeStop {
+    let machine = &mut *machine;
+    machine.stop = machine.run(budget);
+    machine.stop
+}
+
+/// Why the last `intcode_run` returned.
+///
+/// # Safety
+///
+/// `machine` must be a live machine from `intcode_new`.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_stop_reason(machine: *const IntcodeMachine) -> IntcodeStop {
+    (*machine).stop
+}
+
+/// Writes why the machine failed to `buf`, as a string that is cut off to
+/// fit in `len` bytes, and returns the length of all of it. Returns 0, and
+/// writes nothing, if the last run didn't fail.
+///
+/// # Safety
+///
+/// `machine` must be a live machine from `intcode_new`, and `buf` must
+/// point to `len` bytes, or be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_error(
+    machine: *const IntcodeMachine,
+    buf: *mut c_char,
+    len: usize,
+) -> usize {
+    let message = match &(*machine).error {
+        Some(e) => format!("{:?}", e),
+        None => return 0,
+    };
+
+    if len > 0 {
+        let n = message.len().min(len - 1);
+        ptr::copy_nonoverlapping(message.as_ptr(), buf as *mut u8, n);
+        *buf.add(n) = 0;
+    }
+
+    message.len()
+}
+
+/// The number of words of memory.
+///
+/// # Safety
+///
+/// `machine` must be a live machine from `intcode_new`.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_memory_len(machine: *const IntcodeMachine) -> usize {
+    (*machine).machine.memory().len()
+}
+
+/// Reads the word at `addr` into `value`. Returns false, and leaves
+/// `value` alone, if `addr` is out of bounds.
+///
+/// # Safety
+///
+/// `machine` must be a live machine from `intcode_new`, and `value` must
+/// be valid to write.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_read(
+    machine: *const IntcodeMachine,
+    addr: usize,
+    value: *mut isize,
+) -> bool {
+    match (*machine).machine.memory().get(addr) {
+        Some(&word) => {
+            *value = word;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Writes `value` at `addr`. Returns false if `addr` is out of bounds.
+///
+/// # Safety
+///
+/// `machine` must be a live machine from `intcode_new`.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_write(
+    machine: *mut IntcodeMachine,
+    addr: usize,
+    value: isize,
+) -> bool {
+    match (*machine).machine.memory_mut().get_mut(addr) {
+        Some(word) => {
+            *word = value;
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Outputs the sum of every pair of inputs.
+    const ADDER: [isize; 15] = [3, 13, 3, 14, 1, 13, 14, 13, 4, 13, 1105, 1, 0, 0, 0];
+
+    #[test]
+    fn inputs_and_outputs() {
+        unsafe {
+            let machine = intcode_new(ADDER.as_ptr(), ADDER.len());
+            assert_eq!(intcode_stop_reason(machine), IntcodeStop::NotRun);
+            assert_eq!(intcode_run(machine, usize::MAX), IntcodeStop::NeedInput);
+
+            for value in [2, 40, -5, 5] {
+                intcode_push_input(machine, value);
+            }
+            assert_eq!(intcode_run(machine, usize::MAX), IntcodeStop::NeedInput);
+            assert_eq!(intcode_outputs(machine), 2);
+
+            let mut output = 0;
+            assert!(intcode_pop_output(machine, &mut output));
+            assert_eq!(output, 42);
+            assert!(intcode_pop_output(machine, &mut output));
+            assert_eq!(output, 0);
+            assert!(!intcode_pop_output(machine, &mut output));
+
+            intcode_push_input(machine, 1);
+            intcode_push_input(machine, 2);
+            assert_eq!(intcode_run(machine, 2), IntcodeStop::Budget);
+            assert_eq!(intcode_stop_reason(machine), IntcodeStop::Budget);
+
+            intcode_free(machine);
+        }
+    }
+
+    #[test]
+    fn memory_and_errors() {
+        unsafe {
+            let words = [1101, 2, 3, 5, 99, 0];
+            let machine = intcode_new(words.as_ptr(), words.len());
+            assert_eq!(intcode_memory_len(machine), 6);
+
+            assert_eq!(intcode_run(machine, 10), IntcodeStop::Halted);
+            let mut value = 0;
+            assert!(intcode_read(machine, 5, &mut value));
+            assert_eq!(value, 5);
+            assert!(!intcode_read(machine, 6, &mut value));
+            assert_eq!(intcode_error(machine, ptr::null_mut(), 0), 0);
+
+            // Makes the halt an invalid opcode.
+            assert!(intcode_write(machine, 4, 98));
+            assert!(!intcode_write(machine, 6, 0));
+            assert_eq!(intcode_run(machine, 10), IntcodeStop::Error);
+
+            let mut buf = [0 as c_char; 8];
+            let len = intcode_error(machine, buf.as_mut_ptr(), buf.len());
+            assert!(len > buf.len());
+
+            let message = buf.iter().map(|&c| c as u8).collect::<Vec<_>>();
+            assert_eq!(message[7], 0);
+            assert!(message.starts_with(b"Opcode"));
+
+            // Running again forgets the error.
+            assert!(intcode_write(machine, 4, 99));
+            assert_eq!(intcode_run(machine, 10), IntcodeStop::Halted);
+            assert_eq!(intcode_error(machine, ptr::null_mut(), 0), 0);
+
+            intcode_free(machine);
+            intcode_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn header_is_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/intcode.h"));
+        let checked_in = include_str!("../include/intcode.h");
+        assert!(
+            generated == checked_in,
+            "include/intcode.h is out of date, build with INTCODE_FFI_UPDATE_HEADER=1"
+        );
+    }
+}
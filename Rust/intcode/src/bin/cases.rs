@@ -0,0 +1,37 @@
+//! Runs intcode test cases, and reports on every case.
+//!
+//! Usage: `cases FILE...`
+
+use std::{env, io, process};
+
+use intcode::testing::{load_cases, run_cases};
+
+fn main() {
+    let paths = env::args().skip(1).collect::<Vec<_>>();
+    if paths.is_empty() {
+        eprintln!("Usage: cases FILE...");
+        process::exit(2);
+    }
+
+    let (mut passed, mut failed) = (0, 0);
+    let stdout = io::stdout();
+
+    for path in paths {
+        let cases = match load_cases(&path) {
+            Ok(cases) => cases,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        };
+
+        let summary = run_cases(&cases, &mut stdout.lock()).expect("Failed to write the report");
+        passed += summary.passed;
+        failed += summary.failed;
+    }
+
+    println!("\n{} passed, {} failed", passed, failed);
+    if failed > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,40 @@
+//! Compiles a program in the small language of `intcode::lang`, and prints
+//! the intcode.
+//!
+//! Usage: `compile FILE`
+
+use std::{env, fs, process};
+
+use intcode::lang::compile;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: compile FILE");
+            process::exit(2);
+        }
+    };
+
+    let src = match fs::read_to_string(&path) {
+        Ok(src) => src,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    let words = match compile(&src) {
+        Ok(words) => words,
+        Err(e) => {
+            eprintln!("{}:{}", path, e);
+            process::exit(1);
+        }
+    };
+
+    let words = words
+        .iter()
+        .map(|word| word.to_string())
+        .collect::<Vec<_>>();
+    println!("{}", words.join(","));
+}
@@ -0,0 +1,27 @@
+//! Prints an intcode program as structured pseudo-Rust.
+//!
+//! Usage: `decompile FILE`
+
+use std::{env, process};
+
+use intcode::{decompile::decompile, program::Program};
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: decompile FILE");
+            process::exit(2);
+        }
+    };
+
+    let program = match Program::load(&path) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    print!("{}", decompile(program.words()));
+}
@@ -0,0 +1,31 @@
+//! Prints an intcode program, optimized.
+//!
+//! Usage: `optimize FILE`
+
+use std::{env, process};
+
+use intcode::{optimize::optimize, program::Program};
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: optimize FILE");
+            process::exit(2);
+        }
+    };
+
+    let program = match Program::load(&path) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let words = optimize(program.words())
+        .iter()
+        .map(|word| word.to_string())
+        .collect::<Vec<_>>();
+    println!("{}", words.join(","));
+}
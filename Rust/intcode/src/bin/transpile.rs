@@ -0,0 +1,27 @@
+//! Prints an intcode program as a Rust module that runs it.
+//!
+//! Usage: `transpile FILE`
+
+use std::{env, process};
+
+use intcode::{program::Program, transpile::transpile};
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: transpile FILE");
+            process::exit(2);
+        }
+    };
+
+    let program = match Program::load(&path) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    print!("{}", transpile(program.words()));
+}
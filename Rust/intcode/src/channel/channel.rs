@@ -1,4 +1,4 @@
-use std::{
+use core::{
     cell::Cell,
     fmt::{self, Debug},
 };
@@ -39,7 +39,7 @@ impl<T> Channel<T> {
     }
 }
 
-impl<'a, T: Copy + Debug> Debug for Channel<T> {
+impl<T: Copy + Debug> Debug for Channel<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.inner.fmt(f)
     }
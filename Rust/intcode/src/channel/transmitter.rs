@@ -1,4 +1,4 @@
-use std::{
+use core::{
     cell::Cell,
     fmt::{self, Debug},
 };
@@ -51,10 +51,9 @@ impl<'a, T> Sink<T> for Tx<'a, T> {
 
     fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
         if self.is_empty() {
-            return Poll::Ready(Ok(()));
+            Poll::Ready(Ok(()))
         } else {
-            println!("COULD NOT WRITE YET");
-            return Poll::Running;
+            Poll::Running
         }
     }
 
@@ -62,7 +61,7 @@ impl<'a, T> Sink<T> for Tx<'a, T> {
         match self.inner.replace(None) {
             Some(elem) => {
                 self.inner.replace(Some(elem));
-                return Err(SendError::NotEmpty(value));
+                Err(SendError::NotEmpty(value))
             }
             None => {
                 self.inner.set(Some(value));
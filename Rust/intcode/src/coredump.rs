@@ -0,0 +1,472 @@
+//! Core files: the state of a machine, saved to be inspected or resumed.
+//!
+//! A core file is a program file, so it can be loaded as a program, with
+//! the rest of the state in the comments at the top. Version 1 looks like
+//!
+//! ```text
+//! # intcode core 1
+//! # ip: 8
+//! # base: 0
+//! # pending: in
+//! # error: InvalidMode { mode: 3 }
+//! # trace: 4 0 1007,13,5,14
+//! # trace: 8 0 1005,14,0
+//! 1001,13,1,13,1007,13,5,14,1005,14,0,99,0,5,0
+//! ```
+//!
+//! The header comes first, and starts with the version. `ip` and `base`
+//! are required. `pending` names the I/O instruction (`in` or `out`) the
+//! machine was waiting on, and `error` why it stopped. Every `trace` line
+//! is an instruction that was attempted, oldest first, as the address, the
+//! relative base, and its words at the time. The memory follows, sixteen
+//! words to a line. Unknown header fields are ignored.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, str::FromStr};
+
+#[cfg(feature = "std")]
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    future::{sink::Sink, stream::Stream},
+    machine::Machine,
+    opcode::Mnemonic,
+    program::{ParseError, Program},
+};
+
+/// The version of the format written by [`Core`].
+pub const VERSION: u32 = 1;
+
+const WORDS_PER_LINE: usize = 16;
+
+/// An instruction that was attempted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Executed<T> {
+    pub ip: usize,
+    pub base: isize,
+    pub words: Vec<T>,
+}
+
+/// The last few instructions a machine attempted.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub(crate) struct Trace<T> {
+    capacity: usize,
+    entries: VecDeque<Executed<T>>,
+}
+
+#[cfg(feature = "std")]
+impl<T> Trace<T> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Clone> Trace<T> {
+    /// Records an instruction, reusing the oldest one once full.
+    pub(crate) fn push(&mut self, ip: usize, base: isize, words: &[T]) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entry = if self.entries.len() == self.capacity {
+            self.entries.pop_front().unwrap()
+        } else {
+            Executed {
+                ip,
+                base,
+                words: Vec::with_capacity(4),
+            }
+        };
+
+        entry.ip = ip;
+        entry.base = base;
+        entry.words.clear();
+        entry.words.extend_from_slice(words);
+        self.entries.push_back(entry);
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<Executed<T>> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+/// Where a machine writes its core, and what it remembers for it.
+#[cfg(feature = "std")]
+pub(crate) struct CoreDump<T> {
+    pub(crate) path: PathBuf,
+    pub(crate) trace: Trace<T>,
+    /// Why the last core could not be written.
+    pub(crate) error: Option<io::Error>,
+}
+
+/// A snapshot of a machine.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Core<T> {
+    pub ip: usize,
+    pub base: isize,
+    /// The I/O instruction the machine was waiting on, if any.
+    pub pending: Option<Mnemonic>,
+    /// Why the machine stopped, if it failed.
+    pub error: Option<String>,
+    /// The last instructions attempted, oldest first.
+    pub trace: Vec<Executed<T>>,
+    pub memory: Vec<T>,
+}
+
+impl<T> Core<T> {
+    /// Rebuilds the machine, which picks up where it left off.
+    /// An instruction that was pending is attempted again.
+    pub fn into_machine<R, W>(self, reader: R, writer: W) -> Machine<T, R, W>
+    where
+        R: Stream<Item = T>,
+        W: Sink<T>,
+    {
+        Machine::new(self.memory, reader, writer).at(self.ip, self.base)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Display> Core<T> {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+fn join<T: fmt::Display>(words: &[T]) -> String {
+    words
+        .iter()
+        .map(|word| word.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl<T: fmt::Display> fmt::Display for Core<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# intcode core {}", VERSION)?;
+        writeln!(f, "# ip: {}", self.ip)?;
+        writeln!(f, "# base: {}", self.base)?;
+
+        if let Some(pending) = self.pending {
+            writeln!(f, "# pending: {}", pending.name())?;
+        }
+
+        if let Some(error) = &self.error {
+            writeln!(f, "# error: {}", error.replace('\n', " "))?;
+        }
+
+        for executed in self.trace.iter() {
+            let words = join(&executed.words);
+            writeln!(f, "# trace: {} {} {}", executed.ip, executed.base, words)?;
+        }
+
+        let lines = self
+            .memory
+            .chunks(WORDS_PER_LINE)
+            .map(join)
+            .collect::<Vec<_>>();
+        writeln!(f, "{}", lines.join(",\n"))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CoreErrorKind {
+    /// The first line is not `# intcode core VERSION`.
+    MissingHeader,
+    UnsupportedVersion(String),
+    MissingField(&'static str),
+    /// A field whose value can't be parsed.
+    InvalidField(String),
+    /// The memory is not a valid program.
+    Memory(ParseError),
+}
+
+/// An error while parsing a core file.
+/// Lines and columns both start at 1.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CoreError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: CoreErrorKind,
+}
+
+impl CoreError {
+    const fn new(line: usize, column: usize, kind: CoreErrorKind) -> Self {
+        Self { line, column, kind }
+    }
+}
+
+impl From<ParseError> for CoreError {
+    fn from(e: ParseError) -> Self {
+        Self::new(e.line, e.column, CoreErrorKind::Memory(e))
+    }
+}
+
+impl fmt::Display for CoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            CoreErrorKind::Memory(e) => return e.fmt(f),
+            _ => write!(f, "{}:{}: ", self.line, self.column)?,
+        }
+
+        match &self.kind {
+            CoreErrorKind::MissingHeader => write!(f, "expected `# intcode core VERSION`"),
+            CoreErrorKind::UnsupportedVersion(version) => {
+                write!(f, "unsupported core version `{}`", version)
+            }
+            CoreErrorKind::MissingField(name) => write!(f, "missing field `{}`", name),
+            CoreErrorKind::InvalidField(name) => write!(f, "invalid field `{}`", name),
+            CoreErrorKind::Memory(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum LoadCoreError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { path: PathBuf, error: CoreError },
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for LoadCoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            Self::Parse { path, error } => write!(f, "{}:{}", path.display(), error),
+        }
+    }
+}
+
+fn field<V: FromStr>(line: usize, name: &str, value: &str) -> Result<V, CoreError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| CoreError::new(line, 3, CoreErrorKind::InvalidField(name.to_string())))
+}
+
+fn executed(line: usize, value: &str) -> Result<Executed<isize>, CoreError> {
+    let invalid = || CoreError::new(line, 3, CoreErrorKind::InvalidField("trace".to_string()));
+
+    let mut parts = value.split_whitespace();
+    let (ip, base, words) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(ip), Some(base), Some(words), None) => (ip, base, words),
+        _ => return Err(invalid()),
+    };
+
+    let words = words
+        .split(',')
+        .map(|word| word.parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Executed {
+        ip: field(line, "trace", ip)?,
+        base: field(line, "trace", base)?,
+        words,
+    })
+}
+
+impl Core<isize> {
+    pub fn parse(s: &str) -> Result<Self, CoreError> {
+        let mut lines = s.lines().enumerate().map(|(n, line)| (n + 1, line));
+
+        match lines.next() {
+            Some((_, header)) if header.starts_with("# intcode core ") => {
+                let version = header["# intcode core ".len()..].trim();
+                if version != VERSION.to_string() {
+                    let kind = CoreErrorKind::UnsupportedVersion(version.to_string());
+                    return Err(CoreError::new(1, 16, kind));
+                }
+            }
+            _ => return Err(CoreError::new(1, 1, CoreErrorKind::MissingHeader)),
+        }
+
+        let (mut ip, mut base) = (None, None);
+        let mut core = Self {
+            ip: 0,
+            base: 0,
+            pending: None,
+            error: None,
+            trace: Vec::new(),
+            memory: Vec::new(),
+        };
+
+        for (n, line) in lines {
+            let line = match line.strip_prefix('#') {
+                Some(line) => line.trim_start(),
+                None => break,
+            };
+
+            let (name, value) = match line.find(':') {
+                Some(idx) => (&line[..idx], &line[idx + 1..]),
+                None => continue,
+            };
+
+            match name {
+                "ip" => ip = Some(field(n, name, value)?),
+                "base" => base = Some(field(n, name, value)?),
+                "pending" => {
+                    core.pending = match value.trim() {
+                        "in" => Some(Mnemonic::Save),
+                        "out" => Some(Mnemonic::Output),
+                        _ => {
+                            let kind = CoreErrorKind::InvalidField(name.to_string());
+                            return Err(CoreError::new(n, 3, kind));
+                        }
+                    }
+                }
+                "error" => core.error = Some(value.trim().to_string()),
+                "trace" => core.trace.push(executed(n, value)?),
+                _ => {}
+            }
+        }
+
+        let missing = |name| CoreError::new(1, 1, CoreErrorKind::MissingField(name));
+        core.ip = ip.ok_or_else(|| missing("ip"))?;
+        core.base = base.ok_or_else(|| missing("base"))?;
+        core.memory = Program::parse(s)?.into_vec();
+
+        Ok(core)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadCoreError> {
+        let path = path.as_ref();
+
+        let s = fs::read_to_string(path).map_err(|error| LoadCoreError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        Self::parse(&s).map_err(|error| LoadCoreError::Parse {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        channel::Channel,
+        future::{
+            sink::Stdout,
+            stream::{empty, once},
+            Future, FutureExt,
+        },
+        machine::MachineError,
+    };
+
+    // Counts [13] up to 5.
+    const COUNTER: [isize; 15] = [1001, 13, 1, 13, 1007, 13, 5, 14, 1005, 14, 0, 99, 0, 0, 0];
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("intcode-{}-{}.core", name, std::process::id()))
+    }
+
+    #[test]
+    fn cores_round_trip() {
+        let mut program = vec![3, 21, 1001, 21, 1, 21, 4, 21, 99];
+        program.resize(40, 7);
+
+        let channel = Channel::empty();
+        let (_, rx) = channel.split();
+        let mut machine =
+            Machine::new(program, rx, Stdout::new()).dump_core(temp_path("unused"), 2);
+        assert!(!machine.poll().is_ready());
+
+        let mut core = machine.core();
+        assert_eq!(core.pending, Some(Mnemonic::Save));
+        assert_eq!(core.trace.len(), 1);
+        assert_eq!(Core::parse(&core.to_string()), Ok(core.clone()));
+
+        core.error = Some("InfiniteLoop { ip: 3, period: 2 }".to_string());
+        core.trace.push(Executed {
+            ip: 2,
+            base: -4,
+            words: vec![1001, 21, 1, 21],
+        });
+        assert_eq!(Core::parse(&core.to_string()), Ok(core.clone()));
+
+        // A core is also a program.
+        let text = core.to_string();
+        assert_eq!(Program::parse(&text).unwrap().words(), &core.memory[..]);
+
+        // It picks up with the pending input.
+        let mut stdout = Stdout::new();
+        let mut machine = core.into_machine(once(41), &mut stdout);
+        assert!(machine.execute().is_ok());
+        assert_eq!(stdout.into_inner(), Some(42));
+    }
+
+    #[test]
+    fn failing_machines_dump_their_core() {
+        let path = temp_path("failing");
+
+        // Runs into an invalid opcode once the loop is done.
+        let mut program = COUNTER.to_vec();
+        program[11] = 42;
+
+        let mut machine = Machine::new(program, empty(), Stdout::new()).dump_core(&path, 3);
+        assert!(matches!(
+            machine.execute(),
+            Err(MachineError::OpcodeError(_))
+        ));
+        assert!(machine.core_dump_error().is_none());
+
+        let mut core = Core::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(core.ip, 11);
+        assert!(core.error.as_ref().unwrap().starts_with("OpcodeError"));
+        let ips = core.trace.iter().map(|e| e.ip).collect::<Vec<_>>();
+        assert_eq!(ips, vec![4, 8, 11]);
+        assert_eq!(core.trace[2].words, vec![42]);
+        assert_eq!(core.memory[13], 5);
+
+        // Fix it, and carry on.
+        core.memory[11] = 99;
+        let mut machine = core.into_machine(empty(), Stdout::new());
+        assert!(machine.execute().is_ok());
+    }
+
+    #[test]
+    fn parse_errors_are_positioned() {
+        let err = Core::parse("# intcode core 2\n# ip: 0\n# base: 0\n99").unwrap_err();
+        assert_eq!(err.to_string(), "1:16: unsupported core version `2`");
+
+        let err = Core::parse("# intcode core 1\n# ip: x\n# base: 0\n99").unwrap_err();
+        assert_eq!(
+            err,
+            CoreError::new(2, 3, CoreErrorKind::InvalidField("ip".to_string()))
+        );
+
+        let err = Core::parse("# intcode core 1\n# ip: 0\n99").unwrap_err();
+        assert_eq!(err.kind, CoreErrorKind::MissingField("base"));
+
+        let err = Core::parse("# intcode core 1\n# ip: 0\n# base: 0\n99,\n1 2").unwrap_err();
+        assert_eq!(err.to_string(), "5:3: expected a comma");
+
+        assert_eq!(
+            Core::parse("99").unwrap_err().kind,
+            CoreErrorKind::MissingHeader
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Simulated time.
+//!
+//! A machine with a [`CostModel`] keeps a clock, that every instruction
+//! moves forward by its cost in cycles. Joins run the machines with the
+//! earliest clocks first, so machines that talk over channels see each
+//! other's values in the order they would on real hardware, and how long
+//! everything takes does not depend on the order they are polled in.
+
+use core::fmt;
+
+use crate::opcode::{Mnemonic, Mode, Opcode};
+
+const MNEMONICS: [Mnemonic; 10] = [
+    Mnemonic::Add,
+    Mnemonic::Mul,
+    Mnemonic::Save,
+    Mnemonic::Output,
+    Mnemonic::JumpIfTrue,
+    Mnemonic::JumpIfFalse,
+    Mnemonic::LessThan,
+    Mnemonic::Equals,
+    Mnemonic::AdjustBase,
+    Mnemonic::Halt,
+];
+
+fn index(mnemonic: Mnemonic) -> usize {
+    MNEMONICS.iter().position(|&m| m == mnemonic).unwrap()
+}
+
+/// How many cycles instructions take.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CostModel {
+    cycles: [u64; 10],
+    relative: u64,
+}
+
+impl Default for CostModel {
+    /// Every instruction takes a single cycle.
+    fn default() -> Self {
+        Self::uniform(1)
+    }
+}
+
+impl CostModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every instruction takes `cycles`. Costs of 0 are rounded up to 1,
+    /// so that time always moves forward.
+    pub fn uniform(cycles: u64) -> Self {
+        Self {
+            cycles: [cycles.max(1); 10],
+            relative: 0,
+        }
+    }
+
+    /// Makes `mnemonic` take `cycles`, which are at least 1.
+    pub fn cycles(mut self, mnemonic: Mnemonic, cycles: u64) -> Self {
+        self.cycles[index(mnemonic)] = cycles.max(1);
+        self
+    }
+
+    /// Adds `cycles` for every operand in relative mode.
+    pub fn relative(mut self, cycles: u64) -> Self {
+        self.relative = cycles;
+        self
+    }
+
+    pub fn cost(&self, opcode: &Opcode) -> u64 {
+        let arity = opcode.mnemonic.arity();
+        let relative = opcode.modes[..arity]
+            .iter()
+            .filter(|&&mode| mode == Mode::Relative)
+            .count() as u64;
+
+        self.cycles[index(opcode.mnemonic)] + relative * self.relative
+    }
+}
+
+/// What a machine did with its time.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    pub instructions: u64,
+    /// Cycles spent running instructions.
+    pub busy: u64,
+    /// Cycles spent waiting on I/O.
+    pub idle: u64,
+    pub inputs: u64,
+    pub outputs: u64,
+}
+
+impl Stats {
+    /// The number of cycles since the machine started.
+    pub fn elapsed(&self) -> u64 {
+        self.busy + self.idle
+    }
+
+    /// The share of the time spent running instructions.
+    pub fn utilization(&self) -> f64 {
+        match self.elapsed() {
+            0 => 0.0,
+            elapsed => self.busy as f64 / elapsed as f64,
+        }
+    }
+
+    /// Outputs per thousand cycles.
+    pub fn throughput(&self) -> f64 {
+        match self.elapsed() {
+            0 => 0.0,
+            elapsed => self.outputs as f64 * 1000.0 / elapsed as f64,
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} instructions in {} cycles ({:.1}% busy), {} in, {} out, {:.2} out/kcycle",
+            self.instructions,
+            self.elapsed(),
+            self.utilization() * 100.0,
+            self.inputs,
+            self.outputs,
+            self.throughput()
+        )
+    }
+}
+
+/// The clock of a machine.
+#[derive(Clone, Debug)]
+pub(crate) struct Clock {
+    model: CostModel,
+    pub(crate) stats: Stats,
+}
+
+impl Clock {
+    pub(crate) fn new(model: CostModel) -> Self {
+        Self {
+            model,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Starts the clock over at zero.
+    pub(crate) fn reset(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    pub(crate) fn now(&self) -> u64 {
+        self.stats.elapsed()
+    }
+
+    /// Charges for an instruction that ran.
+    pub(crate) fn charge(&mut self, opcode: &Opcode) {
+        self.stats.instructions += 1;
+        self.stats.busy += self.model.cost(opcode);
+
+        match opcode.mnemonic {
+            Mnemonic::Save => self.stats.inputs += 1,
+            Mnemonic::Output => self.stats.outputs += 1,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn advance_to(&mut self, time: u64) {
+        self.stats.idle += time.saturating_sub(self.now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{cell::RefCell, convert::Infallible};
+
+    use crate::{
+        channel::Channel,
+        future::{
+            sink::{Sink, Stdout},
+            stream::empty,
+            Future, FutureExt, Poll,
+        },
+        machine::Machine,
+    };
+
+    /// Appends everything to a shared log.
+    struct Log<'a>(&'a RefCell<Vec<isize>>);
+
+    impl<'a> Sink<isize> for Log<'a> {
+        type Error = Infallible;
+
+        fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn send(&mut self, value: isize) -> Result<(), Self::Error> {
+            self.0.borrow_mut().push(value);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn relative_operands_cost_extra() {
+        let model = CostModel::new()
+            .cycles(Mnemonic::Add, 4)
+            .cycles(Mnemonic::Halt, 0)
+            .relative(2);
+
+        assert_eq!(model.cost(&Opcode::parse(1).unwrap()), 4);
+        assert_eq!(model.cost(&Opcode::parse(22201).unwrap()), 10);
+        assert_eq!(model.cost(&Opcode::parse(204).unwrap()), 3);
+        assert_eq!(model.cost(&Opcode::parse(99).unwrap()), 1);
+    }
+
+    #[test]
+    fn joins_interleave_by_time() {
+        let log = RefCell::new(Vec::new());
+
+        let slow = Machine::new(vec![104, 1, 104, 1, 104, 1, 99], empty(), Log(&log))
+            .timed(CostModel::new().cycles(Mnemonic::Output, 10));
+        let fast = Machine::new(vec![104, 2, 104, 2, 104, 2, 99], empty(), Log(&log))
+            .timed(CostModel::new().cycles(Mnemonic::Output, 3));
+
+        let (a, b) = slow.join(fast).execute();
+        assert!(a.is_ok() && b.is_ok());
+        assert_eq!(log.into_inner(), vec![1, 2, 2, 2, 1, 1]);
+    }
+
+    #[test]
+    fn waiting_on_input_is_idle_time() {
+        let channel = Channel::empty();
+        let (tx, rx) = channel.split();
+        let model = CostModel::new().cycles(Mnemonic::Add, 5);
+
+        // Works for a while, then sends 7.
+        let program = vec![1101, 0, 0, 11, 1101, 0, 0, 11, 104, 7, 99, 0];
+        let mut producer = Machine::new(program, empty(), tx).timed(model);
+
+        // Echoes a single value.
+        let mut consumer = Machine::new(vec![3, 5, 4, 5, 99, 0], rx, Stdout::new()).timed(model);
+
+        let (p, c) = (&mut producer).join(&mut consumer).execute();
+        assert!(p.is_ok() && c.is_ok());
+
+        let expected = Stats {
+            instructions: 3,
+            busy: 11,
+            idle: 0,
+            inputs: 0,
+            outputs: 1,
+        };
+        assert_eq!(producer.stats(), Some(&expected));
+
+        let expected = Stats {
+            instructions: 2,
+            busy: 2,
+            idle: 10,
+            inputs: 1,
+            outputs: 1,
+        };
+        assert_eq!(consumer.stats(), Some(&expected));
+        assert_eq!(consumer.clock(), Some(12));
+    }
+}
@@ -0,0 +1,340 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec::Vec,
+};
+use core::fmt::{self, Write};
+
+use crate::{
+    disasm::{self, Line, Operand},
+    hooks::{Hooks, State},
+    opcode::Opcode,
+};
+
+/// How often a conditional jump went either way.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Branch {
+    pub taken: u64,
+    pub fallthrough: u64,
+}
+
+impl Branch {
+    pub fn is_covered(&self) -> bool {
+        self.taken > 0 && self.fallthrough > 0
+    }
+}
+
+/// Records which instructions execute, and which way jumps go.
+///
+/// A single `Coverage` can be handed to any number of machines
+/// (as `&mut Coverage`), or the coverage of separate runs can be
+/// combined with [`Coverage::merge`].
+///
+/// A jump to the very next instruction can't be told apart from
+/// not jumping at all, and counts as a fallthrough.
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    hits: BTreeMap<usize, u64>,
+    branches: BTreeMap<usize, Branch>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn hits(&self, addr: usize) -> u64 {
+        self.hits.get(&addr).copied().unwrap_or(0)
+    }
+
+    pub fn branch(&self, addr: usize) -> Option<Branch> {
+        self.branches.get(&addr).copied()
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (addr, hits) in other.hits.iter() {
+            *self.hits.entry(*addr).or_insert(0) += hits;
+        }
+
+        for (addr, branch) in other.branches.iter() {
+            let entry = self.branches.entry(*addr).or_default();
+            entry.taken += branch.taken;
+            entry.fallthrough += branch.fallthrough;
+        }
+    }
+
+    /// Lines up the coverage with the listing of `words`, the program
+    /// as it was before running.
+    pub fn report(&self, words: &[isize]) -> Report {
+        let starts = self.hits.keys().copied().collect::<BTreeSet<_>>();
+
+        let lines = disasm::listing(words, &starts)
+            .into_iter()
+            .map(|line| {
+                let addr = line.addr();
+                let hits = match line {
+                    Line::Instruction(_) => Some(self.hits(addr)),
+                    Line::Data { .. } => None,
+                };
+
+                let branch = if is_branch(&line) {
+                    self.branch(addr)
+                } else {
+                    None
+                };
+
+                ReportLine { line, hits, branch }
+            })
+            .collect();
+
+        Report { lines }
+    }
+}
+
+impl Hooks<isize> for Coverage {
+    fn before_instruction(&mut self, _: &Opcode, state: &State<'_, isize>) {
+        *self.hits.entry(state.ip).or_insert(0) += 1;
+    }
+
+    fn after_instruction(&mut self, ip: usize, opcode: &Opcode, state: &State<'_, isize>) {
+        let mnemonic = opcode.mnemonic();
+
+        if mnemonic.is_jump() {
+            let branch = self.branches.entry(ip).or_default();
+
+            if state.ip == ip + 1 + mnemonic.arity() {
+                branch.fallthrough += 1;
+            } else {
+                branch.taken += 1;
+            }
+        }
+    }
+}
+
+pub struct ReportLine {
+    pub line: Line,
+    /// `None` for data.
+    pub hits: Option<u64>,
+    pub branch: Option<Branch>,
+}
+
+/// Whether `line` is a jump that can go either way. A jump on an
+/// immediate condition always goes the same way.
+fn is_branch(line: &Line) -> bool {
+    match line {
+        Line::Instruction(instruction) => {
+            instruction.mnemonic.is_jump()
+                && !matches!(instruction.operands()[0], Operand::Immediate(_))
+        }
+        Line::Data { .. } => false,
+    }
+}
+
+/// Coverage, line by line. Displays as an annotated listing.
+pub struct Report {
+    pub lines: Vec<ReportLine>,
+}
+
+impl Report {
+    fn instructions(&self) -> impl Iterator<Item = &ReportLine> {
+        self.lines.iter().filter(|line| line.hits.is_some())
+    }
+
+    fn branches(&self) -> impl Iterator<Item = &ReportLine> {
+        self.lines.iter().filter(|line| is_branch(&line.line))
+    }
+
+    /// The number of executed instructions, out of all instructions.
+    pub fn instruction_coverage(&self) -> (usize, usize) {
+        let hit = self
+            .instructions()
+            .filter(|line| line.hits > Some(0))
+            .count();
+
+        (hit, self.instructions().count())
+    }
+
+    /// The number of jumps that went both ways, out of all jumps
+    /// that can.
+    pub fn branch_coverage(&self) -> (usize, usize) {
+        let covered = self
+            .branches()
+            .filter(|line| line.branch.iter().any(Branch::is_covered))
+            .count();
+
+        (covered, self.branches().count())
+    }
+
+    /// The plain listing, one line per instruction or data word.
+    /// These are the lines [`Report::lcov`] refers to.
+    pub fn source(&self) -> String {
+        let mut source = String::new();
+
+        for line in self.lines.iter() {
+            let _ = writeln!(source, "{:>5}: {}", line.line.addr(), line.line);
+        }
+
+        source
+    }
+
+    /// An lcov tracefile for `name`, which should be the output of [`Report::source`].
+    pub fn lcov(&self, name: &str) -> String {
+        let mut lcov = String::new();
+
+        let _ = writeln!(lcov, "TN:");
+        let _ = writeln!(lcov, "SF:{}", name);
+
+        for (lineno, line) in (1..).zip(self.lines.iter()) {
+            if let Some(branch) = line.branch {
+                let _ = writeln!(lcov, "BRDA:{},0,0,{}", lineno, branch.taken);
+                let _ = writeln!(lcov, "BRDA:{},0,1,{}", lineno, branch.fallthrough);
+            } else if is_branch(&line.line) {
+                let _ = writeln!(lcov, "BRDA:{},0,0,-", lineno);
+                let _ = writeln!(lcov, "BRDA:{},0,1,-", lineno);
+            }
+
+            if let Some(hits) = line.hits {
+                let _ = writeln!(lcov, "DA:{},{}", lineno, hits);
+            }
+        }
+
+        let branches = self
+            .branches()
+            .flat_map(|line| line.branch)
+            .map(|branch| (branch.taken > 0) as usize + (branch.fallthrough > 0) as usize)
+            .sum::<usize>();
+
+        let (hit, found) = self.instruction_coverage();
+        let _ = writeln!(lcov, "BRF:{}", 2 * self.branches().count());
+        let _ = writeln!(lcov, "BRH:{}", branches);
+        let _ = writeln!(lcov, "LF:{}", found);
+        let _ = writeln!(lcov, "LH:{}", hit);
+        let _ = writeln!(lcov, "end_of_record");
+
+        lcov
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in self.lines.iter() {
+            match line.hits {
+                Some(0) => write!(f, "{:>8} ", "#####")?,
+                Some(hits) => write!(f, "{:>8} ", hits)?,
+                None => write!(f, "{:>8} ", "-")?,
+            }
+
+            write!(f, "{:>5}: {}", line.line.addr(), line.line)?;
+
+            if let Some(branch) = line.branch {
+                write!(
+                    f,
+                    "    ; taken {}, fallthrough {}",
+                    branch.taken, branch.fallthrough
+                )?;
+            }
+
+            writeln!(f)?;
+        }
+
+        let (hit, found) = self.instruction_coverage();
+        let (covered, jumps) = self.branch_coverage();
+
+        writeln!(f, "instructions: {}/{} executed", hit, found)?;
+        write!(f, "branches:     {}/{} went both ways", covered, jumps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        future::{sink::Stdout, stream::once, FutureExt},
+        machine::Machine,
+        testing::compare_with_8,
+    };
+
+    fn run(coverage: &mut Coverage, input: isize) {
+        let mut machine =
+            Machine::with_hooks(compare_with_8(), once(input), Stdout::new(), coverage);
+        machine.execute().unwrap();
+    }
+
+    #[test]
+    fn a_single_run_leaves_branches_uncovered() {
+        let mut coverage = Coverage::new();
+        run(&mut coverage, 7);
+
+        let report = coverage.report(&compare_with_8());
+
+        assert_eq!(coverage.hits(0), 1);
+        assert_eq!(coverage.hits(36), 0);
+        assert_eq!(report.branch_coverage(), (0, 2));
+        assert_eq!(
+            coverage.branch(6),
+            Some(Branch {
+                taken: 0,
+                fallthrough: 1
+            })
+        );
+    }
+
+    #[test]
+    fn merged_runs_cover_every_branch_direction() {
+        let mut merged = Coverage::new();
+
+        for input in [7, 8, 9].iter() {
+            let mut coverage = Coverage::new();
+            run(&mut coverage, *input);
+            merged.merge(&coverage);
+        }
+
+        let report = merged.report(&compare_with_8());
+
+        assert_eq!(report.instruction_coverage(), (15, 15));
+        assert_eq!(report.branch_coverage(), (2, 2));
+
+        let listing = report.to_string();
+        assert!(listing.contains("       3     0: in [21]\n"));
+        assert!(listing.contains("       1    36: add 1000, 1, [20]\n"));
+        assert!(listing.contains("       -    19: .word 98\n"));
+    }
+
+    #[test]
+    fn unconditional_jumps_are_not_branches() {
+        // Jumps to 4, and from there back to the halt at 3.
+        let words = [1105, 1, 4, 99, 1106, 0, 3];
+
+        let mut coverage = Coverage::new();
+        let mut machine =
+            Machine::with_hooks(words.to_vec(), once(0), Stdout::new(), &mut coverage);
+        machine.execute().unwrap();
+
+        let report = coverage.report(&words);
+        assert_eq!(report.instruction_coverage(), (3, 3));
+        assert_eq!(report.branch_coverage(), (0, 0));
+        assert!(report.lines.iter().all(|line| line.branch.is_none()));
+
+        let lcov = report.lcov("jumps.intcode");
+        assert!(!lcov.contains("BRDA:"));
+        assert!(lcov.contains("\nBRF:0\nBRH:0\n"));
+    }
+
+    #[test]
+    fn lcov_refers_to_source_lines() {
+        let mut coverage = Coverage::new();
+        run(&mut coverage, 8);
+
+        let report = coverage.report(&compare_with_8());
+        let lcov = report.lcov("compare.intcode");
+        let source = report.source();
+
+        // `jt [20], 22` is the third line of the listing.
+        assert_eq!(source.lines().nth(2), Some("    6: jt [20], 22"));
+        assert!(lcov.contains("BRDA:3,0,0,1\nBRDA:3,0,1,0\nDA:3,1\n"));
+        assert!(lcov.contains("\nLF:15\nLH:7\n"));
+        assert!(lcov.starts_with("TN:\nSF:compare.intcode\n"));
+        assert!(lcov.ends_with("end_of_record\n"));
+    }
+}
@@ -0,0 +1,318 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    disasm::{Instruction, Operand},
+    opcode::Mnemonic,
+};
+
+/// How a basic block ends.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum End {
+    /// Runs into the block at the given address.
+    Next(usize),
+    Branch {
+        jump: Instruction,
+        taken: usize,
+        fallthrough: usize,
+    },
+    Call {
+        target: usize,
+        ret: usize,
+        /// The instruction that stores the return address.
+        store: usize,
+    },
+    /// A jump to a computed address, which may turn out to be a return.
+    Indirect(Instruction),
+    Halt,
+    /// Runs into something that doesn't decode, or can't execute.
+    Invalid(usize),
+}
+
+impl End {
+    pub(crate) fn successors(&self) -> Vec<usize> {
+        match *self {
+            Self::Next(next) | Self::Call { ret: next, .. } => vec![next],
+            Self::Branch {
+                taken, fallthrough, ..
+            } => vec![taken, fallthrough],
+            Self::Indirect(_) | Self::Halt | Self::Invalid(_) => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Block {
+    /// Everything but the instruction that ends the block, if any.
+    pub(crate) instructions: Vec<Instruction>,
+    pub(crate) end: End,
+}
+
+impl Block {
+    /// The instructions of the block, including the one that ends it.
+    fn all(&self) -> impl Iterator<Item = &Instruction> {
+        let last = match &self.end {
+            End::Branch { jump, .. } | End::Indirect(jump) => Some(jump),
+            _ => None,
+        };
+
+        self.instructions.iter().chain(last)
+    }
+}
+
+/// What an instruction does to the flow of control.
+enum Flow {
+    Continue,
+    Jump(usize),
+    Branch(usize),
+    /// A call, and the instruction that stores its return address.
+    Call(usize, usize),
+    Indirect,
+    Halt,
+    Invalid,
+}
+
+/// The code reachable from address 0, split into basic blocks.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Graph {
+    pub(crate) blocks: BTreeMap<usize, Block>,
+    /// The entry points of the program, and of everything it calls.
+    pub(crate) functions: BTreeSet<usize>,
+    /// The addresses of parameters that the code writes to.
+    pub(crate) patched: BTreeSet<usize>,
+    /// Cells that are only ever used to hold a comparison for the very next jump.
+    pub(crate) flags: BTreeSet<usize>,
+}
+
+impl Graph {
+    pub(crate) fn new(words: &[isize]) -> Self {
+        let mut graph = Self::default();
+        graph.functions.insert(0);
+
+        let leaders = graph.discover(words);
+
+        for &leader in leaders.iter() {
+            let block = graph.block(words, leader, &leaders);
+            graph.blocks.insert(leader, block);
+        }
+
+        graph.patched = graph
+            .instructions()
+            .filter_map(|instruction| match instruction.written()? {
+                Operand::Position(addr) if addr >= 0 => Some(addr as usize),
+                _ => None,
+            })
+            .filter(|addr| graph.is_parameter(*addr))
+            .collect();
+
+        graph.flags = graph.flags();
+        graph
+    }
+
+    fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.blocks.values().flat_map(Block::all)
+    }
+
+    fn is_parameter(&self, addr: usize) -> bool {
+        self.instructions()
+            .any(|instruction| instruction.addr < addr && addr < instruction.next())
+    }
+
+    /// Follows every path from 0, and returns the addresses that start blocks.
+    fn discover(&mut self, words: &[isize]) -> BTreeSet<usize> {
+        let mut leaders = BTreeSet::new();
+        let mut todo = vec![0];
+
+        while let Some(start) = todo.pop() {
+            if !leaders.insert(start) {
+                continue;
+            }
+
+            let mut trace = Vec::new();
+            let mut addr = start;
+
+            while let Some(instruction) = Instruction::decode(words, addr) {
+                let next = instruction.next();
+
+                match flow(&instruction, &trace) {
+                    Flow::Continue => {
+                        trace.push(instruction);
+                        addr = next;
+                        continue;
+                    }
+                    Flow::Jump(target) => todo.push(target),
+                    Flow::Branch(target) => todo.extend(&[target, next]),
+                    Flow::Call(target, _) => {
+                        self.functions.insert(target);
+                        todo.extend(&[target, next]);
+                    }
+                    Flow::Indirect | Flow::Halt | Flow::Invalid => {}
+                }
+
+                break;
+            }
+        }
+
+        leaders
+    }
+
+    /// Decodes the block starting at `start`.
+    fn block(&self, words: &[isize], start: usize, leaders: &BTreeSet<usize>) -> Block {
+        let mut instructions = Vec::new();
+        let mut addr = start;
+
+        let end = loop {
+            if addr != start && leaders.contains(&addr) {
+                break End::Next(addr);
+            }
+
+            let instruction = match Instruction::decode(words, addr) {
+                Some(instruction) => instruction,
+                None => break End::Invalid(addr),
+            };
+
+            let next = instruction.next();
+
+            match flow(&instruction, &instructions) {
+                Flow::Continue => {
+                    instructions.push(instruction);
+                    addr = next;
+                }
+                Flow::Jump(target) => break End::Next(target),
+                Flow::Branch(target) => {
+                    break End::Branch {
+                        jump: instruction,
+                        taken: target,
+                        fallthrough: next,
+                    }
+                }
+                Flow::Call(target, store) => {
+                    break End::Call {
+                        target,
+                        ret: next,
+                        store,
+                    }
+                }
+                Flow::Indirect => break End::Indirect(instruction),
+                Flow::Halt => break End::Halt,
+                Flow::Invalid => break End::Invalid(addr),
+            }
+        };
+
+        Block { instructions, end }
+    }
+
+    /// Finds the cells that hold the result of a comparison for the jump
+    /// right after it, and are read nowhere else.
+    fn flags(&self) -> BTreeSet<usize> {
+        let mut reads = BTreeMap::<usize, usize>::new();
+        let mut folded = BTreeMap::<usize, usize>::new();
+
+        for block in self.blocks.values() {
+            for instruction in block.all() {
+                let operands = instruction.operands();
+                let inputs = match instruction.mnemonic {
+                    Mnemonic::Save => &operands[..0],
+                    Mnemonic::Add | Mnemonic::Mul | Mnemonic::LessThan | Mnemonic::Equals => {
+                        &operands[..2]
+                    }
+                    _ => operands,
+                };
+
+                for operand in inputs.iter() {
+                    if let Operand::Position(addr) = operand {
+                        if *addr < 0 {
+                            continue;
+                        }
+
+                        *reads.entry(*addr as usize).or_default() += 1;
+                    }
+                }
+            }
+
+            if let End::Branch { jump, .. } = &block.end {
+                let compare = block.instructions.last().filter(|compare| {
+                    matches!(compare.mnemonic, Mnemonic::LessThan | Mnemonic::Equals)
+                });
+
+                if let (Some(compare), Operand::Position(addr)) = (compare, jump.operands()[0]) {
+                    if compare.written() == Some(Operand::Position(addr))
+                        && jump.operands()[1] != Operand::Position(addr)
+                    {
+                        *folded.entry(addr as usize).or_default() += 1;
+                    }
+                }
+            }
+        }
+
+        folded
+            .into_iter()
+            .filter(|(addr, count)| reads.get(addr) == Some(count) && !self.patched.contains(addr))
+            .map(|(addr, _)| addr)
+            .collect()
+    }
+}
+
+/// Classifies `instruction`, which follows the instructions in `trace`.
+fn flow(instruction: &Instruction, trace: &[Instruction]) -> Flow {
+    let operands = instruction.operands();
+
+    if let Some(Operand::Immediate(_)) = instruction.written() {
+        return Flow::Invalid;
+    }
+
+    let jumps_if = match instruction.mnemonic {
+        Mnemonic::JumpIfTrue => true,
+        Mnemonic::JumpIfFalse => false,
+        Mnemonic::Halt => return Flow::Halt,
+        _ => return Flow::Continue,
+    };
+
+    let target = match operands[1] {
+        Operand::Immediate(target) if target >= 0 => Some(target as usize),
+        _ => None,
+    };
+
+    match (operands[0], target) {
+        (Operand::Immediate(cond), _) if (cond != 0) != jumps_if => Flow::Continue,
+        (Operand::Immediate(_), Some(target)) => match return_address(instruction, trace) {
+            Some(store) => Flow::Call(target, store),
+            None => Flow::Jump(target),
+        },
+        (Operand::Immediate(_), None) => Flow::Indirect,
+        (_, Some(target)) => Flow::Branch(target),
+        // Lifted as a conditional `goto` in the middle of the block.
+        (_, None) => Flow::Continue,
+    }
+}
+
+/// If `jump` is a call, the address of the instruction in `trace` that
+/// stores its return address in `[rb+0]`, where the callee expects it.
+fn return_address(jump: &Instruction, trace: &[Instruction]) -> Option<usize> {
+    for instruction in trace.iter().rev() {
+        if instruction.mnemonic == Mnemonic::AdjustBase {
+            return None;
+        }
+
+        if instruction.written() != Some(Operand::Relative(0)) {
+            continue;
+        }
+
+        let value = match (instruction.mnemonic, instruction.operands()) {
+            (Mnemonic::Add, [Operand::Immediate(a), Operand::Immediate(b), _]) => a + b,
+            (Mnemonic::Mul, [Operand::Immediate(a), Operand::Immediate(b), _]) => a * b,
+            _ => return None,
+        };
+
+        return if value == jump.next() as isize {
+            Some(instruction.addr)
+        } else {
+            None
+        };
+    }
+
+    None
+}
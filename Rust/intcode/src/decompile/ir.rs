@@ -0,0 +1,344 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{fmt, ops};
+
+/// A memory cell, as the decompiled code refers to it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Var {
+    /// A cell at a fixed address.
+    Mem(usize),
+    /// A slot in the frame of the current function, counting from the
+    /// relative base it was called with. Slot 0 holds the return address,
+    /// the slots after it the arguments.
+    Local(isize),
+    /// A slot past the end of the current frame, where the arguments
+    /// of the next call go, and where results are passed back.
+    Arg(isize),
+    /// A relative cell, where the relative base isn't known.
+    Relative(isize),
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Mem(addr) => write!(f, "m{}", addr),
+            Self::Local(0) => write!(f, "ret"),
+            Self::Local(slot) if slot < 0 => write!(f, "up{}", -slot),
+            Self::Local(slot) => write!(f, "l{}", slot),
+            Self::Arg(slot) => write!(f, "a{}", slot),
+            Self::Relative(offset) if offset < 0 => write!(f, "mem[rb - {}]", -offset),
+            Self::Relative(offset) => write!(f, "mem[rb + {}]", offset),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Cmp {
+    Lt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Cmp {
+    fn negate(self) -> Self {
+        match self {
+            Self::Lt => Self::Ge,
+            Self::Ge => Self::Lt,
+            Self::Eq => Self::Ne,
+            Self::Ne => Self::Eq,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Lt => "<",
+            Self::Ge => ">=",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    Const(isize),
+    Var(Var),
+    /// The cell at a computed address.
+    Deref(Box<Expr>),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Cmp(Cmp, Box<Expr>, Box<Expr>),
+    /// The relative base, where it isn't known.
+    Base,
+}
+
+impl Expr {
+    pub fn cmp(cmp: Cmp, lhs: Self, rhs: Self) -> Self {
+        Self::Cmp(cmp, Box::new(lhs), Box::new(rhs))
+    }
+
+    /// The condition `self != 0`.
+    pub fn truthy(self) -> Self {
+        match self {
+            cond @ Self::Cmp(..) => cond,
+            other => Self::cmp(Cmp::Ne, other, Self::Const(0)),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Cmp(..) => 1,
+            Self::Add(..) => 2,
+            Self::Mul(..) => 3,
+            Self::Neg(..) => 4,
+            _ => 5,
+        }
+    }
+
+    /// Formats `self`, in parentheses if it binds less tightly than `min`.
+    fn fmt_within(&self, f: &mut fmt::Formatter, min: u8) -> fmt::Result {
+        if self.precedence() < min {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl ops::Add for Expr {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        match (self, rhs) {
+            (Self::Const(a), Self::Const(b)) => Self::Const(a + b),
+            (Self::Const(0), other) | (other, Self::Const(0)) => other,
+            (Self::Const(a), other) => Self::Add(Box::new(other), Box::new(Self::Const(a))),
+            (lhs, rhs) => Self::Add(Box::new(lhs), Box::new(rhs)),
+        }
+    }
+}
+
+impl ops::Mul for Expr {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        match (self, rhs) {
+            (Self::Const(a), Self::Const(b)) => Self::Const(a * b),
+            (Self::Const(1), other) | (other, Self::Const(1)) => other,
+            (Self::Const(-1), other) | (other, Self::Const(-1)) => Self::Neg(Box::new(other)),
+            (Self::Const(a), other) => Self::Mul(Box::new(other), Box::new(Self::Const(a))),
+            (lhs, rhs) => Self::Mul(Box::new(lhs), Box::new(rhs)),
+        }
+    }
+}
+
+/// The negation of a condition.
+impl ops::Not for Expr {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        match self.truthy() {
+            Self::Cmp(cmp, lhs, rhs) => Self::Cmp(cmp.negate(), lhs, rhs),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Const(value) => write!(f, "{}", value),
+            Self::Var(var) => write!(f, "{}", var),
+            Self::Deref(addr) => write!(f, "mem[{}]", addr),
+            Self::Base => write!(f, "rb"),
+            Self::Neg(inner) => {
+                write!(f, "-")?;
+                inner.fmt_within(f, 4)
+            }
+            Self::Add(lhs, rhs) => {
+                lhs.fmt_within(f, 2)?;
+
+                match &**rhs {
+                    Self::Const(value) if *value < 0 => write!(f, " - {}", -value),
+                    Self::Neg(inner) => {
+                        write!(f, " - ")?;
+                        inner.fmt_within(f, 3)
+                    }
+                    rhs => {
+                        write!(f, " + ")?;
+                        rhs.fmt_within(f, 3)
+                    }
+                }
+            }
+            Self::Mul(lhs, rhs) => {
+                lhs.fmt_within(f, 3)?;
+                write!(f, " * ")?;
+                rhs.fmt_within(f, 4)
+            }
+            Self::Cmp(cmp, lhs, rhs) => {
+                lhs.fmt_within(f, 2)?;
+                write!(f, " {} ", cmp.symbol())?;
+                rhs.fmt_within(f, 2)
+            }
+        }
+    }
+}
+
+/// Where a value is written to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Place {
+    Var(Var),
+    Deref(Expr),
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Var(var) => write!(f, "{}", var),
+            Self::Deref(addr) => write!(f, "mem[{}]", addr),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Stmt {
+    Assign(Place, Expr),
+    Input(Place),
+    Output(Expr),
+    /// `rb += offset`, where the relative base isn't tracked.
+    AdjustBase(Expr),
+    /// `rb = base`, where the relative base stops being tracked.
+    SetBase(Expr),
+    Call {
+        target: usize,
+        args: Vec<(Var, Expr)>,
+    },
+    If {
+        cond: Expr,
+        then: Vec<Stmt>,
+        els: Vec<Stmt>,
+    },
+    While {
+        cond: Expr,
+        body: Vec<Stmt>,
+    },
+    Loop(Vec<Stmt>),
+    Break,
+    Continue,
+    Return,
+    Halt,
+    Label(usize),
+    Goto(usize),
+    /// A jump to a computed address.
+    GotoIndirect(Expr),
+    /// Something that doesn't decode as an instruction.
+    Invalid(usize),
+}
+
+/// Writes `stmts` at the given level of indentation.
+pub(crate) fn write_block(f: &mut fmt::Formatter, stmts: &[Stmt], depth: usize) -> fmt::Result {
+    for stmt in stmts {
+        write_stmt(f, stmt, depth)?;
+    }
+
+    Ok(())
+}
+
+fn write_stmt(f: &mut fmt::Formatter, stmt: &Stmt, depth: usize) -> fmt::Result {
+    let indent = "    ".repeat(depth);
+
+    match stmt {
+        Stmt::Assign(place, expr) => writeln!(f, "{}{} = {};", indent, place, expr),
+        Stmt::Input(place) => writeln!(f, "{}{} = input();", indent, place),
+        Stmt::Output(expr) => writeln!(f, "{}output({});", indent, expr),
+        Stmt::AdjustBase(expr) => writeln!(f, "{}rb += {};", indent, expr),
+        Stmt::SetBase(expr) => writeln!(f, "{}rb = {};", indent, expr),
+        Stmt::Call { target, args } => {
+            write!(f, "{}f{}(", indent, target)?;
+            for (idx, (var, expr)) in args.iter().enumerate() {
+                let sep = if idx == 0 { "" } else { ", " };
+                write!(f, "{}{} = {}", sep, var, expr)?;
+            }
+            writeln!(f, ");")
+        }
+        Stmt::If { cond, then, els } => {
+            writeln!(f, "{}if {} {{", indent, cond)?;
+            write_block(f, then, depth + 1)?;
+
+            match els.as_slice() {
+                [] => {}
+                // `else if` reads better than nesting.
+                [nested @ Stmt::If { .. }] => {
+                    write!(f, "{}}} else ", indent)?;
+                    return write_else_if(f, nested, depth);
+                }
+                els => {
+                    writeln!(f, "{}}} else {{", indent)?;
+                    write_block(f, els, depth + 1)?;
+                }
+            }
+
+            writeln!(f, "{}}}", indent)
+        }
+        Stmt::While { cond, body } => {
+            writeln!(f, "{}while {} {{", indent, cond)?;
+            write_block(f, body, depth + 1)?;
+            writeln!(f, "{}}}", indent)
+        }
+        Stmt::Loop(body) => {
+            writeln!(f, "{}loop {{", indent)?;
+            write_block(f, body, depth + 1)?;
+            writeln!(f, "{}}}", indent)
+        }
+        Stmt::Break => writeln!(f, "{}break;", indent),
+        Stmt::Continue => writeln!(f, "{}continue;", indent),
+        Stmt::Return => writeln!(f, "{}return;", indent),
+        Stmt::Halt => writeln!(f, "{}halt();", indent),
+        Stmt::Label(addr) => writeln!(f, "{}L{}:", indent, addr),
+        Stmt::Goto(addr) => writeln!(f, "{}goto L{};", indent, addr),
+        Stmt::GotoIndirect(expr) => writeln!(f, "{}goto *{};", indent, expr),
+        Stmt::Invalid(addr) => writeln!(f, "{}invalid(); // at {}", indent, addr),
+    }
+}
+
+/// Writes an `if` that follows an `else` on the same line.
+fn write_else_if(f: &mut fmt::Formatter, stmt: &Stmt, depth: usize) -> fmt::Result {
+    let mut nested = String::new();
+    {
+        use fmt::Write;
+        struct Block<'a>(&'a Stmt, usize);
+
+        impl fmt::Display for Block<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write_stmt(f, self.0, self.1)
+            }
+        }
+
+        write!(nested, "{}", Block(stmt, depth))?;
+    }
+
+    f.write_str(nested.trim_start())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expressions_print_like_rust() {
+        let x = Expr::Var(Var::Mem(10));
+        let y = Expr::Var(Var::Local(2));
+
+        let sum = x.clone() + y.clone() * Expr::Const(-1);
+        assert_eq!(sum.to_string(), "m10 - l2");
+
+        let product = (x.clone() + Expr::Const(-1)) * Expr::Const(3);
+        assert_eq!(product.to_string(), "(m10 - 1) * 3");
+
+        let cond = !Expr::cmp(Cmp::Lt, x, y);
+        assert_eq!(cond.to_string(), "m10 >= l2");
+
+        assert_eq!((!Expr::Var(Var::Arg(1))).to_string(), "a1 == 0");
+    }
+}
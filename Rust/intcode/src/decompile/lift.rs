@@ -0,0 +1,341 @@
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+
+use super::{
+    cfg::{End, Graph},
+    ir::{Cmp, Expr, Place, Stmt, Var},
+};
+use crate::{
+    disasm::{Instruction, Operand},
+    opcode::Mnemonic,
+};
+
+/// How a lifted block hands over control.
+#[derive(Clone, Debug)]
+pub(crate) enum Exit {
+    Next(usize),
+    Branch {
+        cond: Expr,
+        taken: usize,
+        fallthrough: usize,
+    },
+    /// The block ends in a statement that doesn't fall through.
+    Stop,
+}
+
+impl Exit {
+    pub(crate) fn successors(&self) -> Vec<usize> {
+        match *self {
+            Self::Next(next) => vec![next],
+            Self::Branch {
+                taken, fallthrough, ..
+            } => vec![taken, fallthrough],
+            Self::Stop => vec![],
+        }
+    }
+}
+
+/// A basic block, as statements.
+#[derive(Clone, Debug)]
+pub(crate) struct Node {
+    pub(crate) stmts: Vec<Stmt>,
+    pub(crate) exit: Exit,
+}
+
+/// Lifts the instructions of a function into statements.
+pub(crate) struct Lifter<'a> {
+    graph: &'a Graph,
+    entry: usize,
+    /// Whether this is the program itself, where the relative base
+    /// starts out at 0, rather than at a frame.
+    absolute: bool,
+    /// Whether `[rb+1]` and up are the arguments of calls.
+    calls: bool,
+}
+
+impl<'a> Lifter<'a> {
+    pub(crate) fn new(graph: &'a Graph, entry: usize) -> Self {
+        Self {
+            graph,
+            entry,
+            absolute: entry == 0,
+            calls: entry != 0 || graph.functions.len() > 1,
+        }
+    }
+
+    /// The blocks of the function, by address.
+    pub(crate) fn lift(&self) -> BTreeMap<usize, Node> {
+        self.deltas()
+            .into_iter()
+            .filter_map(|(start, delta)| {
+                let block = self.graph.blocks.get(&start)?;
+                Some((start, self.block(&block.instructions, &block.end, delta)))
+            })
+            .collect()
+    }
+
+    /// How far the relative base is from where it was at the entry, at the
+    /// start of every block of the function. `None` where it isn't known.
+    fn deltas(&self) -> BTreeMap<usize, Option<isize>> {
+        let mut deltas = BTreeMap::new();
+        deltas.insert(self.entry, Some(0));
+
+        let mut todo = vec![self.entry];
+
+        while let Some(start) = todo.pop() {
+            let block = match self.graph.blocks.get(&start) {
+                Some(block) => block,
+                None => continue,
+            };
+
+            let mut delta = deltas[&start];
+            for instruction in block.instructions.iter() {
+                if instruction.mnemonic == Mnemonic::AdjustBase {
+                    delta = self.adjust(instruction, delta);
+                }
+            }
+
+            for successor in block.end.successors() {
+                match deltas.get(&successor) {
+                    Some(known) if *known == delta || known.is_none() => {}
+                    Some(_) => {
+                        deltas.insert(successor, None);
+                        todo.push(successor);
+                    }
+                    None => {
+                        deltas.insert(successor, delta);
+                        todo.push(successor);
+                    }
+                }
+            }
+        }
+
+        deltas
+    }
+
+    /// The delta after the `arb` instruction `instruction`.
+    fn adjust(&self, instruction: &Instruction, delta: Option<isize>) -> Option<isize> {
+        match instruction.operands()[0] {
+            Operand::Immediate(offset) if !self.is_patched(instruction, 0) => Some(delta? + offset),
+            _ => None,
+        }
+    }
+
+    fn block(&self, instructions: &[Instruction], end: &End, mut delta: Option<isize>) -> Node {
+        let mut stmts = Vec::new();
+        let mut flag = None;
+
+        let store = match end {
+            End::Call { store, .. } => Some(*store),
+            _ => None,
+        };
+
+        for instruction in instructions.iter() {
+            if Some(instruction.addr) != store {
+                self.instruction(instruction, &mut delta, &mut flag, &mut stmts);
+            }
+        }
+
+        let exit = match *end {
+            End::Next(next) => Exit::Next(next),
+            End::Call { target, ret, .. } => {
+                // The arguments are whatever was last stored past the frame,
+                // as long as nothing stored later reads it back.
+                let mut args = Vec::<(Var, Expr)>::new();
+                let mut read = BTreeSet::new();
+
+                while let Some(Stmt::Assign(Place::Var(var @ Var::Arg(_)), expr)) = stmts.last() {
+                    if read.contains(var) || args.iter().any(|(arg, _)| arg == var) {
+                        break;
+                    }
+
+                    super::visit_expr(expr, &mut |var, _| {
+                        read.insert(var);
+                    });
+
+                    if let Some(Stmt::Assign(Place::Var(var), expr)) = stmts.pop() {
+                        args.push((var, expr));
+                    }
+                }
+
+                args.sort_by_key(|(var, _)| *var);
+                stmts.push(Stmt::Call { target, args });
+                Exit::Next(ret)
+            }
+            End::Branch {
+                jump,
+                taken,
+                fallthrough,
+            } => Exit::Branch {
+                cond: self.condition(&jump, delta, &mut flag),
+                taken,
+                fallthrough,
+            },
+            End::Indirect(jump) => {
+                stmts.push(self.jump(&jump, delta));
+                Exit::Stop
+            }
+            End::Halt => {
+                stmts.push(Stmt::Halt);
+                Exit::Stop
+            }
+            End::Invalid(addr) => {
+                stmts.push(Stmt::Invalid(addr));
+                Exit::Stop
+            }
+        };
+
+        Node { stmts, exit }
+    }
+
+    fn instruction(
+        &self,
+        instruction: &Instruction,
+        delta: &mut Option<isize>,
+        flag: &mut Option<(usize, Expr)>,
+        stmts: &mut Vec<Stmt>,
+    ) {
+        let operands = instruction.operands();
+        let value = |index| self.operand(instruction, index, *delta);
+
+        let expr = match instruction.mnemonic {
+            Mnemonic::Add => value(0) + value(1),
+            Mnemonic::Mul => value(0) * value(1),
+            Mnemonic::LessThan => Expr::cmp(Cmp::Lt, value(0), value(1)),
+            Mnemonic::Equals => Expr::cmp(Cmp::Eq, value(0), value(1)),
+            Mnemonic::Save => {
+                stmts.push(Stmt::Input(self.place(instruction, 0, *delta)));
+                return;
+            }
+            Mnemonic::Output => {
+                stmts.push(Stmt::Output(value(0)));
+                return;
+            }
+            Mnemonic::AdjustBase => {
+                let adjusted = self.adjust(instruction, *delta);
+
+                if adjusted.is_none() {
+                    let offset = value(0);
+                    stmts.push(match *delta {
+                        Some(delta) if self.absolute => Stmt::SetBase(Expr::Const(delta) + offset),
+                        _ => Stmt::AdjustBase(offset),
+                    });
+                }
+
+                *delta = adjusted;
+                return;
+            }
+            // Only conditional jumps to computed addresses are left in the middle of blocks.
+            Mnemonic::JumpIfTrue | Mnemonic::JumpIfFalse => {
+                if let Operand::Immediate(_) = operands[0] {
+                    return;
+                }
+
+                stmts.push(Stmt::If {
+                    cond: self.condition(instruction, *delta, flag),
+                    then: vec![self.jump(instruction, *delta)],
+                    els: Vec::new(),
+                });
+                return;
+            }
+            Mnemonic::Halt => unreachable!(),
+        };
+
+        match operands[2] {
+            Operand::Position(addr) if self.graph.flags.contains(&(addr as usize)) => {
+                *flag = Some((addr as usize, expr));
+            }
+            _ => stmts.push(Stmt::Assign(self.place(instruction, 2, *delta), expr)),
+        }
+    }
+
+    /// The condition under which `jump` jumps, folding in the comparison
+    /// before it, if it went to a flag.
+    fn condition(
+        &self,
+        jump: &Instruction,
+        delta: Option<isize>,
+        flag: &mut Option<(usize, Expr)>,
+    ) -> Expr {
+        let value = match (jump.operands()[0], flag.take()) {
+            (Operand::Position(addr), Some((cell, expr))) if addr as usize == cell => expr,
+            _ => self.operand(jump, 0, delta),
+        };
+
+        match jump.mnemonic {
+            Mnemonic::JumpIfTrue => value.truthy(),
+            _ => !value,
+        }
+    }
+
+    /// The jump to the computed target of `jump`.
+    fn jump(&self, jump: &Instruction, delta: Option<isize>) -> Stmt {
+        match (jump.operands()[1], delta) {
+            (Operand::Relative(offset), Some(delta))
+                if !self.absolute && delta + offset == 0 && !self.is_patched(jump, 1) =>
+            {
+                Stmt::Return
+            }
+            _ => Stmt::GotoIndirect(self.operand(jump, 1, delta)),
+        }
+    }
+
+    fn is_patched(&self, instruction: &Instruction, index: usize) -> bool {
+        self.graph.patched.contains(&(instruction.addr + 1 + index))
+    }
+
+    /// The value of the `index`th operand of `instruction`.
+    fn operand(&self, instruction: &Instruction, index: usize, delta: Option<isize>) -> Expr {
+        let operand = instruction.operands()[index];
+
+        // The code writes the parameter itself, so it is a variable.
+        if self.is_patched(instruction, index) {
+            let param = Expr::Var(Var::Mem(instruction.addr + 1 + index));
+
+            return match operand {
+                Operand::Immediate(_) => param,
+                Operand::Position(_) => Expr::Deref(Box::new(param)),
+                Operand::Relative(_) => Expr::Deref(Box::new(self.base(delta) + param)),
+            };
+        }
+
+        match operand {
+            Operand::Immediate(value) => Expr::Const(value),
+            Operand::Position(addr) if addr >= 0 => Expr::Var(Var::Mem(addr as usize)),
+            Operand::Position(addr) => Expr::Deref(Box::new(Expr::Const(addr))),
+            Operand::Relative(offset) => Expr::Var(self.relative(offset, delta)),
+        }
+    }
+
+    /// Where the `index`th operand of `instruction` writes to.
+    fn place(&self, instruction: &Instruction, index: usize, delta: Option<isize>) -> Place {
+        match self.operand(instruction, index, delta) {
+            Expr::Var(var) => Place::Var(var),
+            Expr::Deref(addr) => Place::Deref(*addr),
+            other => Place::Deref(other),
+        }
+    }
+
+    fn base(&self, delta: Option<isize>) -> Expr {
+        match delta {
+            Some(delta) if self.absolute => Expr::Const(delta),
+            _ => Expr::Base,
+        }
+    }
+
+    fn relative(&self, offset: isize, delta: Option<isize>) -> Var {
+        match delta {
+            None => Var::Relative(offset),
+            Some(_) if self.calls && offset > 0 => Var::Arg(offset),
+            Some(delta) if self.absolute && delta + offset >= 0 => {
+                Var::Mem((delta + offset) as usize)
+            }
+            Some(_) if self.absolute => Var::Relative(offset),
+            Some(delta) => Var::Local(delta + offset),
+        }
+    }
+}
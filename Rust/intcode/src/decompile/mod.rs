@@ -0,0 +1,273 @@
+//! Decompilation of intcode programs into structured pseudo-Rust.
+//!
+//! The code reachable from address 0 is split into basic blocks. A jump
+//! right after storing its own return address in `[rb+0]` is a call, and
+//! a jump to `[rb+0]` at the relative base the function started with is a
+//! return. Within a function, relative cells are named after their slot in
+//! the frame: `l1`, `l2`, ... counting from the relative base at the call,
+//! so the arguments of a function are its first few locals, and `a1`, `a2`,
+//! ... past the current relative base, where the arguments of the next
+//! call go. Position cells become variables named after their address.
+//!
+//! The result is meant for reading, not for compiling: it assumes that
+//! the code only changes the parameters it is seen to write to, and that
+//! calls return where they are expected to.
+
+mod cfg;
+mod ir;
+mod lift;
+mod structure;
+
+pub use self::ir::{Cmp, Expr, Place, Stmt, Var};
+
+use alloc::{collections::BTreeSet, vec::Vec};
+use core::fmt;
+
+use self::{cfg::Graph, lift::Lifter};
+
+/// A function, or the program itself for the one at address 0.
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub entry: usize,
+    /// The locals that are read before the function writes to them.
+    pub params: Vec<Var>,
+    pub body: Vec<Stmt>,
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.entry == 0 {
+            write!(f, "fn main(")?;
+        } else {
+            write!(f, "fn f{}(", self.entry)?;
+        }
+
+        for (idx, param) in self.params.iter().enumerate() {
+            let sep = if idx == 0 { "" } else { ", " };
+            write!(f, "{}{}", sep, param)?;
+        }
+
+        writeln!(f, ") {{")?;
+        ir::write_block(f, &self.body, 1)?;
+        writeln!(f, "}}")
+    }
+}
+
+/// A decompiled program. Displays as pseudo-Rust, with the initial
+/// values of the cells it uses as `static`s.
+#[derive(Clone, Debug)]
+pub struct Decompiled {
+    pub functions: Vec<Function>,
+    /// The cells referred to by address, with their initial values.
+    pub statics: Vec<(usize, isize)>,
+}
+
+pub fn decompile(words: &[isize]) -> Decompiled {
+    let graph = Graph::new(words);
+
+    let functions = graph
+        .functions
+        .iter()
+        .map(|&entry| {
+            let nodes = Lifter::new(&graph, entry).lift();
+            let body = structure::structure(entry, nodes);
+
+            Function {
+                entry,
+                params: params(&body),
+                body,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut cells = BTreeSet::new();
+    for function in functions.iter() {
+        visit(&function.body, &mut |var, _| {
+            if let Var::Mem(addr) = var {
+                cells.insert(addr);
+            }
+        });
+    }
+
+    let statics = cells
+        .into_iter()
+        .filter_map(|addr| Some((addr, *words.get(addr)?)))
+        .collect();
+
+    Decompiled { functions, statics }
+}
+
+impl fmt::Display for Decompiled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (addr, value) in self.statics.iter() {
+            writeln!(f, "static mut {}: isize = {};", Var::Mem(*addr), value)?;
+        }
+
+        for function in self.functions.iter() {
+            writeln!(f)?;
+            write!(f, "{}", function)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The locals of a function that are read before they are written,
+/// going by the order of the statements.
+fn params(body: &[Stmt]) -> Vec<Var> {
+    let mut seen = BTreeSet::new();
+    let mut params = BTreeSet::new();
+
+    visit(body, &mut |var, write| {
+        if let Var::Local(slot) = var {
+            if seen.insert(slot) && !write && slot > 0 {
+                params.insert(slot);
+            }
+        }
+    });
+
+    params.into_iter().map(Var::Local).collect()
+}
+
+/// Calls `f` with every variable in `stmts`, in order,
+/// and whether it is being written to.
+fn visit(stmts: &[Stmt], f: &mut impl FnMut(Var, bool)) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Assign(place, expr) => {
+                visit_expr(expr, f);
+                visit_place(place, f);
+            }
+            Stmt::Input(place) => visit_place(place, f),
+            Stmt::Output(expr)
+            | Stmt::AdjustBase(expr)
+            | Stmt::SetBase(expr)
+            | Stmt::GotoIndirect(expr) => visit_expr(expr, f),
+            Stmt::Call { args, .. } => {
+                for (var, expr) in args.iter() {
+                    visit_expr(expr, f);
+                    f(*var, true);
+                }
+            }
+            Stmt::If { cond, then, els } => {
+                visit_expr(cond, f);
+                visit(then, f);
+                visit(els, f);
+            }
+            Stmt::While { cond, body } => {
+                visit_expr(cond, f);
+                visit(body, f);
+            }
+            Stmt::Loop(body) => visit(body, f),
+            Stmt::Break
+            | Stmt::Continue
+            | Stmt::Return
+            | Stmt::Halt
+            | Stmt::Label(_)
+            | Stmt::Goto(_)
+            | Stmt::Invalid(_) => {}
+        }
+    }
+}
+
+fn visit_place(place: &Place, f: &mut impl FnMut(Var, bool)) {
+    match place {
+        Place::Var(var) => f(*var, true),
+        Place::Deref(addr) => visit_expr(addr, f),
+    }
+}
+
+fn visit_expr(expr: &Expr, f: &mut impl FnMut(Var, bool)) {
+    match expr {
+        Expr::Const(_) | Expr::Base => {}
+        Expr::Var(var) => f(*var, false),
+        Expr::Deref(inner) | Expr::Neg(inner) => visit_expr(inner, f),
+        Expr::Add(lhs, rhs) | Expr::Mul(lhs, rhs) | Expr::Cmp(_, lhs, rhs) => {
+            visit_expr(lhs, f);
+            visit_expr(rhs, f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::compare_with_8;
+
+    #[test]
+    fn if_else_joins_after_both_branches() {
+        // The output `[20]` holds the comparisons as well, so they can't
+        // be folded into the jumps.
+        let words = compare_with_8();
+
+        let expected = "\
+static mut m20: isize = 0;
+static mut m21: isize = 0;
+
+fn main() {
+    m21 = input();
+    m20 = m21 == 8;
+    if m20 != 0 {
+        m20 = m21 * 125;
+        output(m20);
+    } else {
+        m20 = 8 < m21;
+        if m20 == 0 {
+            output(999);
+        } else {
+            m20 = 1001;
+            output(m20);
+        }
+    }
+    halt();
+}
+";
+
+        assert_eq!(decompile(&words).to_string(), expected);
+    }
+
+    #[test]
+    fn loops_and_calls() {
+        // Reads n, and outputs n, n - 1, ..., 1 by calling a function that
+        // outputs its argument, and returns it minus one.
+        let mut words = vec![
+            109, 100, // arb 100
+            3, 60, // in [60]
+            1006, 60, 25, // jf [60], 25
+            21101, 0, 18, 0, // add 0, 18, [rb+0]
+            20101, 0, 60, 1, // add 0, [60], [rb+1]
+            1105, 1, 26, // jt 1, 26
+            1201, 1, 0, 60, // add [rb+1], 0, [60]
+            1105, 1, 4,  // jt 1, 4
+            99, // hlt
+            109, 2, // arb 2
+            204, -1, // out [rb-1]
+            21201, -1, -1, -1, // add [rb-1], -1, [rb-1]
+            109, -2, // arb -2
+            2105, 1, 0, // jt 1, [rb+0]
+        ];
+        words.resize(61, 0);
+
+        let expected = "\
+static mut m60: isize = 0;
+
+fn main() {
+    m60 = input();
+    while m60 != 0 {
+        f26(a1 = m60);
+        m60 = a1;
+    }
+    halt();
+}
+
+fn f26(l1) {
+    output(l1);
+    l1 = l1 - 1;
+    return;
+}
+";
+
+        assert_eq!(decompile(&words).to_string(), expected);
+    }
+}
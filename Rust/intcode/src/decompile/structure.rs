@@ -0,0 +1,512 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+    vec,
+    vec::Vec,
+};
+
+use super::{
+    ir::Stmt,
+    lift::{Exit, Node},
+};
+
+/// Stands in for "leaving the function" in post-dominator sets.
+const EXIT: usize = usize::MAX;
+
+/// A natural loop.
+struct Loop {
+    header: usize,
+    /// Where the loop goes when it is done, if anywhere.
+    follow: Option<usize>,
+    body: BTreeSet<usize>,
+}
+
+/// Turns the blocks of a function back into nested statements.
+///
+/// Loops are found from back edges, and `if`s join up again at the
+/// immediate post-dominator of the branch. Anything that doesn't fit,
+/// such as a jump into the middle of a loop, is left as a `goto`.
+struct Structurer {
+    nodes: BTreeMap<usize, Node>,
+    successors: BTreeMap<usize, Vec<usize>>,
+    /// The immediate post-dominator of every block, `None` for leaving the function.
+    ipdom: BTreeMap<usize, Option<usize>>,
+    loops: BTreeMap<usize, Rc<Loop>>,
+    reachable: BTreeMap<usize, BTreeSet<usize>>,
+    emitted: BTreeSet<usize>,
+    gotos: BTreeSet<usize>,
+}
+
+/// The body of the function at `entry`, made up of `nodes`.
+pub(crate) fn structure(entry: usize, nodes: BTreeMap<usize, Node>) -> Vec<Stmt> {
+    let successors = nodes
+        .iter()
+        .map(|(start, node)| {
+            let successors = node.exit.successors();
+            let successors = successors
+                .into_iter()
+                .filter(|successor| nodes.contains_key(successor))
+                .collect();
+
+            (*start, successors)
+        })
+        .collect();
+
+    let mut structurer = Structurer {
+        nodes,
+        successors,
+        ipdom: BTreeMap::new(),
+        loops: BTreeMap::new(),
+        reachable: BTreeMap::new(),
+        emitted: BTreeSet::new(),
+        gotos: BTreeSet::new(),
+    };
+
+    let order = structurer.order(entry);
+    structurer.ipdom = structurer.post_dominators(&order);
+    structurer.loops = structurer.loops(&order);
+
+    let mut body = Vec::new();
+    structurer.region(Some(entry), None, None, &mut body);
+
+    // Whatever is only reached through `goto`s goes at the end.
+    while let Some(start) = structurer
+        .gotos
+        .iter()
+        .copied()
+        .find(|start| !structurer.emitted.contains(start))
+    {
+        structurer.region(Some(start), None, None, &mut body);
+    }
+
+    simplify(strip_labels(body, &structurer.gotos))
+}
+
+impl Structurer {
+    /// The blocks reachable from `entry`, in reverse postorder.
+    fn order(&self, entry: usize) -> Vec<usize> {
+        let mut order = Vec::new();
+        let mut visited = BTreeSet::new();
+        let mut stack = vec![(entry, 0)];
+        visited.insert(entry);
+
+        while let Some((node, next)) = stack.pop() {
+            match self.successors[&node].get(next) {
+                Some(&successor) => {
+                    stack.push((node, next + 1));
+
+                    if visited.insert(successor) {
+                        stack.push((successor, 0));
+                    }
+                }
+                None => order.push(node),
+            }
+        }
+
+        order.reverse();
+        order
+    }
+
+    fn predecessors(&self) -> BTreeMap<usize, Vec<usize>> {
+        let mut predecessors = BTreeMap::<usize, Vec<usize>>::new();
+
+        for (node, successors) in self.successors.iter() {
+            for successor in successors.iter() {
+                predecessors.entry(*successor).or_default().push(*node);
+            }
+        }
+
+        predecessors
+    }
+
+    /// Solves `sets[n] = {n} ∪ ⋂ sets[m]` for the `m` in `edges[n]`,
+    /// starting from `sets`.
+    fn solve(
+        order: &[usize],
+        edges: &BTreeMap<usize, Vec<usize>>,
+        sets: &mut BTreeMap<usize, BTreeSet<usize>>,
+    ) {
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for node in order.iter() {
+                let mut set: Option<BTreeSet<usize>> = None;
+
+                for other in edges.get(node).into_iter().flatten() {
+                    if let Some(other) = sets.get(other) {
+                        set = Some(match set {
+                            Some(set) => set.intersection(other).copied().collect(),
+                            None => other.clone(),
+                        });
+                    }
+                }
+
+                let mut set = match set {
+                    Some(set) => set,
+                    None => continue,
+                };
+
+                set.insert(*node);
+
+                if sets[node] != set {
+                    sets.insert(*node, set);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    fn post_dominators(&self, order: &[usize]) -> BTreeMap<usize, Option<usize>> {
+        let predecessors = self.predecessors();
+
+        // Only blocks that can leave the function have post-dominators.
+        let mut exits = order
+            .iter()
+            .copied()
+            .filter(|node| self.successors[node].is_empty())
+            .collect::<Vec<_>>();
+
+        let mut leaving = exits.iter().copied().collect::<BTreeSet<_>>();
+        while let Some(node) = exits.pop() {
+            for predecessor in predecessors.get(&node).into_iter().flatten() {
+                if leaving.insert(*predecessor) {
+                    exits.push(*predecessor);
+                }
+            }
+        }
+
+        let mut all = leaving.clone();
+        all.insert(EXIT);
+
+        let mut sets = BTreeMap::new();
+        let mut edges = BTreeMap::new();
+
+        for node in leaving.iter() {
+            let successors = self.successors[node]
+                .iter()
+                .copied()
+                .filter(|successor| leaving.contains(successor))
+                .collect::<Vec<_>>();
+
+            if successors.is_empty() {
+                sets.insert(*node, [*node, EXIT].iter().copied().collect());
+            } else {
+                sets.insert(*node, all.clone());
+                edges.insert(*node, successors);
+            }
+        }
+
+        let reversed = order.iter().rev().copied().collect::<Vec<_>>();
+        let non_exits = reversed
+            .into_iter()
+            .filter(|node| edges.contains_key(node))
+            .collect::<Vec<_>>();
+        Self::solve(&non_exits, &edges, &mut sets);
+
+        sets.iter()
+            .map(|(node, set)| {
+                // The closest post-dominator is the one with the most post-dominators itself.
+                let ipdom = set
+                    .iter()
+                    .filter(|other| *other != node)
+                    .max_by_key(|other| sets.get(other).map_or(1, BTreeSet::len))
+                    .copied()
+                    .filter(|other| *other != EXIT);
+
+                (*node, ipdom)
+            })
+            .collect()
+    }
+
+    fn loops(&self, order: &[usize]) -> BTreeMap<usize, Rc<Loop>> {
+        let predecessors = self.predecessors();
+
+        let mut dominators = order
+            .iter()
+            .map(|node| (*node, order.iter().copied().collect()))
+            .collect::<BTreeMap<_, BTreeSet<_>>>();
+        dominators.insert(order[0], [order[0]].iter().copied().collect());
+        Self::solve(&order[1..], &predecessors, &mut dominators);
+
+        let mut bodies = BTreeMap::<usize, BTreeSet<usize>>::new();
+
+        for node in order.iter() {
+            for successor in self.successors[node].iter() {
+                if !dominators[node].contains(successor) {
+                    continue;
+                }
+
+                // Everything that reaches the back edge without going through the header.
+                let body = bodies
+                    .entry(*successor)
+                    .or_insert_with(|| [*successor].iter().copied().collect::<BTreeSet<_>>());
+
+                let mut todo = vec![*node];
+                while let Some(member) = todo.pop() {
+                    if body.insert(member) {
+                        todo.extend(predecessors.get(&member).into_iter().flatten());
+                    }
+                }
+            }
+        }
+
+        bodies
+            .into_iter()
+            .map(|(header, body)| {
+                let exits = body
+                    .iter()
+                    .flat_map(|member| self.successors[member].iter())
+                    .filter(|successor| !body.contains(successor))
+                    .copied()
+                    .collect::<BTreeSet<_>>();
+
+                let follow = if exits.len() == 1 {
+                    exits.iter().next().copied()
+                } else {
+                    let mut follow = self.ipdom.get(&header).copied().flatten();
+                    while let Some(inside) = follow.filter(|node| body.contains(node)) {
+                        follow = self.ipdom.get(&inside).copied().flatten();
+                    }
+
+                    follow.or_else(|| exits.iter().next().copied())
+                };
+
+                let lp = Loop {
+                    header,
+                    follow,
+                    body,
+                };
+
+                (header, Rc::new(lp))
+            })
+            .collect()
+    }
+
+    fn reaches(&mut self, from: usize, to: usize) -> bool {
+        if !self.reachable.contains_key(&from) {
+            let mut reachable = BTreeSet::new();
+            let mut todo = vec![from];
+
+            while let Some(node) = todo.pop() {
+                for successor in self.successors[&node].iter() {
+                    if reachable.insert(*successor) {
+                        todo.push(*successor);
+                    }
+                }
+            }
+
+            self.reachable.insert(from, reachable);
+        }
+
+        self.reachable[&from].contains(&to)
+    }
+
+    fn goto(&mut self, target: usize, out: &mut Vec<Stmt>) {
+        self.gotos.insert(target);
+        out.push(Stmt::Goto(target));
+    }
+
+    /// Emits the blocks from `cur` on, up to `stop`, within `lp`.
+    fn region(
+        &mut self,
+        mut cur: Option<usize>,
+        stop: Option<usize>,
+        lp: Option<Rc<Loop>>,
+        out: &mut Vec<Stmt>,
+    ) {
+        while let Some(node) = cur {
+            if Some(node) == stop {
+                return;
+            }
+
+            if let Some(lp) = &lp {
+                if Some(node) == lp.follow {
+                    out.push(Stmt::Break);
+                    return;
+                }
+
+                if node == lp.header {
+                    out.push(Stmt::Continue);
+                    return;
+                }
+
+                // Leaving the loop for somewhere other than the follow is
+                // fine, as long as it never comes back.
+                let leaves = !lp.body.contains(&node)
+                    && (self.reaches(node, lp.header)
+                        || lp.follow.iter().any(|follow| self.reaches(node, *follow)));
+
+                if leaves {
+                    self.goto(node, out);
+                    return;
+                }
+            }
+
+            if self.emitted.contains(&node) {
+                self.goto(node, out);
+                return;
+            }
+
+            cur = match self.loops.get(&node).cloned() {
+                Some(inner) => {
+                    let mut body = Vec::new();
+                    let next = self.block(node, None, Some(inner.clone()), &mut body);
+                    self.region(next, None, Some(inner.clone()), &mut body);
+
+                    out.push(Stmt::Loop(body));
+                    inner.follow
+                }
+                None => self.block(node, stop, lp.clone(), out),
+            };
+        }
+    }
+
+    /// Emits the block `node`, and any `if` it ends in.
+    /// Returns where to carry on after it.
+    fn block(
+        &mut self,
+        node: usize,
+        stop: Option<usize>,
+        lp: Option<Rc<Loop>>,
+        out: &mut Vec<Stmt>,
+    ) -> Option<usize> {
+        self.emitted.insert(node);
+
+        let Node { stmts, exit } = self.nodes[&node].clone();
+        out.push(Stmt::Label(node));
+        out.extend(stmts);
+
+        match exit {
+            Exit::Stop => None,
+            Exit::Next(next) => Some(next),
+            Exit::Branch {
+                taken, fallthrough, ..
+            } if taken == fallthrough => Some(taken),
+            Exit::Branch {
+                cond,
+                taken,
+                fallthrough,
+            } => {
+                let mut join = self.ipdom.get(&node).copied().flatten();
+
+                // Each side leaves the loop on its own.
+                if let Some(lp) = &lp {
+                    if join.iter().any(|join| !lp.body.contains(join)) {
+                        join = None;
+                    }
+                }
+
+                let (mut then, mut els) = (Vec::new(), Vec::new());
+                self.region(Some(taken), join.or(stop), lp.clone(), &mut then);
+                self.region(Some(fallthrough), join.or(stop), lp, &mut els);
+
+                out.push(Stmt::If { cond, then, els });
+                join
+            }
+        }
+    }
+}
+
+/// Drops the labels nothing jumps to.
+fn strip_labels(stmts: Vec<Stmt>, targets: &BTreeSet<usize>) -> Vec<Stmt> {
+    stmts
+        .into_iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Label(addr) if !targets.contains(&addr) => None,
+            Stmt::If { cond, then, els } => Some(Stmt::If {
+                cond,
+                then: strip_labels(then, targets),
+                els: strip_labels(els, targets),
+            }),
+            Stmt::Loop(body) => Some(Stmt::Loop(strip_labels(body, targets))),
+            other => Some(other),
+        })
+        .collect()
+}
+
+/// Whether control never gets past the end of `stmts`.
+fn diverges(stmts: &[Stmt]) -> bool {
+    matches!(
+        stmts.last(),
+        Some(Stmt::Break)
+            | Some(Stmt::Continue)
+            | Some(Stmt::Return)
+            | Some(Stmt::Halt)
+            | Some(Stmt::Goto(_))
+            | Some(Stmt::GotoIndirect(_))
+            | Some(Stmt::Invalid(_))
+    )
+}
+
+/// Drops the `continue`s at the very end of a loop body.
+fn strip_continue(body: &mut Vec<Stmt>) {
+    match body.last_mut() {
+        Some(Stmt::Continue) => {
+            body.pop();
+        }
+        Some(Stmt::If { then, els, .. }) => {
+            strip_continue(then);
+            strip_continue(els);
+        }
+        _ => {}
+    }
+}
+
+/// Tidies up the output of [`Structurer`]: flips `if`s with empty bodies,
+/// pulls `else`s out after bodies that don't fall through, and turns loops
+/// that start by checking whether to stop into `while`s.
+fn simplify(stmts: Vec<Stmt>) -> Vec<Stmt> {
+    let mut out = Vec::new();
+
+    for stmt in stmts {
+        match stmt {
+            Stmt::If { cond, then, els } => {
+                let (then, els) = (simplify(then), simplify(els));
+
+                let (cond, then, els) = if then.is_empty() {
+                    (!cond, els, then)
+                } else {
+                    (cond, then, els)
+                };
+
+                // Conditions have no side effects.
+                if then.is_empty() {
+                    continue;
+                }
+
+                if diverges(&then) {
+                    out.push(Stmt::If {
+                        cond,
+                        then,
+                        els: Vec::new(),
+                    });
+                    out.extend(els);
+                } else {
+                    out.push(Stmt::If { cond, then, els });
+                }
+            }
+            Stmt::Loop(mut body) => {
+                strip_continue(&mut body);
+                let mut body = simplify(body);
+
+                let exits_first = matches!(
+                    body.first(),
+                    Some(Stmt::If { then, els, .. }) if then == &[Stmt::Break] && els.is_empty()
+                );
+
+                if exits_first {
+                    if let Stmt::If { cond, .. } = body.remove(0) {
+                        out.push(Stmt::While { cond: !cond, body });
+                    }
+                } else {
+                    out.push(Stmt::Loop(body));
+                }
+            }
+            other => out.push(other),
+        }
+    }
+
+    out
+}
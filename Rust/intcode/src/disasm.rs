@@ -0,0 +1,228 @@
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+use core::fmt;
+
+use crate::opcode::{Mnemonic, Mode, Opcode};
+
+/// A decoded parameter.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Operand {
+    Position(isize),
+    Immediate(isize),
+    Relative(isize),
+}
+
+impl Operand {
+    pub fn new(mode: Mode, word: isize) -> Self {
+        match mode {
+            Mode::Position => Self::Position(word),
+            Mode::Immediate => Self::Immediate(word),
+            Mode::Relative => Self::Relative(word),
+        }
+    }
+
+    /// The mode digit of the operand, and the word that holds it.
+    fn encode(self) -> (isize, isize) {
+        match self {
+            Self::Position(word) => (0, word),
+            Self::Immediate(word) => (1, word),
+            Self::Relative(word) => (2, word),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Position(addr) => write!(f, "[{}]", addr),
+            Self::Immediate(value) => write!(f, "{}", value),
+            Self::Relative(offset) if offset < 0 => write!(f, "[rb-{}]", -offset),
+            Self::Relative(offset) => write!(f, "[rb+{}]", offset),
+        }
+    }
+}
+
+/// An instruction, together with the address it was decoded at.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Instruction {
+    pub addr: usize,
+    pub mnemonic: Mnemonic,
+    operands: [Operand; 3],
+}
+
+impl Instruction {
+    /// An instruction at `addr`. `operands` must have as many
+    /// operands as the instruction takes.
+    pub fn new(addr: usize, mnemonic: Mnemonic, operands: &[Operand]) -> Self {
+        assert_eq!(operands.len(), mnemonic.arity());
+
+        let mut all = [Operand::Immediate(0); 3];
+        all[..operands.len()].copy_from_slice(operands);
+
+        Self {
+            addr,
+            mnemonic,
+            operands: all,
+        }
+    }
+
+    /// Decodes the instruction at `addr`, if it is a valid one,
+    /// and all of its parameters are within `words`.
+    pub fn decode(words: &[isize], addr: usize) -> Option<Self> {
+        let Opcode { mnemonic, modes } = Opcode::parse(*words.get(addr)?).ok()?;
+
+        let params = words.get(addr + 1..addr + 1 + mnemonic.arity())?;
+
+        let mut operands = [Operand::Immediate(0); 3];
+        for (idx, word) in params.iter().enumerate() {
+            operands[idx] = Operand::new(modes[idx], *word);
+        }
+
+        Some(Self {
+            addr,
+            mnemonic,
+            operands,
+        })
+    }
+
+    pub fn operands(&self) -> &[Operand] {
+        &self.operands[..self.mnemonic.arity()]
+    }
+
+    /// The number of words this instruction takes up.
+    pub fn size(&self) -> usize {
+        1 + self.mnemonic.arity()
+    }
+
+    /// The address right after this instruction.
+    pub fn next(&self) -> usize {
+        self.addr + self.size()
+    }
+
+    /// The words of the instruction, as they are stored in memory.
+    pub fn encode(&self) -> Vec<isize> {
+        let mut opcode = self.mnemonic.code();
+        let mut words = vec![0];
+
+        for (operand, scale) in self.operands().iter().zip(&[100, 1000, 10_000]) {
+            let (mode, word) = operand.encode();
+            opcode += mode * scale;
+            words.push(word);
+        }
+
+        words[0] = opcode;
+        words
+    }
+
+    /// The operand this instruction writes to, if any.
+    pub fn written(&self) -> Option<Operand> {
+        match self.mnemonic {
+            Mnemonic::Add | Mnemonic::Mul | Mnemonic::LessThan | Mnemonic::Equals => {
+                Some(self.operands[2])
+            }
+            Mnemonic::Save => Some(self.operands[0]),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic.name())?;
+
+        for (idx, operand) in self.operands().iter().enumerate() {
+            let sep = if idx == 0 { " " } else { ", " };
+            write!(f, "{}{}", sep, operand)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A line of a listing: either an instruction, or a single word of data.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Line {
+    Instruction(Instruction),
+    Data { addr: usize, value: isize },
+}
+
+impl Line {
+    pub fn addr(&self) -> usize {
+        match self {
+            Self::Instruction(instruction) => instruction.addr,
+            Self::Data { addr, .. } => *addr,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Instruction(instruction) => instruction.fmt(f),
+            Self::Data { value, .. } => write!(f, ".word {}", value),
+        }
+    }
+}
+
+/// Lists `words` from the start. Anything that decodes as an instruction is
+/// shown as one, unless it would run into one of the known instruction
+/// `starts`, in which case it is shown as data instead.
+pub fn listing(words: &[isize], starts: &BTreeSet<usize>) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut addr = 0;
+
+    while addr < words.len() {
+        let next_start = starts.range(addr + 1..).next().copied();
+
+        match Instruction::decode(words, addr) {
+            Some(instruction) if next_start.iter().all(|&start| instruction.next() <= start) => {
+                lines.push(Line::Instruction(instruction));
+                addr = instruction.next();
+            }
+            _ => {
+                lines.push(Line::Data {
+                    addr,
+                    value: words[addr],
+                });
+                addr += 1;
+            }
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listing_prefers_known_starts() {
+        // Jumps over the `1` at address 3, which would otherwise swallow the halt.
+        let words = [1105, 1, 5, 1, 0, 99, 0, 0];
+
+        let lines = listing(&words, &BTreeSet::new())
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, ["jt 1, 5", "add [0], [99], [0]", ".word 0"]);
+
+        let starts = [0, 5].iter().copied().collect();
+        let lines = listing(&words, &starts)
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            ["jt 1, 5", ".word 1", ".word 0", "hlt", ".word 0", ".word 0"]
+        );
+    }
+
+    #[test]
+    fn relative_operands() {
+        let instruction = Instruction::decode(&[21201, -2, 7, 3], 0).unwrap();
+
+        assert_eq!(instruction.to_string(), "add [rb-2], 7, [rb+3]");
+        assert_eq!(instruction.next(), 4);
+        assert_eq!(instruction.encode(), [21201, -2, 7, 3]);
+    }
+}
@@ -0,0 +1,301 @@
+//! Messages made of a fixed number of words.
+//!
+//! Programs often output a few words at a time that belong together, such
+//! as a position and what is there. A [`Framed`] sink groups them into
+//! messages, and a [`Flattened`] stream turns messages back into the words
+//! a program reads.
+
+use alloc::vec::Vec;
+use core::{array, convert::Infallible, fmt, marker::PhantomData};
+
+use super::{sink::Sink, stream::Stream, Poll};
+
+/// A message that is read from `N` words.
+pub trait Decode<T, const N: usize>: Sized {
+    type Error;
+
+    fn decode(words: [T; N]) -> Result<Self, Self::Error>;
+}
+
+impl<T, const N: usize> Decode<T, N> for [T; N] {
+    type Error = Infallible;
+
+    fn decode(words: [T; N]) -> Result<Self, Self::Error> {
+        Ok(words)
+    }
+}
+
+/// A message that is written as `N` words.
+pub trait Encode<T, const N: usize> {
+    fn encode(self) -> [T; N];
+}
+
+impl<T, const N: usize> Encode<T, N> for [T; N] {
+    fn encode(self) -> [T; N] {
+        self
+    }
+}
+
+/// Collects words until there are `N` of them.
+#[derive(Clone, Debug)]
+pub struct Framer<T, const N: usize> {
+    words: Vec<T>,
+}
+
+impl<T, const N: usize> Default for Framer<T, N> {
+    fn default() -> Self {
+        Self {
+            words: Vec::with_capacity(N),
+        }
+    }
+}
+
+impl<T, const N: usize> Framer<T, N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a word, and returns the frame it completes, if it does.
+    pub fn push(&mut self, word: T) -> Option<[T; N]> {
+        self.words.push(word);
+        if self.words.len() < N {
+            return None;
+        }
+
+        let mut words = self.words.drain(..);
+        Some(array::from_fn(|_| words.next().unwrap()))
+    }
+
+    /// The number of words of the frame that is not complete yet.
+    pub fn pending(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Whether the next word completes a frame.
+    pub fn is_last(&self) -> bool {
+        self.words.len() + 1 >= N
+    }
+}
+
+/// Why a [`Framed`] sink did not pass on a message.
+#[derive(Debug, Eq, PartialEq)]
+pub enum FrameError<D, E> {
+    /// The words don't make a message.
+    Decode(D),
+    /// The sink behind it failed.
+    Sink(E),
+    /// It ended with `words` words of a frame of `expected`.
+    Incomplete { words: usize, expected: usize },
+}
+
+impl<D: fmt::Display, E: fmt::Display> fmt::Display for FrameError<D, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Decode(e) => write!(f, "invalid message: {}", e),
+            Self::Sink(e) => write!(f, "{}", e),
+            Self::Incomplete { words, expected } => {
+                write!(f, "incomplete message: {} of {} words", words, expected)
+            }
+        }
+    }
+}
+
+/// A sink of words, that sends every `N` of them on to `inner` as an `M`.
+pub struct Framed<S, M, T, const N: usize> {
+    inner: S,
+    framer: Framer<T, N>,
+    message: PhantomData<M>,
+}
+
+impl<S, M, T, const N: usize> Framed<S, M, T, N>
+where
+    S: Sink<M>,
+    M: Decode<T, N>,
+{
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            framer: Framer::new(),
+            message: PhantomData,
+        }
+    }
+
+    /// Returns the sink behind it, or an error if a message was cut off.
+    pub fn finish(self) -> Result<S, FrameError<M::Error, S::Error>> {
+        match self.framer.pending() {
+            0 => Ok(self.inner),
+            words => Err(FrameError::Incomplete { words, expected: N }),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, M, T, const N: usize> Sink<T> for Framed<S, M, T, N>
+where
+    S: Sink<M>,
+    M: Decode<T, N>,
+{
+    type Error = FrameError<M::Error, S::Error>;
+
+    fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+        // Only the last word of a frame goes through to the sink.
+        if !self.framer.is_last() {
+            return Poll::Ready(Ok(()));
+        }
+
+        match ready!(self.inner.poll_ready()) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(e) => Poll::Ready(Err(FrameError::Sink(e))),
+        }
+    }
+
+    fn send(&mut self, word: T) -> Result<(), Self::Error> {
+        match self.framer.push(word) {
+            Some(words) => {
+                let message = M::decode(words).map_err(FrameError::Decode)?;
+                self.inner.send(message).map_err(FrameError::Sink)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// A stream of the words of the messages `inner` yields.
+pub struct Flattened<S, T, const N: usize> {
+    inner: S,
+    words: Option<array::IntoIter<T, N>>,
+}
+
+impl<S, T, const N: usize> Flattened<S, T, N>
+where
+    S: Stream,
+    S::Item: Encode<T, N>,
+{
+    pub fn new(inner: S) -> Self {
+        Self { inner, words: None }
+    }
+
+    /// Returns the stream behind it, and the words of the current message
+    /// that were not read yet.
+    pub fn into_inner(self) -> (S, Vec<T>) {
+        (self.inner, self.words.into_iter().flatten().collect())
+    }
+}
+
+impl<S, T, const N: usize> Stream for Flattened<S, T, N>
+where
+    S: Stream,
+    S::Item: Encode<T, N>,
+{
+    type Item = T;
+
+    fn poll_next(&mut self) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(word) = self.words.as_mut().and_then(Iterator::next) {
+                return Poll::Ready(Some(word));
+            }
+
+            match ready!(self.inner.poll_next()) {
+                Some(message) => self.words = Some(IntoIterator::into_iter(message.encode())),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        future::{
+            stream::{once, StreamExt},
+            FutureExt,
+        },
+        machine::Machine,
+    };
+
+    #[derive(Debug, Eq, PartialEq)]
+    enum Paint {
+        Black(isize),
+        White(isize),
+    }
+
+    impl Decode<isize, 2> for Paint {
+        type Error = isize;
+
+        fn decode([color, at]: [isize; 2]) -> Result<Self, Self::Error> {
+            match color {
+                0 => Ok(Paint::Black(at)),
+                1 => Ok(Paint::White(at)),
+                color => Err(color),
+            }
+        }
+    }
+
+    impl Encode<isize, 2> for (isize, isize) {
+        fn encode(self) -> [isize; 2] {
+            [self.0, self.1]
+        }
+    }
+
+    struct Messages<M>(Vec<M>);
+
+    impl<M> Sink<M> for Messages<M> {
+        type Error = Infallible;
+
+        fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn send(&mut self, message: M) -> Result<(), Self::Error> {
+            self.0.push(message);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn frames_and_flattens() {
+        // Outputs the sum and the product of every pair it reads.
+        let program = vec![
+            3, 100, 3, 101, 1, 100, 101, 102, 2, 100, 101, 103, 4, 102, 4, 103, 1105, 1, 0,
+        ];
+        let mut memory = program;
+        memory.resize(104, 0);
+
+        let input = Flattened::new(once((2, 5)).chain(once((3, 4))));
+        let mut output = Framed::<_, [isize; 2], _, 2>::new(Messages(Vec::new()));
+
+        let result = Machine::new(memory, input, &mut output).execute();
+        assert!(result.is_err());
+        assert_eq!(output.finish().unwrap().0, vec![[7, 10], [7, 12]]);
+    }
+
+    #[test]
+    fn framing_errors() {
+        let mut framed = Framed::<_, Paint, _, 2>::new(Messages(Vec::new()));
+        assert_eq!(framed.send(1), Ok(()));
+        assert_eq!(framed.send(4), Ok(()));
+        assert_eq!(framed.send(2), Ok(()));
+        assert_eq!(framed.send(4), Err(FrameError::Decode(2)));
+        assert_eq!(framed.send(0), Ok(()));
+
+        assert!(matches!(
+            framed.finish(),
+            Err(FrameError::Incomplete {
+                words: 1,
+                expected: 2
+            })
+        ));
+
+        let mut framed = Framed::<_, Paint, _, 2>::new(Messages(Vec::new()));
+        for word in [0, 3, 1, 5] {
+            framed.send(word).unwrap();
+        }
+        let messages = framed.finish().unwrap().0;
+        assert_eq!(messages, vec![Paint::Black(3), Paint::White(5)]);
+    }
+}
@@ -7,10 +7,7 @@ pub enum Poll<T> {
 
 impl<T> Poll<T> {
     pub fn is_ready(&self) -> bool {
-        match self {
-            Self::Ready(_) => true,
-            _ => false,
-        }
+        matches!(self, Self::Ready(_))
     }
 }
 
@@ -18,6 +15,35 @@ pub trait Future {
     type Output;
 
     fn poll(&mut self) -> Poll<Self::Output>;
+
+    /// The simulated time at which the future does its next step, if it
+    /// keeps one. Joins poll the earliest futures first.
+    fn clock(&self) -> Option<u64> {
+        None
+    }
+
+    /// Tells the future that nothing it waits on happens before `time`,
+    /// so it may as well idle until then.
+    fn advance_to(&mut self, _time: u64) {}
+}
+
+impl<F: ?Sized> Future for &mut F
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(&mut self) -> Poll<Self::Output> {
+        (**self).poll()
+    }
+
+    fn clock(&self) -> Option<u64> {
+        (**self).clock()
+    }
+
+    fn advance_to(&mut self, time: u64) {
+        (**self).advance_to(time)
+    }
 }
 
 pub trait FutureExt: Future {
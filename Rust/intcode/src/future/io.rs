@@ -0,0 +1,370 @@
+//! Streams and sinks for readers and writers.
+//!
+//! [`ReadWords`] reads numbers, or other words, separated by whitespace or
+//! commas, and [`ReadAscii`] reads text a byte at a time. Both end when
+//! their reader does, or when it fails, which they keep the error of.
+//! [`ReadWords`] can go on after a word it didn't accept.
+//!
+//! [`WriteNumbers`] writes a number per line, and [`WriteText`] writes
+//! ASCII as text, and anything else as a number on its own line.
+
+use std::{
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use super::{sink::Sink, stream::Stream, Poll};
+
+#[derive(Debug)]
+pub enum ReadErrorKind {
+    Io(io::Error),
+    InvalidWord(String),
+    NotAscii(u8),
+}
+
+/// Why a stream stopped before its reader ended.
+#[derive(Debug)]
+pub struct ReadError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ReadErrorKind,
+}
+
+impl ReadError {
+    const fn new(line: usize, column: usize, kind: ReadErrorKind) -> Self {
+        Self { line, column, kind }
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+
+        match &self.kind {
+            ReadErrorKind::Io(e) => write!(f, "{}", e),
+            ReadErrorKind::InvalidWord(word) => write!(f, "invalid word '{}'", word),
+            ReadErrorKind::NotAscii(byte) => write!(f, "not ASCII: {:#04x}", byte),
+        }
+    }
+}
+
+fn parse_number(word: &str) -> Option<isize> {
+    word.parse().ok()
+}
+
+/// A stream of the words of a reader, as `parse` makes them.
+pub struct ReadWords<R, F = fn(&str) -> Option<isize>> {
+    reader: R,
+    parse: F,
+    line: String,
+    /// The line number, and where the next word starts in it.
+    at: (usize, usize),
+    error: Option<ReadError>,
+}
+
+impl<R: BufRead> ReadWords<R> {
+    /// Reads integers.
+    pub fn numbers(reader: R) -> Self {
+        Self::new(reader, parse_number)
+    }
+}
+
+impl<R: BufRead, F: FnMut(&str) -> Option<isize>> ReadWords<R, F> {
+    /// Reads the words `parse` accepts, and ends at any other.
+    pub fn new(reader: R, parse: F) -> Self {
+        Self {
+            reader,
+            parse,
+            line: String::new(),
+            at: (0, 0),
+            error: None,
+        }
+    }
+
+    /// Why the stream ended, if it didn't end with its reader.
+    pub fn error(&self) -> Option<&ReadError> {
+        self.error.as_ref()
+    }
+
+    /// Takes the error the stream ended at, so that it goes on after it.
+    pub fn take_error(&mut self) -> Option<ReadError> {
+        self.error.take()
+    }
+
+    /// Whether there are words left on the line that was read last, so
+    /// that the next one doesn't wait on the reader.
+    pub fn has_buffered(&self) -> bool {
+        !self.line[self.at.1..]
+            .trim_start_matches(is_separator)
+            .is_empty()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn fail(&mut self, column: usize, kind: ReadErrorKind) -> Poll<Option<isize>> {
+        self.error = Some(ReadError::new(self.at.0, column, kind));
+        Poll::Ready(None)
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || c == ','
+}
+
+impl<R: BufRead, F: FnMut(&str) -> Option<isize>> Stream for ReadWords<R, F> {
+    type Item = isize;
+
+    fn poll_next(&mut self) -> Poll<Option<Self::Item>> {
+        if self.error.is_some() {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            let rest = &self.line[self.at.1..];
+            let start = self.at.1 + (rest.len() - rest.trim_start_matches(is_separator).len());
+
+            if start < self.line.len() {
+                let rest = &self.line[start..];
+                let end = start + rest.find(is_separator).unwrap_or(rest.len());
+                self.at.1 = end;
+
+                return match (self.parse)(&self.line[start..end]) {
+                    Some(value) => Poll::Ready(Some(value)),
+                    None => {
+                        let word = self.line[start..end].to_string();
+                        self.fail(start + 1, ReadErrorKind::InvalidWord(word))
+                    }
+                };
+            }
+
+            self.line.clear();
+            self.at = (self.at.0 + 1, 0);
+
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return Poll::Ready(None),
+                Ok(_) => {}
+                Err(e) => return self.fail(1, ReadErrorKind::Io(e)),
+            }
+        }
+    }
+}
+
+/// A stream of the bytes of a reader, which are all ASCII.
+pub struct ReadAscii<R> {
+    reader: R,
+    /// Where the next byte is, by line and column.
+    at: (usize, usize),
+    error: Option<ReadError>,
+}
+
+impl<R: BufRead> ReadAscii<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            at: (1, 1),
+            error: None,
+        }
+    }
+
+    /// Why the stream ended, if it didn't end with its reader.
+    pub fn error(&self) -> Option<&ReadError> {
+        self.error.as_ref()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> Stream for ReadAscii<R> {
+    type Item = isize;
+
+    fn poll_next(&mut self) -> Poll<Option<Self::Item>> {
+        if self.error.is_some() {
+            return Poll::Ready(None);
+        }
+
+        let (line, column) = self.at;
+        let byte = match self.reader.fill_buf() {
+            Ok([]) => return Poll::Ready(None),
+            Ok(buf) => buf[0],
+            Err(e) => {
+                self.error = Some(ReadError::new(line, column, ReadErrorKind::Io(e)));
+                return Poll::Ready(None);
+            }
+        };
+
+        if !byte.is_ascii() {
+            self.error = Some(ReadError::new(line, column, ReadErrorKind::NotAscii(byte)));
+            return Poll::Ready(None);
+        }
+
+        self.reader.consume(1);
+        self.at = match byte {
+            b'\n' => (line + 1, 1),
+            _ => (line, column + 1),
+        };
+
+        Poll::Ready(Some(byte as isize))
+    }
+}
+
+/// A sink that writes every number on a line of its own.
+pub struct WriteNumbers<W> {
+    writer: W,
+}
+
+impl<W: Write> WriteNumbers<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Sink<isize> for WriteNumbers<W> {
+    type Error = io::Error;
+
+    fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn send(&mut self, value: isize) -> Result<(), Self::Error> {
+        writeln!(self.writer, "{}", value)
+    }
+}
+
+/// A sink that writes ASCII as text, and other numbers on a line of
+/// their own. Lines are flushed as they end.
+pub struct WriteText<W> {
+    writer: W,
+}
+
+impl<W: Write> WriteText<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Sink<isize> for WriteText<W> {
+    type Error = io::Error;
+
+    fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn send(&mut self, value: isize) -> Result<(), Self::Error> {
+        match value {
+            0..=0x7f => self.writer.write_all(&[value as u8])?,
+            _ => writeln!(self.writer, "{}", value)?,
+        }
+
+        if value == b'\n' as isize {
+            self.writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{collections::VecDeque, sync::mpsc, thread};
+
+    use crate::{future::FutureExt, machine::Machine};
+
+    // Echoes its input until it ends.
+    const ECHO: [isize; 8] = [3, 7, 4, 7, 1105, 1, 0, 0];
+
+    #[test]
+    fn read_words() {
+        let mut words = ReadWords::numbers("1,2, -3\n\n  4 x5\n6".as_bytes());
+        let mut numbers = Vec::new();
+        while let Poll::Ready(Some(n)) = words.poll_next() {
+            numbers.push(n);
+        }
+
+        assert_eq!(numbers, vec![1, 2, -3, 4]);
+        assert_eq!(words.error().unwrap().to_string(), "3:5: invalid word 'x5'");
+        assert!(!words.has_buffered());
+
+        // Goes on after the invalid word.
+        assert!(words.take_error().is_some());
+        assert!(matches!(words.poll_next(), Poll::Ready(Some(6))));
+        assert!(matches!(words.poll_next(), Poll::Ready(None)));
+        assert!(words.error().is_none());
+
+        let mut words = ReadWords::numbers("1 2\n".as_bytes());
+        assert!(!words.has_buffered());
+        assert!(matches!(words.poll_next(), Poll::Ready(Some(1))));
+        assert!(words.has_buffered());
+        assert!(matches!(words.poll_next(), Poll::Ready(Some(2))));
+        assert!(!words.has_buffered());
+
+        let moves = |word: &str| match word {
+            "l" => Some(-1),
+            "n" => Some(0),
+            "r" => Some(1),
+            _ => None,
+        };
+        let mut output = WriteNumbers::new(Vec::new());
+        let input = ReadWords::new("l n\nr".as_bytes(), moves);
+        let result = Machine::new(ECHO.to_vec(), input, &mut output).execute();
+
+        assert!(matches!(
+            result,
+            Err(crate::machine::MachineError::ReaderExhausted)
+        ));
+        assert_eq!(output.into_inner(), b"-1\n0\n1\n");
+    }
+
+    #[test]
+    fn read_and_write_text() {
+        let mut output = WriteText::new(Vec::new());
+        let input = ReadAscii::new("hi\n".as_bytes());
+        let _ = Machine::new(ECHO.to_vec(), input, &mut output).execute();
+
+        let _ = output.send(12345);
+        assert_eq!(output.into_inner(), b"hi\n12345\n");
+
+        let mut input = ReadAscii::new(&b"a\n\xffb"[..]);
+        while let Poll::Ready(Some(_)) = input.poll_next() {}
+        assert_eq!(input.error().unwrap().to_string(), "2:1: not ASCII: 0xff");
+    }
+
+    #[test]
+    fn queues_and_channels() {
+        let (to_machine, input) = mpsc::channel();
+        let (output, from_machine) = mpsc::channel();
+
+        let host = thread::spawn(move || {
+            let mut echoed = VecDeque::new();
+            for n in 0..5 {
+                to_machine.send(n).unwrap();
+                echoed.push_back(from_machine.recv().unwrap());
+            }
+            echoed
+        });
+
+        // The machine waits on the host, until it hangs up.
+        let result = Machine::new(ECHO.to_vec(), input, output).execute();
+        assert!(result.is_err());
+
+        let mut echoed = host.join().unwrap();
+        assert_eq!(echoed.len(), 5);
+
+        let mut output = VecDeque::new();
+        let _ = Machine::new(ECHO.to_vec(), &mut echoed, &mut output).execute();
+        assert_eq!(output, vec![0, 1, 2, 3, 4]);
+    }
+}
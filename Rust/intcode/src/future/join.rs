@@ -1,4 +1,7 @@
 #![allow(non_snake_case)]
+
+use alloc::vec::Vec;
+
 use super::{Future, Poll};
 
 enum MaybeDone<Fut: Future> {
@@ -28,9 +31,28 @@ where
     }
 }
 
+impl<Fut: Future> MaybeDone<Fut> {
+    fn is_done(&self) -> bool {
+        !matches!(self, Self::Future(_))
+    }
+}
+
 impl<Fut: Future> Future for MaybeDone<Fut> {
     type Output = ();
 
+    fn clock(&self) -> Option<u64> {
+        match self {
+            Self::Future(f) => f.clock(),
+            Self::Done(_) | Self::Gone => None,
+        }
+    }
+
+    fn advance_to(&mut self, time: u64) {
+        if let Self::Future(f) = self {
+            f.advance_to(time);
+        }
+    }
+
     fn poll(&mut self) -> Poll<Self::Output> {
         let res = match self {
             Self::Future(f) => ready!(f.poll()),
@@ -62,22 +84,120 @@ macro_rules! generate {
         impl<$($Fut: Future),*> Future for $Join<$($Fut),*> {
             type Output = ($($Fut::Output),*);
 
+            /// Polls the futures whose clock is the earliest, as well as
+            /// those without one. When none of them gets anywhere, they
+            /// are waiting on the others, so they idle until the next one.
             fn poll(&mut self) -> Poll<Self::Output> {
+                let now = self.clock();
                 let mut all_done = true;
+                let mut progressed = false;
                 $(
-                    all_done &= self.$Fut.poll().is_ready();
+                    let before = self.$Fut.clock();
+                    if !self.$Fut.is_done() && (before.is_none() || before == now) {
+                        let ready = self.$Fut.poll().is_ready();
+                        progressed |= ready || before.is_none() || self.$Fut.clock() != before;
+                    }
+                    all_done &= self.$Fut.is_done();
                 )*
 
                 if all_done {
-                    Poll::Ready(($(self.$Fut.take_output().unwrap()), *))
-                } else {
-                    Poll::Running
+                    return Poll::Ready(($(self.$Fut.take_output().unwrap()), *));
                 }
+
+                if let (false, Some(now)) = (progressed, now) {
+                    let next = None$(.into_iter().chain(self.$Fut.clock()))*
+                        .filter(|&clock| clock > now)
+                        .min();
+
+                    if let Some(next) = next {
+                        self.advance_to(next);
+                    }
+                }
+
+                Poll::Running
+            }
+
+            fn clock(&self) -> Option<u64> {
+                None$(.into_iter().chain(self.$Fut.clock()))*.min()
+            }
+
+            fn advance_to(&mut self, time: u64) {
+                $(
+                    if matches!(self.$Fut.clock(), Some(clock) if clock < time) {
+                        self.$Fut.advance_to(time);
+                    }
+                )*
             }
         }
     )*)
 }
 
+/// Joins any number of futures of the same type.
+pub struct JoinAll<Fut: Future> {
+    futures: Vec<MaybeDone<Fut>>,
+}
+
+/// Runs all `futures` together, and collects their outputs in order.
+pub fn join_all<I>(futures: I) -> JoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    JoinAll {
+        futures: futures.into_iter().map(maybe_done).collect(),
+    }
+}
+
+impl<Fut: Future> Future for JoinAll<Fut> {
+    type Output = Vec<Fut::Output>;
+
+    /// Polls the futures like the fixed size joins do.
+    fn poll(&mut self) -> Poll<Self::Output> {
+        let now = self.clock();
+        let mut progressed = false;
+
+        for future in self.futures.iter_mut() {
+            let before = future.clock();
+            if !future.is_done() && (before.is_none() || before == now) {
+                let ready = future.poll().is_ready();
+                progressed |= ready || before.is_none() || future.clock() != before;
+            }
+        }
+
+        if self.futures.iter().all(MaybeDone::is_done) {
+            let outputs = self.futures.iter_mut().map(|f| f.take_output().unwrap());
+            return Poll::Ready(outputs.collect());
+        }
+
+        if let (false, Some(now)) = (progressed, now) {
+            let next = self
+                .futures
+                .iter()
+                .filter_map(MaybeDone::clock)
+                .filter(|&clock| clock > now)
+                .min();
+
+            if let Some(next) = next {
+                self.advance_to(next);
+            }
+        }
+
+        Poll::Running
+    }
+
+    fn clock(&self) -> Option<u64> {
+        self.futures.iter().filter_map(MaybeDone::clock).min()
+    }
+
+    fn advance_to(&mut self, time: u64) {
+        for future in self.futures.iter_mut() {
+            if matches!(future.clock(), Some(clock) if clock < time) {
+                future.advance_to(time);
+            }
+        }
+    }
+}
+
 generate! {
     (Join, <Fut1, Fut2>),
 
@@ -1,6 +1,7 @@
 #[macro_use]
 pub mod macros;
 
+#[allow(clippy::module_inception)]
 pub mod future;
 pub use future::{Future, FutureExt, Poll};
 
@@ -8,4 +9,10 @@ pub mod stream;
 
 pub mod sink;
 
+pub mod frame;
+
+#[cfg(feature = "std")]
+pub mod io;
+
 mod join;
+pub use join::{join_all, JoinAll};
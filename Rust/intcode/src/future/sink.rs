@@ -1,5 +1,11 @@
 use super::Poll;
 
+use alloc::collections::VecDeque;
+use core::convert::Infallible;
+
+#[cfg(feature = "std")]
+use std::sync::mpsc::{SendError, Sender};
+
 pub trait Sink<Item> {
     type Error;
 
@@ -26,6 +32,12 @@ pub struct Stdout<T> {
     inner: Option<T>,
 }
 
+impl<T> Default for Stdout<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> Stdout<T> {
     pub const fn new() -> Self {
         Self { inner: None }
@@ -37,7 +49,7 @@ impl<T> Stdout<T> {
 }
 
 impl<T> Sink<T> for Stdout<T> {
-    type Error = !;
+    type Error = Infallible;
 
     fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
         Poll::Ready(Ok(()))
@@ -48,3 +60,30 @@ impl<T> Sink<T> for Stdout<T> {
         Ok(())
     }
 }
+
+/// Adds items to the back.
+impl<T> Sink<T> for VecDeque<T> {
+    type Error = Infallible;
+
+    fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn send(&mut self, item: T) -> Result<(), Self::Error> {
+        self.push_back(item);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Sink<T> for Sender<T> {
+    type Error = SendError<T>;
+
+    fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn send(&mut self, item: T) -> Result<(), Self::Error> {
+        Sender::send(self, item)
+    }
+}
@@ -1,5 +1,10 @@
 use super::future::Poll;
 
+use alloc::collections::VecDeque;
+
+#[cfg(feature = "std")]
+use std::sync::mpsc::{Receiver, TryRecvError};
+
 pub trait Stream {
     type Item;
 
@@ -46,11 +51,11 @@ impl<T> Stream for Once<T> {
 }
 
 pub struct Empty<T> {
-    inner: std::marker::PhantomData<T>,
+    inner: core::marker::PhantomData<T>,
 }
 
 pub fn empty<T>() -> Empty<T> {
-    Empty { inner: std::marker::PhantomData }
+    Empty { inner: core::marker::PhantomData }
 }
 
 impl <T> Stream for Empty<T> {
@@ -93,3 +98,26 @@ where
         self.second.poll_next()
     }
 }
+
+/// Takes items from the front, and ends once it is empty.
+impl<T> Stream for VecDeque<T> {
+    type Item = T;
+
+    fn poll_next(&mut self) -> Poll<Option<Self::Item>> {
+        Poll::Ready(self.pop_front())
+    }
+}
+
+/// Waits on items, and ends once every sender is gone.
+#[cfg(feature = "std")]
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(&mut self) -> Poll<Option<Self::Item>> {
+        match self.try_recv() {
+            Ok(item) => Poll::Ready(Some(item)),
+            Err(TryRecvError::Empty) => Poll::Running,
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+        }
+    }
+}
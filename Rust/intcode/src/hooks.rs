@@ -0,0 +1,206 @@
+use crate::opcode::Opcode;
+
+/// A view of a machine, handed to [`Hooks`].
+pub struct State<'a, T> {
+    pub ip: usize,
+    pub base: isize,
+    pub memory: &'a [T],
+}
+
+/// Observes a running [`Machine`](crate::machine::Machine).
+///
+/// Every method does nothing by default, so an implementation only
+/// has to provide the events it cares about. An instruction that is
+/// blocked on I/O is only reported once, when it is first attempted.
+pub trait Hooks<T> {
+    #[inline(always)]
+    fn before_instruction(&mut self, _opcode: &Opcode, _state: &State<'_, T>) {}
+
+    /// Called once the instruction at `ip` has executed. `state.ip` is
+    /// where the machine continues.
+    #[inline(always)]
+    fn after_instruction(&mut self, _ip: usize, _opcode: &Opcode, _state: &State<'_, T>) {}
+
+    #[inline(always)]
+    fn on_write(&mut self, _addr: usize, _old: &T, _new: &T) {}
+
+    /// Called after the input has been written to `addr`.
+    #[inline(always)]
+    fn on_input(&mut self, _addr: usize, _value: &T) {}
+
+    #[inline(always)]
+    fn on_output(&mut self, _value: &T) {}
+
+    #[inline(always)]
+    fn on_halt(&mut self, _state: &State<'_, T>) {}
+}
+
+/// The default hooks, which do nothing.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoHooks;
+
+impl<T> Hooks<T> for NoHooks {}
+
+impl<T, H: ?Sized> Hooks<T> for &mut H
+where
+    H: Hooks<T>,
+{
+    #[inline(always)]
+    fn before_instruction(&mut self, opcode: &Opcode, state: &State<'_, T>) {
+        (**self).before_instruction(opcode, state)
+    }
+
+    #[inline(always)]
+    fn after_instruction(&mut self, ip: usize, opcode: &Opcode, state: &State<'_, T>) {
+        (**self).after_instruction(ip, opcode, state)
+    }
+
+    #[inline(always)]
+    fn on_write(&mut self, addr: usize, old: &T, new: &T) {
+        (**self).on_write(addr, old, new)
+    }
+
+    #[inline(always)]
+    fn on_input(&mut self, addr: usize, value: &T) {
+        (**self).on_input(addr, value)
+    }
+
+    #[inline(always)]
+    fn on_output(&mut self, value: &T) {
+        (**self).on_output(value)
+    }
+
+    #[inline(always)]
+    fn on_halt(&mut self, state: &State<'_, T>) {
+        (**self).on_halt(state)
+    }
+}
+
+/// Runs both hooks, first `A`, then `B`.
+impl<T, A, B> Hooks<T> for (A, B)
+where
+    A: Hooks<T>,
+    B: Hooks<T>,
+{
+    #[inline(always)]
+    fn before_instruction(&mut self, opcode: &Opcode, state: &State<'_, T>) {
+        self.0.before_instruction(opcode, state);
+        self.1.before_instruction(opcode, state);
+    }
+
+    #[inline(always)]
+    fn after_instruction(&mut self, ip: usize, opcode: &Opcode, state: &State<'_, T>) {
+        self.0.after_instruction(ip, opcode, state);
+        self.1.after_instruction(ip, opcode, state);
+    }
+
+    #[inline(always)]
+    fn on_write(&mut self, addr: usize, old: &T, new: &T) {
+        self.0.on_write(addr, old, new);
+        self.1.on_write(addr, old, new);
+    }
+
+    #[inline(always)]
+    fn on_input(&mut self, addr: usize, value: &T) {
+        self.0.on_input(addr, value);
+        self.1.on_input(addr, value);
+    }
+
+    #[inline(always)]
+    fn on_output(&mut self, value: &T) {
+        self.0.on_output(value);
+        self.1.on_output(value);
+    }
+
+    #[inline(always)]
+    fn on_halt(&mut self, state: &State<'_, T>) {
+        self.0.on_halt(state);
+        self.1.on_halt(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        channel::Channel,
+        future::{sink::Stdout, stream::once, Future, FutureExt},
+        machine::Machine,
+    };
+
+    #[derive(Default)]
+    struct Counter {
+        before: usize,
+        after: usize,
+        writes: Vec<(usize, isize, isize)>,
+        inputs: Vec<isize>,
+        outputs: Vec<isize>,
+        halted: bool,
+    }
+
+    impl Hooks<isize> for Counter {
+        fn before_instruction(&mut self, _: &Opcode, _: &State<'_, isize>) {
+            self.before += 1;
+        }
+
+        fn after_instruction(&mut self, _: usize, _: &Opcode, _: &State<'_, isize>) {
+            self.after += 1;
+        }
+
+        fn on_write(&mut self, addr: usize, old: &isize, new: &isize) {
+            self.writes.push((addr, *old, *new));
+        }
+
+        fn on_input(&mut self, _: usize, value: &isize) {
+            self.inputs.push(*value);
+        }
+
+        fn on_output(&mut self, value: &isize) {
+            self.outputs.push(*value);
+        }
+
+        fn on_halt(&mut self, _: &State<'_, isize>) {
+            self.halted = true;
+        }
+    }
+
+    #[test]
+    fn hooks_see_every_event() {
+        let mut stdout = Stdout::new();
+        let mut counter = Counter::default();
+
+        // Doubles its input.
+        let mut machine = Machine::with_hooks(
+            vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0],
+            once(21),
+            &mut stdout,
+            &mut counter,
+        );
+
+        assert!(machine.execute().is_ok());
+
+        assert_eq!(counter.before, 4);
+        assert_eq!(counter.after, 3);
+        assert_eq!(counter.writes, vec![(9, 0, 21), (9, 21, 42)]);
+        assert_eq!(counter.inputs, vec![21]);
+        assert_eq!(counter.outputs, vec![42]);
+        assert!(counter.halted);
+    }
+
+    #[test]
+    fn blocked_instructions_are_seen_once() {
+        let channel = Channel::empty();
+        let (_, rx) = channel.split();
+
+        let mut machine =
+            Machine::with_hooks(vec![3, 0, 99], rx, Stdout::new(), Counter::default());
+
+        for _ in 0..10 {
+            assert!(!machine.poll().is_ready());
+        }
+
+        assert_eq!(machine.hooks().before, 1);
+        assert_eq!(machine.hooks().after, 0);
+    }
+}
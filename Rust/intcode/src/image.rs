@@ -0,0 +1,177 @@
+//! Memory that machines share until they write to it.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::ops::Deref;
+
+use crate::program::Program;
+
+/// A program loaded once, for any number of machines to start from.
+/// Cloning an image is cheap, and so is starting a machine from one:
+/// its memory is only copied when the machine first writes to it.
+#[derive(Debug, Eq, PartialEq, Hash)]
+pub struct Image<T = isize>(Arc<[T]>);
+
+impl<T> Clone for Image<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T> Image<T> {
+    pub fn new(words: Vec<T>) -> Self {
+        Self(words.into())
+    }
+
+    pub fn words(&self) -> &[T] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<&Program> for Image {
+    fn from(program: &Program) -> Self {
+        Self(program.words().into())
+    }
+}
+
+impl From<Program> for Image {
+    fn from(program: Program) -> Self {
+        Self::new(program.into_vec())
+    }
+}
+
+impl<T: Clone> From<&[T]> for Image<T> {
+    fn from(words: &[T]) -> Self {
+        Self(words.into())
+    }
+}
+
+impl<T> From<Vec<T>> for Image<T> {
+    fn from(words: Vec<T>) -> Self {
+        Self::new(words)
+    }
+}
+
+/// The memory of a machine, which is either its own, or an image it
+/// has not written to yet.
+#[derive(Debug)]
+pub(crate) enum Memory<T> {
+    Owned(Vec<T>),
+    Shared(Image<T>),
+}
+
+impl<T: Clone> Memory<T> {
+    /// The memory, copied out of the image first if it is shared.
+    #[inline]
+    pub(crate) fn to_mut(&mut self) -> &mut Vec<T> {
+        if let Memory::Shared(image) = self {
+            *self = Memory::Owned(image.words().to_vec());
+        }
+
+        match self {
+            Memory::Owned(words) => words,
+            Memory::Shared(_) => unreachable!(),
+        }
+    }
+
+    pub(crate) fn into_vec(self) -> Vec<T> {
+        match self {
+            Memory::Owned(words) => words,
+            Memory::Shared(image) => image.words().to_vec(),
+        }
+    }
+
+    /// Makes the memory `words` again, in place if it is its own.
+    pub(crate) fn reset(&mut self, words: &[T]) {
+        match self {
+            Memory::Owned(memory) => {
+                memory.clear();
+                memory.extend_from_slice(words);
+            }
+            Memory::Shared(_) => *self = Memory::Owned(words.to_vec()),
+        }
+    }
+
+    /// Makes the memory `image` again, in place if it is its own,
+    /// since that doesn't allocate.
+    pub(crate) fn reset_to_image(&mut self, image: &Image<T>) {
+        match self {
+            Memory::Owned(_) => self.reset(image.words()),
+            Memory::Shared(_) => *self = Memory::Shared(image.clone()),
+        }
+    }
+}
+
+impl<T> Deref for Memory<T> {
+    type Target = [T];
+
+    #[inline(always)]
+    fn deref(&self) -> &[T] {
+        match self {
+            Memory::Owned(words) => words,
+            Memory::Shared(image) => image.words(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        future::{sink::Stdout, stream::once, FutureExt},
+        machine::Machine,
+    };
+
+    // Doubles its input.
+    const DOUBLE: [isize; 10] = [3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+
+    #[test]
+    fn images_are_copied_on_write() {
+        let image = Image::new(DOUBLE.to_vec());
+
+        let mut machines = (1..=3)
+            .map(|n| Machine::from_image(&image, once(n), Stdout::new()))
+            .collect::<Vec<_>>();
+        assert_eq!(Arc::strong_count(&image.0), 4);
+
+        machines[0].execute().unwrap();
+        assert_eq!(Arc::strong_count(&image.0), 3);
+        assert_eq!(machines[0].memory()[9], 2);
+        assert_eq!(machines[1].memory(), image.words());
+
+        machines[2].execute().unwrap();
+        let (_, stdout) = machines[2].replace_io(once(0), Stdout::new());
+        assert_eq!(stdout.into_inner(), Some(6));
+        assert_eq!(image.words(), &DOUBLE[..]);
+    }
+
+    #[test]
+    fn reset_in_place() {
+        let program = Program::new(DOUBLE.to_vec());
+        let mut machine = Machine::new(DOUBLE.to_vec(), once(5), Stdout::new());
+        machine.execute().unwrap();
+
+        let memory = machine.memory().as_ptr();
+        for n in 0..3 {
+            machine.reset_from(&program);
+            assert_eq!((machine.ip(), machine.memory()), (0, &DOUBLE[..]));
+
+            machine.replace_io(once(n), Stdout::new());
+            machine.execute().unwrap();
+
+            let (_, stdout) = machine.replace_io(once(0), Stdout::new());
+            assert_eq!(stdout.into_inner(), Some(2 * n));
+        }
+
+        machine.reset_to(&Image::from(&program));
+        assert_eq!(machine.memory().as_ptr(), memory);
+    }
+}
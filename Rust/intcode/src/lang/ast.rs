@@ -0,0 +1,58 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+/// Where something starts in the source: a line and a column.
+pub(crate) type Pos = (usize, usize);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum UnOp {
+    Neg,
+    Not,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Expr {
+    Int(isize),
+    Var(String, Pos),
+    /// A call, including the built-in `input()` and `output(x)`.
+    Call(String, Vec<Expr>, Pos),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Stmt {
+    Let(String, Expr),
+    Assign(String, Expr, Pos),
+    If {
+        cond: Expr,
+        then: Vec<Stmt>,
+        els: Vec<Stmt>,
+    },
+    While {
+        cond: Expr,
+        body: Vec<Stmt>,
+    },
+    Return(Option<Expr>),
+    Expr(Expr),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Function {
+    pub(crate) name: String,
+    pub(crate) params: Vec<String>,
+    pub(crate) body: Vec<Stmt>,
+    pub(crate) pos: Pos,
+}
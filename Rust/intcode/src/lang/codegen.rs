@@ -0,0 +1,469 @@
+use alloc::{collections::BTreeMap, string::ToString, vec, vec::Vec};
+
+use super::{
+    ast::{BinOp, Expr, Function, Pos, Stmt, UnOp},
+    CompileError, CompileErrorKind, STACK_SIZE,
+};
+use crate::opcode::Mnemonic;
+
+/// A word of the program, which may depend on things that are only known
+/// once the code around it has been generated.
+#[derive(Copy, Clone, Debug)]
+enum Word {
+    Lit(isize),
+    /// The address of a label.
+    Label(usize),
+    /// `scale` times the size of the current frame, plus `offset`.
+    Frame {
+        scale: isize,
+        offset: isize,
+    },
+}
+
+/// A parameter of an instruction.
+#[derive(Copy, Clone, Debug)]
+enum Param {
+    Immediate(Word),
+    Relative(Word),
+}
+
+/// Where the value of an expression is.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Value {
+    Const(isize),
+    /// A slot of the current frame.
+    Slot(isize),
+}
+
+impl Value {
+    fn param(self) -> Param {
+        match self {
+            Self::Const(value) => Param::Immediate(Word::Lit(value)),
+            Self::Slot(slot) => Param::Relative(Word::Lit(slot)),
+        }
+    }
+}
+
+/// A slot of the frame of the function being called, which starts right
+/// after the current one.
+fn callee(slot: isize) -> Param {
+    Param::Relative(Word::Frame {
+        scale: 1,
+        offset: slot,
+    })
+}
+
+fn label(label: usize) -> Param {
+    Param::Immediate(Word::Label(label))
+}
+
+/// Generates the code for a whole program.
+///
+/// The relative base points at the frame of the running function: slot 0
+/// holds the return address, then come the parameters, the variables, and
+/// room for intermediate results. A call fills in the frame right after
+/// the current one, moves the relative base there, and jumps. The callee
+/// leaves its result in its first slot after the return address.
+pub(crate) struct Codegen<'a> {
+    code: Vec<Word>,
+    labels: Vec<Option<usize>>,
+    /// The label and number of parameters of every function.
+    functions: BTreeMap<&'a str, (usize, usize)>,
+    /// The variables in scope, innermost last.
+    scopes: Vec<BTreeMap<&'a str, isize>>,
+    /// The next slot for a variable.
+    next_var: isize,
+    /// The first slot for intermediate results.
+    temps: isize,
+    /// The number of intermediate results in use, and the most that were.
+    in_use: isize,
+    max_in_use: isize,
+}
+
+impl<'a> Codegen<'a> {
+    pub(crate) fn new() -> Self {
+        Self {
+            code: Vec::new(),
+            labels: Vec::new(),
+            functions: BTreeMap::new(),
+            scopes: Vec::new(),
+            next_var: 0,
+            temps: 0,
+            in_use: 0,
+            max_in_use: 0,
+        }
+    }
+
+    pub(crate) fn program(mut self, functions: &'a [Function]) -> Result<Vec<isize>, CompileError> {
+        for function in functions {
+            let (line, column) = function.pos;
+            let name = function.name.as_str();
+
+            if name == "input" || name == "output" || self.functions.contains_key(name) {
+                let kind = CompileErrorKind::DuplicateFunction(function.name.clone());
+                return Err(CompileError::new(line, column, kind));
+            }
+
+            let label = self.label();
+            self.functions.insert(name, (label, function.params.len()));
+        }
+
+        let main = match self.functions.get("main") {
+            Some(&(label, 0)) => label,
+            _ => return Err(CompileError::new(1, 1, CompileErrorKind::MissingMain)),
+        };
+
+        // Calls `main` with its frame at the start of the stack, and halts.
+        let (stack, exit) = (self.label(), self.label());
+        self.emit(Mnemonic::AdjustBase, &[label(stack)]);
+        self.emit(
+            Mnemonic::Add,
+            &[label(exit), Value::Const(0).param(), Value::Slot(0).param()],
+        );
+        self.jump(main);
+        self.place(exit);
+        self.emit(Mnemonic::Halt, &[]);
+
+        for function in functions {
+            self.function(function)?;
+        }
+
+        self.place(stack);
+
+        let labels = &self.labels;
+        let mut words = self
+            .code
+            .iter()
+            .map(|word| match *word {
+                Word::Lit(value) => value,
+                Word::Label(label) => labels[label].expect("label was never placed") as isize,
+                Word::Frame { .. } => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+        words.resize(words.len() + STACK_SIZE, 0);
+        Ok(words)
+    }
+
+    fn label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn place(&mut self, label: usize) {
+        self.labels[label] = Some(self.code.len());
+    }
+
+    fn emit(&mut self, mnemonic: Mnemonic, params: &[Param]) {
+        let mut opcode = mnemonic.code();
+        let mut words = Vec::new();
+
+        for (param, scale) in params.iter().zip(&[100, 1000, 10_000]) {
+            let word = match *param {
+                Param::Immediate(word) => {
+                    opcode += scale;
+                    word
+                }
+                Param::Relative(word) => {
+                    opcode += 2 * scale;
+                    word
+                }
+            };
+            words.push(word);
+        }
+
+        self.code.push(Word::Lit(opcode));
+        self.code.extend(words);
+    }
+
+    fn jump(&mut self, target: usize) {
+        self.emit(
+            Mnemonic::JumpIfTrue,
+            &[Value::Const(1).param(), label(target)],
+        );
+    }
+
+    fn temp(&mut self) -> Value {
+        let slot = self.temps + self.in_use;
+        self.in_use += 1;
+        self.max_in_use = self.max_in_use.max(self.in_use);
+        Value::Slot(slot)
+    }
+
+    fn lookup(&self, name: &str, (line, column): Pos) -> Result<isize, CompileError> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+            .ok_or_else(|| {
+                let kind = CompileErrorKind::UnknownVariable(name.to_string());
+                CompileError::new(line, column, kind)
+            })
+    }
+
+    fn function(&mut self, function: &'a Function) -> Result<(), CompileError> {
+        let start = self.code.len();
+        self.place(self.functions[function.name.as_str()].0);
+
+        let params = function.params.len() as isize;
+        self.scopes = vec![function
+            .params
+            .iter()
+            .zip(1..)
+            .map(|(name, slot)| (name.as_str(), slot))
+            .collect()];
+        self.next_var = 1 + params;
+        self.temps = self.next_var + lets(&function.body);
+        self.in_use = 0;
+        self.max_in_use = 0;
+
+        self.block(&function.body)?;
+        self.ret(Value::Const(0));
+
+        let frame = self.temps + self.max_in_use;
+        for word in self.code[start..].iter_mut() {
+            if let Word::Frame { scale, offset } = *word {
+                *word = Word::Lit(scale * frame + offset);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn block(&mut self, stmts: &'a [Stmt]) -> Result<(), CompileError> {
+        self.scopes.push(BTreeMap::new());
+        for stmt in stmts {
+            self.stmt(stmt)?;
+            self.in_use = 0;
+        }
+        self.scopes.pop();
+
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt) -> Result<(), CompileError> {
+        match stmt {
+            Stmt::Let(name, expr) => {
+                let slot = self.next_var;
+                self.next_var += 1;
+
+                self.expr_into(expr, slot)?;
+                self.scopes.last_mut().unwrap().insert(name, slot);
+            }
+            Stmt::Assign(name, expr, pos) => {
+                let slot = self.lookup(name, *pos)?;
+                self.expr_into(expr, slot)?;
+            }
+            Stmt::If { cond, then, els } => {
+                let (els_label, end) = (self.label(), self.label());
+
+                let cond = self.expr(cond)?;
+                self.emit(Mnemonic::JumpIfFalse, &[cond.param(), label(els_label)]);
+                self.block(then)?;
+                self.jump(end);
+                self.place(els_label);
+                self.block(els)?;
+                self.place(end);
+            }
+            Stmt::While { cond, body } => {
+                let (start, end) = (self.label(), self.label());
+
+                self.place(start);
+                let cond = self.expr(cond)?;
+                self.emit(Mnemonic::JumpIfFalse, &[cond.param(), label(end)]);
+                self.block(body)?;
+                self.jump(start);
+                self.place(end);
+            }
+            Stmt::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.expr(expr)?,
+                    None => Value::Const(0),
+                };
+                self.ret(value);
+            }
+            Stmt::Expr(expr) => {
+                self.expr(expr)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn ret(&mut self, value: Value) {
+        self.emit(
+            Mnemonic::Add,
+            &[
+                value.param(),
+                Value::Const(0).param(),
+                Value::Slot(1).param(),
+            ],
+        );
+        self.emit(
+            Mnemonic::JumpIfTrue,
+            &[Value::Const(1).param(), Value::Slot(0).param()],
+        );
+    }
+
+    /// Evaluates `expr`, into a new slot unless it is a constant or a variable.
+    fn expr(&mut self, expr: &'a Expr) -> Result<Value, CompileError> {
+        match expr {
+            Expr::Int(value) => Ok(Value::Const(*value)),
+            Expr::Var(name, pos) => Ok(Value::Slot(self.lookup(name, *pos)?)),
+            expr => match self.temp() {
+                Value::Slot(slot) => {
+                    self.expr_into(expr, slot)?;
+                    Ok(Value::Slot(slot))
+                }
+                Value::Const(_) => unreachable!(),
+            },
+        }
+    }
+
+    /// Evaluates `expr` into `slot`.
+    fn expr_into(&mut self, expr: &'a Expr, slot: isize) -> Result<(), CompileError> {
+        let dest = Value::Slot(slot).param();
+        let zero = Value::Const(0).param();
+
+        match expr {
+            Expr::Int(_) | Expr::Var(..) => {
+                let value = self.expr(expr)?;
+                if value != Value::Slot(slot) {
+                    self.emit(Mnemonic::Add, &[value.param(), zero, dest]);
+                }
+            }
+            Expr::Call(name, args, pos) => self.call(name, args, *pos, slot)?,
+            Expr::Unary(op, inner) => {
+                let value = self.expr(inner)?.param();
+                match op {
+                    UnOp::Neg => self.emit(Mnemonic::Mul, &[value, Value::Const(-1).param(), dest]),
+                    UnOp::Not => self.emit(Mnemonic::Equals, &[value, zero, dest]),
+                }
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (self.expr(lhs)?, self.expr(rhs)?);
+                let (lhs, rhs) = match op {
+                    BinOp::Sub => match rhs {
+                        Value::Const(value) => (lhs, Value::Const(-value)),
+                        rhs => {
+                            let negated = self.temp();
+                            let minus_one = Value::Const(-1).param();
+                            self.emit(Mnemonic::Mul, &[rhs.param(), minus_one, negated.param()]);
+                            (lhs, negated)
+                        }
+                    },
+                    // `a > b` is `b < a`, and `a <= b` is `!(b < a)`.
+                    BinOp::Gt | BinOp::Le => (rhs, lhs),
+                    _ => (lhs, rhs),
+                };
+
+                let mnemonic = match op {
+                    BinOp::Add | BinOp::Sub => Mnemonic::Add,
+                    BinOp::Mul => Mnemonic::Mul,
+                    BinOp::Eq | BinOp::Ne => Mnemonic::Equals,
+                    BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => Mnemonic::LessThan,
+                };
+                self.emit(mnemonic, &[lhs.param(), rhs.param(), dest]);
+
+                if let BinOp::Ne | BinOp::Le | BinOp::Ge = op {
+                    self.emit(Mnemonic::Equals, &[dest, zero, dest]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn call(
+        &mut self,
+        name: &str,
+        args: &'a [Expr],
+        (line, column): Pos,
+        slot: isize,
+    ) -> Result<(), CompileError> {
+        let dest = Value::Slot(slot).param();
+
+        let (target, arity) = match name {
+            "input" => (None, 0),
+            "output" => (None, 1),
+            name => match self.functions.get(name) {
+                Some(&(target, arity)) => (Some(target), arity),
+                None => {
+                    let kind = CompileErrorKind::UnknownFunction(name.to_string());
+                    return Err(CompileError::new(line, column, kind));
+                }
+            },
+        };
+
+        if args.len() != arity {
+            let kind = CompileErrorKind::ArgumentCount {
+                name: name.to_string(),
+                expected: arity,
+                found: args.len(),
+            };
+            return Err(CompileError::new(line, column, kind));
+        }
+
+        let target = match (target, args) {
+            (None, []) => {
+                self.emit(Mnemonic::Save, &[dest]);
+                return Ok(());
+            }
+            // Outputs the value, and evaluates to it.
+            (None, [arg]) => {
+                self.expr_into(arg, slot)?;
+                self.emit(Mnemonic::Output, &[dest]);
+                return Ok(());
+            }
+            (None, _) => unreachable!(),
+            (Some(target), _) => target,
+        };
+
+        // The arguments are all evaluated before any of them is stored,
+        // as they may make calls of their own.
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.expr(arg)?);
+        }
+
+        let zero = Value::Const(0).param();
+        for (value, param) in values.into_iter().zip(1..) {
+            self.emit(Mnemonic::Add, &[value.param(), zero, callee(param)]);
+        }
+
+        let ret = self.label();
+        self.emit(Mnemonic::Add, &[label(ret), zero, callee(0)]);
+        self.emit(
+            Mnemonic::AdjustBase,
+            &[Param::Immediate(Word::Frame {
+                scale: 1,
+                offset: 0,
+            })],
+        );
+        self.jump(target);
+        self.place(ret);
+        self.emit(
+            Mnemonic::AdjustBase,
+            &[Param::Immediate(Word::Frame {
+                scale: -1,
+                offset: 0,
+            })],
+        );
+        self.emit(Mnemonic::Add, &[callee(1), zero, dest]);
+
+        Ok(())
+    }
+}
+
+/// The number of variables declared in `stmts`, each of which gets a slot.
+fn lets(stmts: &[Stmt]) -> isize {
+    stmts
+        .iter()
+        .map(|stmt| match stmt {
+            Stmt::Let(..) => 1,
+            Stmt::If { then, els, .. } => lets(then) + lets(els),
+            Stmt::While { body, .. } => lets(body),
+            _ => 0,
+        })
+        .sum()
+}
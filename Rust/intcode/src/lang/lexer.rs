@@ -0,0 +1,198 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use super::{CompileError, CompileErrorKind};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Token {
+    Int(isize),
+    Ident(String),
+    Fn,
+    Let,
+    If,
+    Else,
+    While,
+    Return,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    Semicolon,
+    Assign,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Plus,
+    Minus,
+    Star,
+    Not,
+    Eof,
+}
+
+impl Token {
+    /// How the token reads in error messages.
+    pub(crate) fn describe(&self) -> String {
+        let symbol = match self {
+            Self::Int(value) => return format!("`{}`", value),
+            Self::Ident(name) => return format!("`{}`", name),
+            Self::Eof => return "the end of the input".to_string(),
+            Self::Fn => "fn",
+            Self::Let => "let",
+            Self::If => "if",
+            Self::Else => "else",
+            Self::While => "while",
+            Self::Return => "return",
+            Self::LParen => "(",
+            Self::RParen => ")",
+            Self::LBrace => "{",
+            Self::RBrace => "}",
+            Self::Comma => ",",
+            Self::Semicolon => ";",
+            Self::Assign => "=",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Star => "*",
+            Self::Not => "!",
+        };
+
+        format!("`{}`", symbol)
+    }
+}
+
+/// A token, with the line and column it starts at.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Spanned {
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+    pub(crate) token: Token,
+}
+
+/// Splits `src` into tokens, skipping whitespace and `//` comments.
+/// The last token is always [`Token::Eof`].
+pub(crate) fn tokenize(src: &str) -> Result<Vec<Spanned>, CompileError> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut column = 1;
+    let mut chars = src.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let (start_line, start_column) = (line, column);
+
+        if c == '\n' {
+            line += 1;
+            column = 1;
+            continue;
+        }
+        column += 1;
+
+        let mut next_is = |expected: char| {
+            if chars.peek() == Some(&expected) {
+                chars.next();
+                column += 1;
+                true
+            } else {
+                false
+            }
+        };
+
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '/' if next_is('/') => {
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            ',' => Token::Comma,
+            ';' => Token::Semicolon,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '=' if next_is('=') => Token::Eq,
+            '=' => Token::Assign,
+            '!' if next_is('=') => Token::Ne,
+            '!' => Token::Not,
+            '<' if next_is('=') => Token::Le,
+            '<' => Token::Lt,
+            '>' if next_is('=') => Token::Ge,
+            '>' => Token::Gt,
+            c if c.is_ascii_digit() || c.is_alphabetic() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                    column += 1;
+                }
+
+                if c.is_ascii_digit() {
+                    let value = word.parse().map_err(|_| {
+                        CompileError::new(
+                            start_line,
+                            start_column,
+                            CompileErrorKind::InvalidNumber(word),
+                        )
+                    })?;
+                    Token::Int(value)
+                } else {
+                    keyword(&word).unwrap_or(Token::Ident(word))
+                }
+            }
+            c => {
+                return Err(CompileError::new(
+                    start_line,
+                    start_column,
+                    CompileErrorKind::UnexpectedChar(c),
+                ))
+            }
+        };
+
+        tokens.push(Spanned {
+            line: start_line,
+            column: start_column,
+            token,
+        });
+    }
+
+    tokens.push(Spanned {
+        line,
+        column,
+        token: Token::Eof,
+    });
+
+    Ok(tokens)
+}
+
+fn keyword(word: &str) -> Option<Token> {
+    Some(match word {
+        "fn" => Token::Fn,
+        "let" => Token::Let,
+        "if" => Token::If,
+        "else" => Token::Else,
+        "while" => Token::While,
+        "return" => Token::Return,
+        _ => return None,
+    })
+}
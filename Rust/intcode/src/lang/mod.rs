@@ -0,0 +1,234 @@
+//! A tiny language that compiles to intcode.
+//!
+//! A program is a list of functions, and runs `main`. Every value is an
+//! integer:
+//!
+//! ```text
+//! fn fib(n) {
+//!     if n < 2 {
+//!         return n;
+//!     }
+//!     return fib(n - 1) + fib(n - 2);
+//! }
+//!
+//! fn main() {
+//!     output(fib(input()));
+//! }
+//! ```
+//!
+//! There are `let` bindings and assignments, `if`/`else`, `while`, and the
+//! operators `+ - * == != < <= > >=` and `!`. Comparisons evaluate to 1 or
+//! 0, and conditions hold when they are not 0. `input()` reads a value,
+//! and `output(x)` writes one. Functions keep their frames on a stack,
+//! addressed through the relative base, so they may recurse.
+
+mod ast;
+mod codegen;
+mod lexer;
+mod parser;
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use codegen::Codegen;
+use parser::Parser;
+
+/// The number of words reserved for the stack, after the code.
+pub const STACK_SIZE: usize = 1024;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CompileErrorKind {
+    /// A character that does not start any token.
+    UnexpectedChar(char),
+    /// An integer literal that does not fit in a word.
+    InvalidNumber(String),
+    Expected {
+        expected: String,
+        found: String,
+    },
+    UnknownVariable(String),
+    UnknownFunction(String),
+    /// A function defined twice, or named after a builtin.
+    DuplicateFunction(String),
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// There is no `main` without parameters.
+    MissingMain,
+}
+
+/// An error while compiling a program.
+/// Lines and columns both start at 1.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompileError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: CompileErrorKind,
+}
+
+impl CompileError {
+    const fn new(line: usize, column: usize, kind: CompileErrorKind) -> Self {
+        Self { line, column, kind }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+
+        match &self.kind {
+            CompileErrorKind::UnexpectedChar(c) => write!(f, "unexpected character `{}`", c),
+            CompileErrorKind::InvalidNumber(word) => write!(f, "invalid number `{}`", word),
+            CompileErrorKind::Expected { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            CompileErrorKind::UnknownVariable(name) => write!(f, "unknown variable `{}`", name),
+            CompileErrorKind::UnknownFunction(name) => write!(f, "unknown function `{}`", name),
+            CompileErrorKind::DuplicateFunction(name) => {
+                write!(f, "function `{}` is already defined", name)
+            }
+            CompileErrorKind::ArgumentCount {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` takes {} argument(s), but {} were given",
+                name, expected, found
+            ),
+            CompileErrorKind::MissingMain => write!(f, "expected a function `main()`"),
+        }
+    }
+}
+
+/// Compiles a program to intcode, followed by room for its stack.
+pub fn compile(src: &str) -> Result<Vec<isize>, CompileError> {
+    let tokens = lexer::tokenize(src)?;
+    let functions = Parser::new(tokens).program()?;
+
+    Codegen::new().program(&functions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        channel::Channel,
+        future::{
+            sink::Stdout,
+            stream::{empty, once},
+            FutureExt,
+        },
+        machine::Machine,
+    };
+
+    fn run(src: &str, input: isize) -> Option<isize> {
+        let mut stdout = Stdout::new();
+        let mut machine = Machine::new(compile(src).unwrap(), once(input), &mut stdout);
+        machine.execute().unwrap();
+
+        stdout.into_inner()
+    }
+
+    #[test]
+    fn recursive_fibonacci() {
+        let src = "
+            // The n-th Fibonacci number, the slow way.
+            fn fib(n) {
+                if n < 2 {
+                    return n;
+                }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn main() {
+                output(fib(input()));
+            }
+        ";
+
+        assert_eq!(run(src, 10), Some(55));
+        assert_eq!(run(src, 20), Some(6765));
+    }
+
+    #[test]
+    fn operators() {
+        let src = "
+            fn main() {
+                let x = input();
+                let y = 0;
+                if x >= 3 { y = y + 1; }
+                if x <= 3 { y = y + 10; }
+                if x != 4 { y = y + 100; }
+                if !(x > 3) { y = y + 1000; }
+                output(y * -1 - (2 - x));
+            }
+        ";
+
+        assert_eq!(run(src, 3), Some(-1110));
+        assert_eq!(run(src, 4), Some(1));
+    }
+
+    #[test]
+    fn producer_and_consumer() {
+        let producer = compile(
+            "
+            fn main() {
+                let i = 1;
+                while i <= 100 {
+                    output(i);
+                    i = i + 1;
+                }
+                output(0);
+            }
+        ",
+        )
+        .unwrap();
+
+        let consumer = compile(
+            "
+            fn main() {
+                let sum = 0;
+                let x = input();
+                while x != 0 {
+                    sum = sum + x;
+                    x = input();
+                }
+                output(sum);
+            }
+        ",
+        )
+        .unwrap();
+
+        let channel = Channel::empty();
+        let (tx, rx) = channel.split();
+        let mut stdout = Stdout::new();
+
+        let (p, c) = Machine::new(producer, empty(), tx)
+            .join(Machine::new(consumer, rx, &mut stdout))
+            .execute();
+
+        assert!(p.is_ok() && c.is_ok());
+        assert_eq!(stdout.into_inner(), Some(5050));
+    }
+
+    #[test]
+    fn errors_point_at_their_cause() {
+        let err = compile("fn main() {\n    output(x);\n}").unwrap_err();
+        assert_eq!(
+            err,
+            CompileError::new(2, 12, CompileErrorKind::UnknownVariable("x".to_string()))
+        );
+
+        let err = compile("fn f(a, b) { return a; }\nfn main() { f(1); }").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "2:13: `f` takes 2 argument(s), but 1 were given"
+        );
+
+        let err = compile("fn main() { let = 1; }").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 17));
+    }
+}
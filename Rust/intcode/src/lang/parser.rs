@@ -0,0 +1,266 @@
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use super::{
+    ast::{BinOp, Expr, Function, Pos, Stmt, UnOp},
+    lexer::{Spanned, Token},
+    CompileError, CompileErrorKind,
+};
+
+/// A recursive descent parser over the tokens of a program.
+pub(crate) struct Parser {
+    tokens: Vec<Spanned>,
+    idx: usize,
+}
+
+impl Parser {
+    pub(crate) fn new(tokens: Vec<Spanned>) -> Self {
+        Self { tokens, idx: 0 }
+    }
+
+    /// Parses the whole program, which is a list of functions.
+    pub(crate) fn program(mut self) -> Result<Vec<Function>, CompileError> {
+        let mut functions = Vec::new();
+
+        while self.peek() != &Token::Eof {
+            functions.push(self.function()?);
+        }
+
+        Ok(functions)
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.idx].token
+    }
+
+    fn pos(&self) -> Pos {
+        let token = &self.tokens[self.idx];
+        (token.line, token.column)
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.idx].token.clone();
+        if token != Token::Eof {
+            self.idx += 1;
+        }
+        token
+    }
+
+    /// Skips the next token if it is `token`, and returns whether it was.
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == token {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), CompileError> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            Err(self.unexpected(token.describe()))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, CompileError> {
+        match self.peek() {
+            Token::Ident(name) => {
+                let name = name.clone();
+                self.next();
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name".to_string())),
+        }
+    }
+
+    fn unexpected(&self, expected: String) -> CompileError {
+        let (line, column) = self.pos();
+        let found = self.peek().describe();
+        CompileError::new(line, column, CompileErrorKind::Expected { expected, found })
+    }
+
+    fn function(&mut self) -> Result<Function, CompileError> {
+        let pos = self.pos();
+        self.expect(Token::Fn)?;
+        let name = self.ident()?;
+
+        self.expect(Token::LParen)?;
+        let mut params = Vec::new();
+        while !self.eat(&Token::RParen) {
+            if !params.is_empty() {
+                self.expect(Token::Comma)?;
+            }
+            params.push(self.ident()?);
+        }
+
+        let body = self.block()?;
+
+        Ok(Function {
+            name,
+            params,
+            body,
+            pos,
+        })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        self.expect(Token::LBrace)?;
+
+        let mut stmts = Vec::new();
+        while !self.eat(&Token::RBrace) {
+            stmts.push(self.stmt()?);
+        }
+
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt, CompileError> {
+        let stmt = match self.peek() {
+            Token::Let => {
+                self.next();
+                let name = self.ident()?;
+                self.expect(Token::Assign)?;
+                Stmt::Let(name, self.expr()?)
+            }
+            Token::If => return self.if_stmt(),
+            Token::While => {
+                self.next();
+                let cond = self.expr()?;
+                let body = self.block()?;
+                return Ok(Stmt::While { cond, body });
+            }
+            Token::Return => {
+                self.next();
+                match self.peek() {
+                    Token::Semicolon => Stmt::Return(None),
+                    _ => Stmt::Return(Some(self.expr()?)),
+                }
+            }
+            // An assignment, if the name is followed by a single `=`.
+            Token::Ident(name) if self.tokens[self.idx + 1].token == Token::Assign => {
+                let name = name.clone();
+                let pos = self.pos();
+                self.next();
+                self.next();
+                Stmt::Assign(name, self.expr()?, pos)
+            }
+            _ => Stmt::Expr(self.expr()?),
+        };
+
+        self.expect(Token::Semicolon)?;
+        Ok(stmt)
+    }
+
+    fn if_stmt(&mut self) -> Result<Stmt, CompileError> {
+        self.expect(Token::If)?;
+        let cond = self.expr()?;
+        let then = self.block()?;
+
+        let els = if !self.eat(&Token::Else) {
+            Vec::new()
+        } else if self.peek() == &Token::If {
+            vec![self.if_stmt()?]
+        } else {
+            self.block()?
+        };
+
+        Ok(Stmt::If { cond, then, els })
+    }
+
+    /// Comparisons bind the loosest, and don't chain.
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        let lhs = self.sum()?;
+
+        let op = match self.peek() {
+            Token::Eq => BinOp::Eq,
+            Token::Ne => BinOp::Ne,
+            Token::Lt => BinOp::Lt,
+            Token::Le => BinOp::Le,
+            Token::Gt => BinOp::Gt,
+            Token::Ge => BinOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.next();
+
+        let rhs = self.sum()?;
+        Ok(Expr::Binary(op, Box::new(lhs), Box::new(rhs)))
+    }
+
+    fn sum(&mut self) -> Result<Expr, CompileError> {
+        let mut lhs = self.product()?;
+
+        loop {
+            let op = match self.peek() {
+                Token::Plus => BinOp::Add,
+                Token::Minus => BinOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.next();
+
+            let rhs = self.product()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, CompileError> {
+        let mut lhs = self.unary()?;
+
+        while self.eat(&Token::Star) {
+            let rhs = self.unary()?;
+            lhs = Expr::Binary(BinOp::Mul, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        let op = match self.peek() {
+            Token::Minus => UnOp::Neg,
+            Token::Not => UnOp::Not,
+            _ => return self.primary(),
+        };
+        self.next();
+
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, CompileError> {
+        let pos = self.pos();
+
+        match self.peek().clone() {
+            Token::Int(value) => {
+                self.next();
+                Ok(Expr::Int(value))
+            }
+            Token::Ident(name) => {
+                self.next();
+                if !self.eat(&Token::LParen) {
+                    return Ok(Expr::Var(name, pos));
+                }
+
+                let mut args = Vec::new();
+                while !self.eat(&Token::RParen) {
+                    if !args.is_empty() {
+                        self.expect(Token::Comma)?;
+                    }
+                    args.push(self.expr()?);
+                }
+
+                Ok(Expr::Call(name, args, pos))
+            }
+            Token::LParen => {
+                self.next();
+                let expr = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            _ => Err(self.unexpected("an expression".to_string())),
+        }
+    }
+}
@@ -1,7 +1,38 @@
+//! An intcode machine, and tools to build, inspect and run programs.
+//!
+//! With the `std` feature, which is on by default, it uses the standard
+//! library. Without it, the crate only needs `core` and `alloc`, and
+//! leaves out what touches files or threads: loading programs and test
+//! cases, core files on disk, and searching on several threads.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod channel;
 
 #[macro_use]
 pub mod future;
 
+pub mod coredump;
+pub mod cost;
+pub mod coverage;
+pub mod decompile;
+pub mod disasm;
+pub mod hooks;
+pub mod image;
+pub mod lang;
 pub mod machine;
 pub mod opcode;
+pub mod optimize;
+pub mod pipeline;
+pub mod program;
+pub mod resume;
+pub mod rewind;
+#[cfg(feature = "std")]
+pub mod search;
+pub mod symbolic;
+pub mod taint;
+#[cfg(feature = "std")]
+pub mod testing;
+pub mod transpile;
@@ -1,11 +1,25 @@
-use std::{
-    convert::{TryFrom, TryInto},
+use alloc::vec::Vec;
+use core::{
+    convert::{Infallible, TryFrom, TryInto},
+    fmt,
+    hash::Hash,
+    mem,
     num::TryFromIntError,
 };
 
+#[cfg(feature = "std")]
+use std::{io, path::PathBuf};
+
+#[cfg(feature = "std")]
+use crate::coredump::{CoreDump, Trace};
 use crate::{
+    coredump::Core,
+    cost::{Clock, CostModel, Stats},
     future::{sink::Sink, stream::Stream, Future, Poll},
+    hooks::{Hooks, NoHooks, State},
+    image::{Image, Memory},
     opcode::{Mnemonic, Mode, Opcode, OpcodeError},
+    program::Program,
 };
 
 #[derive(Debug)]
@@ -18,6 +32,9 @@ pub enum MachineError {
     SinkSendError,
     SinkPrepareError,
     OpcodeError(OpcodeError<isize, isize>),
+    /// The machine came back to the same state without doing any I/O.
+    /// `period` is the number of instructions in between.
+    InfiniteLoop { ip: usize, period: usize },
 }
 
 impl From<TryFromIntError> for MachineError {
@@ -26,8 +43,8 @@ impl From<TryFromIntError> for MachineError {
     }
 }
 
-impl From<!> for MachineError {
-    fn from(never: !) -> Self {
+impl From<Infallible> for MachineError {
+    fn from(never: Infallible) -> Self {
         match never {}
     }
 }
@@ -38,23 +55,259 @@ impl From<OpcodeError<isize, isize>> for MachineError {
     }
 }
 
-pub struct Machine<T, R: Stream<Item = T>, W: Sink<T>> {
+pub struct Machine<T, R: Stream<Item = T>, W: Sink<T>, H = NoHooks> {
     ip: usize,
     base: isize,
-    memory: Vec<T>,
+    memory: Memory<T>,
     reader: R,
     writer: W,
+    hooks: H,
+    /// The current instruction, if it is waiting on I/O,
+    /// so hooks don't see it start more than once.
+    blocked: Option<Mnemonic>,
+    loops: Option<LoopDetector<T>>,
+    #[cfg(feature = "std")]
+    dump: Option<CoreDump<T>>,
+    clock: Option<Clock>,
+}
+
+/// Watches for a machine coming back to a state it was in since its
+/// last I/O, sampled every `interval` instructions.
+///
+/// This is Brent's algorithm: samples are compared to a single snapshot,
+/// which moves up to the latest sample whenever the distance to it
+/// doubles, so a loop is found within about twice the samples it takes to
+/// get into it and around it once.
+struct LoopDetector<T> {
+    interval: usize,
+    steps: usize,
+    next_sample: usize,
+    snapshot: Option<Snapshot<T>>,
+    /// How many samples the snapshot is kept for.
+    power: usize,
+}
+
+struct Snapshot<T> {
+    steps: usize,
+    ip: usize,
+    base: isize,
+    memory: Vec<T>,
+}
+
+impl<T> LoopDetector<T> {
+    fn new(interval: usize) -> Self {
+        let interval = interval.max(1);
+
+        Self {
+            interval,
+            steps: 0,
+            next_sample: interval,
+            snapshot: None,
+            power: 1,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.steps = 0;
+        self.next_sample = self.interval;
+        self.snapshot = None;
+        self.power = 1;
+    }
+}
+
+impl<T: Clone + Eq> LoopDetector<T> {
+    /// Counts an instruction, and returns the period if the machine has
+    /// been in this state before. The state is only looked at when sampled.
+    #[inline]
+    fn step(&mut self, state: &State<'_, T>) -> Option<usize> {
+        self.steps += 1;
+        if self.steps < self.next_sample {
+            return None;
+        }
+        self.next_sample += self.interval;
+
+        match self.snapshot.as_mut() {
+            Some(snapshot) => {
+                if snapshot.ip == state.ip
+                    && snapshot.base == state.base
+                    && snapshot.memory[..] == state.memory[..]
+                {
+                    return Some(self.steps - snapshot.steps);
+                }
+
+                if self.steps - snapshot.steps < self.power * self.interval {
+                    return None;
+                }
+
+                snapshot.steps = self.steps;
+                snapshot.ip = state.ip;
+                snapshot.base = state.base;
+                snapshot.memory.clear();
+                snapshot.memory.extend_from_slice(state.memory);
+            }
+            None => {
+                self.snapshot = Some(Snapshot {
+                    steps: self.steps,
+                    ip: state.ip,
+                    base: state.base,
+                    memory: state.memory.to_vec(),
+                })
+            }
+        }
+
+        self.power *= 2;
+        None
+    }
 }
 
 impl<T, R: Stream<Item = T>, W: Sink<T>> Machine<T, R, W> {
     #[inline(always)]
     pub fn new(memory: Vec<T>, reader: R, writer: W) -> Self {
+        Self::with_hooks(memory, reader, writer, NoHooks)
+    }
+
+    /// A machine that starts on `image`, without copying it until it
+    /// writes to it.
+    pub fn from_image(image: &Image<T>, reader: R, writer: W) -> Self {
+        let mut machine = Self::new(Vec::new(), reader, writer);
+        machine.memory = Memory::Shared(image.clone());
+        machine
+    }
+}
+
+impl<T, R: Stream<Item = T>, W: Sink<T>, H: Hooks<T>> Machine<T, R, W, H> {
+    #[inline(always)]
+    pub fn with_hooks(memory: Vec<T>, reader: R, writer: W, hooks: H) -> Self {
         Self {
             ip: 0,
             base: 0,
-            memory,
+            memory: Memory::Owned(memory),
             reader,
             writer,
+            hooks,
+            blocked: None,
+            loops: None,
+            #[cfg(feature = "std")]
+            dump: None,
+            clock: None,
+        }
+    }
+
+    /// Makes the machine fail with [`MachineError::InfiniteLoop`] when it
+    /// gets stuck in a loop that does no I/O. The state is checked every
+    /// `interval` instructions, so a larger interval is cheaper, but takes
+    /// longer to notice a loop, and reports a multiple of its period.
+    pub fn detect_loops(mut self, interval: usize) -> Self {
+        self.loops = Some(LoopDetector::new(interval));
+        self
+    }
+
+    /// Makes the machine write a [`Core`] to `path` when it fails, with
+    /// the last `trace` instructions it attempted.
+    #[cfg(feature = "std")]
+    pub fn dump_core<P: Into<PathBuf>>(mut self, path: P, trace: usize) -> Self {
+        self.dump = Some(CoreDump {
+            path: path.into(),
+            trace: Trace::new(trace),
+            error: None,
+        });
+        self
+    }
+
+    /// Makes the machine keep a clock, that moves forward by the cost
+    /// of every instruction it runs, and the time it waits on I/O.
+    pub fn timed(mut self, model: CostModel) -> Self {
+        self.clock = Some(Clock::new(model));
+        self
+    }
+
+    /// What the machine did with its time, if it keeps a clock.
+    pub fn stats(&self) -> Option<&Stats> {
+        self.clock.as_ref().map(|clock| &clock.stats)
+    }
+
+    /// Why the last core could not be written, if it couldn't.
+    #[cfg(feature = "std")]
+    pub fn core_dump_error(&self) -> Option<&io::Error> {
+        self.dump.as_ref().and_then(|dump| dump.error.as_ref())
+    }
+
+    /// Makes the machine start at `ip`, with the relative base at `base`,
+    /// to pick up where something else left off.
+    pub fn at(mut self, ip: usize, base: isize) -> Self {
+        self.ip = ip;
+        self.base = base;
+        self
+    }
+
+    #[inline(always)]
+    pub fn memory(&self) -> &[T] {
+        &self.memory
+    }
+
+    #[inline(always)]
+    pub fn hooks(&self) -> &H {
+        &self.hooks
+    }
+
+    #[inline(always)]
+    pub fn hooks_mut(&mut self) -> &mut H {
+        &mut self.hooks
+    }
+
+    pub fn into_hooks(self) -> H {
+        self.hooks
+    }
+
+    pub fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn reader_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Swaps in new I/O ends, and returns the old ones.
+    pub fn replace_io(&mut self, reader: R, writer: W) -> (R, W) {
+        (
+            mem::replace(&mut self.reader, reader),
+            mem::replace(&mut self.writer, writer),
+        )
+    }
+
+    /// Moves the machine back to `ip` and `base`, as if it had never
+    /// attempted the instructions after them.
+    pub(crate) fn restore(&mut self, ip: usize, base: isize) {
+        self.ip = ip;
+        self.base = base;
+        self.blocked = None;
+
+        if let Some(loops) = self.loops.as_mut() {
+            loops.clear();
+        }
+    }
+
+    /// Forgets everything but the memory, I/O ends and hooks, as if the
+    /// machine was new.
+    fn restart(&mut self) {
+        self.restore(0, 0);
+
+        if let Some(clock) = self.clock.as_mut() {
+            clock.reset();
+        }
+
+        #[cfg(feature = "std")]
+        if let Some(dump) = self.dump.as_mut() {
+            dump.trace.clear();
+            dump.error = None;
         }
     }
 }
@@ -68,27 +321,67 @@ macro_rules! oob {
     };
 }
 
-impl<T, R: Stream<Item = T>, W: Sink<T>> Machine<T, R, W> {
+impl<T, R: Stream<Item = T>, W: Sink<T>, H: Hooks<T>> Machine<T, R, W, H> {
+    /// The address of the next instruction.
     #[inline(always)]
-    fn ip(&self) -> usize {
+    pub fn ip(&self) -> usize {
         self.ip
     }
 
+    /// The relative base.
     #[inline(always)]
-    fn base(&self) -> isize {
+    pub fn base(&self) -> isize {
         self.base
     }
+}
+
+impl<T: Clone, R: Stream<Item = T>, W: Sink<T>, H: Hooks<T>> Machine<T, R, W, H> {
+    /// A snapshot of the machine, with the instructions it attempted
+    /// last if it dumps its core.
+    pub fn core(&self) -> Core<T> {
+        Core {
+            ip: self.ip,
+            base: self.base,
+            pending: self.blocked,
+            error: None,
+            #[cfg(feature = "std")]
+            trace: self
+                .dump
+                .as_ref()
+                .map_or_else(Vec::new, |dump| dump.trace.to_vec()),
+            #[cfg(not(feature = "std"))]
+            trace: Vec::new(),
+            memory: self.memory.to_vec(),
+        }
+    }
+
+    pub fn into_memory(self) -> Vec<T> {
+        self.memory.into_vec()
+    }
+
+    /// The memory, to change by hand. Hooks don't see these writes.
+    pub fn memory_mut(&mut self) -> &mut [T] {
+        self.memory.to_mut()
+    }
+
+    /// Starts the machine over on `image`, keeping its I/O ends and hooks.
+    /// Memory the machine has of its own is overwritten, rather than
+    /// allocated again.
+    pub fn reset_to(&mut self, image: &Image<T>) {
+        self.memory.reset_to_image(image);
+        self.restart();
+    }
 
     #[inline]
     fn write(&mut self, addr: usize, value: T) -> Result<(), MachineError> {
-        let len = self.memory.len();
-        let elem = self.memory.get_mut(addr).ok_or(oob!(len, addr))?;
+        let memory = self.memory.to_mut();
+        let len = memory.len();
+        let elem = memory.get_mut(addr).ok_or(oob!(len, addr))?;
+        self.hooks.on_write(addr, elem, &value);
         *elem = value;
         Ok(())
     }
-}
 
-impl<T: Clone, R: Stream<Item = T>, W: Sink<T>> Machine<T, R, W> {
     #[inline]
     fn read(&self, index: usize) -> Result<T, MachineError> {
         self.memory
@@ -98,8 +391,22 @@ impl<T: Clone, R: Stream<Item = T>, W: Sink<T>> Machine<T, R, W> {
     }
 }
 
-impl<T: Clone + TryInto<isize> + TryInto<usize>, R: Stream<Item = T>, W: Sink<T>> Machine<T, R, W>
+impl<R: Stream<Item = isize>, W: Sink<isize>, H: Hooks<isize>> Machine<isize, R, W, H> {
+    /// Starts the machine over on `program`, keeping its I/O ends and
+    /// hooks. The memory is overwritten in place, so this doesn't allocate
+    /// unless the program is larger.
+    pub fn reset_from(&mut self, program: &Program) {
+        self.memory.reset(program.words());
+        self.restart();
+    }
+}
+
+impl<T, R, W, H> Machine<T, R, W, H>
 where
+    T: Clone + TryInto<isize> + TryInto<usize>,
+    R: Stream<Item = T>,
+    W: Sink<T>,
+    H: Hooks<T>,
     MachineError: From<<T as TryInto<isize>>::Error> + From<<T as TryInto<usize>>::Error>,
 {
     #[inline]
@@ -151,7 +458,7 @@ pub trait Intcode {
     fn adjust_base(&mut self, modes: &[Mode]) -> Poll<Self::Output>;
 }
 
-impl<R: Stream<Item = isize>, W: Sink<isize>> Intcode for Machine<isize, R, W> {
+impl<R: Stream<Item = isize>, W: Sink<isize>, H: Hooks<isize>> Intcode for Machine<isize, R, W, H> {
     type Output = Result<(), MachineError>;
 
     fn opcode(&self) -> Result<Opcode, MachineError> {
@@ -189,7 +496,9 @@ impl<R: Stream<Item = isize>, W: Sink<isize>> Intcode for Machine<isize, R, W> {
             Mode::Immediate => return Poll::Ready(Err(MachineError::WriteInImmediateMode)),
         };
 
-        let _ = try_unwrap!(self.write(try_unwrap!(usize::try_from(addr)), value));
+        let addr = try_unwrap!(usize::try_from(addr));
+        try_unwrap!(self.write(addr, value));
+        self.hooks.on_input(addr, &value);
         self.ip += 2;
 
         Poll::Running
@@ -206,6 +515,7 @@ impl<R: Stream<Item = isize>, W: Sink<isize>> Intcode for Machine<isize, R, W> {
             Ok(_) => {}
             Err(_) => return Poll::Ready(Err(MachineError::SinkSendError)),
         };
+        self.hooks.on_output(&value);
 
         self.ip += 2;
 
@@ -270,17 +580,88 @@ impl<R: Stream<Item = isize>, W: Sink<isize>> Intcode for Machine<isize, R, W> {
     }
 }
 
-impl<T, R: Stream<Item = T>, W: Sink<T>> Future for Machine<T, R, W>
+macro_rules! state {
+    ($machine:expr) => {
+        &State {
+            ip: $machine.ip,
+            base: $machine.base,
+            memory: &$machine.memory,
+        }
+    };
+}
+
+impl<T, R, W, H> Future for Machine<T, R, W, H>
 where
+    T: Hash + Clone + Eq + fmt::Display,
+    R: Stream<Item = T>,
+    W: Sink<T>,
+    H: Hooks<T>,
     Self: Intcode<Output = Result<(), MachineError>>,
 {
     type Output = <Self as Intcode>::Output;
 
     #[inline]
     fn poll(&mut self) -> Poll<Self::Output> {
-        let Opcode { mnemonic, modes } = try_unwrap!(self.opcode());
+        let poll = self.step();
+
+        #[cfg(feature = "std")]
+        if let (Poll::Ready(Err(e)), Some(dump)) = (&poll, self.dump.as_ref()) {
+            let mut core = self.core();
+            core.error = Some(format!("{:?}", e));
+            let error = core.save(&dump.path).err();
+
+            if let Some(dump) = self.dump.as_mut() {
+                dump.error = error;
+            }
+        }
+
+        poll
+    }
+
+    fn clock(&self) -> Option<u64> {
+        self.clock.as_ref().map(Clock::now)
+    }
+
+    fn advance_to(&mut self, time: u64) {
+        if let Some(clock) = self.clock.as_mut() {
+            clock.advance_to(time);
+        }
+    }
+}
+
+impl<T, R, W, H> Machine<T, R, W, H>
+where
+    T: Hash + Clone + Eq,
+    R: Stream<Item = T>,
+    W: Sink<T>,
+    H: Hooks<T>,
+    Self: Intcode<Output = Result<(), MachineError>>,
+{
+    /// Attempts the instruction at `ip`.
+    #[inline(always)]
+    fn step(&mut self) -> Poll<Result<(), MachineError>> {
+        let opcode = self.opcode();
+        let ip = self.ip;
+
+        #[cfg(feature = "std")]
+        if let (None, Some(dump)) = (self.blocked, self.dump.as_mut()) {
+            let len = opcode
+                .as_ref()
+                .map_or(1, |opcode| opcode.mnemonic.arity() + 1);
+            let start = ip.min(self.memory.len());
+            let end = (ip + len).min(self.memory.len());
+            dump.trace.push(ip, self.base, &self.memory[start..end]);
+        }
+
+        let opcode = try_unwrap!(opcode);
+
+        if self.blocked.is_none() {
+            self.hooks.before_instruction(&opcode, state!(self));
+        }
+
+        let Opcode { mnemonic, modes } = opcode;
         let modes: &[_] = &modes;
-        match mnemonic {
+        let poll = match mnemonic {
             Mnemonic::Add => self.add(modes),
             Mnemonic::Mul => self.mul(modes),
             Mnemonic::Save => self.save(modes),
@@ -290,8 +671,36 @@ where
             Mnemonic::LessThan => self.less_than(modes),
             Mnemonic::Equals => self.equals(modes),
             Mnemonic::AdjustBase => self.adjust_base(modes),
-            Mnemonic::Halt => return Poll::Ready(Ok(())),
+            Mnemonic::Halt => {
+                self.hooks.on_halt(state!(self));
+                return Poll::Ready(Ok(()));
+            }
+        };
+
+        match poll {
+            // Save and Output don't move the instruction pointer
+            // until their I/O goes through.
+            Poll::Running if self.ip == ip && mnemonic.is_io() => self.blocked = Some(mnemonic),
+            Poll::Running => {
+                self.blocked = None;
+                if let Some(clock) = self.clock.as_mut() {
+                    clock.charge(&opcode);
+                }
+                self.hooks.after_instruction(ip, &opcode, state!(self));
+
+                if let Some(loops) = self.loops.as_mut() {
+                    if mnemonic.is_io() {
+                        loops.clear();
+                    } else if let Some(period) = loops.step(state!(self)) {
+                        let ip = self.ip;
+                        return Poll::Ready(Err(MachineError::InfiniteLoop { ip, period }));
+                    }
+                }
+            }
+            Poll::Ready(_) => self.blocked = None,
         }
+
+        poll
     }
 }
 
@@ -360,4 +769,73 @@ mod tests {
         assert!(r.is_ok());
         assert_eq!(outputter.0, 1219070632396864);
     }
+
+    #[test]
+    fn test_detect_loops() {
+        // Counts [14] up to 3, then spins on `jt 1, 11`.
+        let program = vec![
+            1001, 14, 1, 14, 1007, 14, 3, 15, 1005, 15, 0, 1105, 1, 11, 0, 0,
+        ];
+
+        let mut m = Machine::new(program, Dummy(0), Dummy(0)).detect_loops(1);
+
+        match m.execute() {
+            Err(MachineError::InfiniteLoop { ip, period }) => {
+                assert_eq!(ip, 11);
+                assert_eq!(period, 1);
+            }
+            r => panic!("expected an infinite loop, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_detect_longer_loops() {
+        // Flips the sign of [7] forever.
+        let program = vec![1002, 7, -1, 7, 1105, 1, 0, 1];
+
+        for &(interval, expected) in &[(1, 4), (3, 12)] {
+            let mut m = Machine::new(program.clone(), Dummy(0), Dummy(0)).detect_loops(interval);
+
+            match m.execute() {
+                Err(MachineError::InfiniteLoop { period, .. }) => assert_eq!(period, expected),
+                r => panic!("expected an infinite loop, got {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_loops_that_end_are_not_infinite() {
+        // Counts [13] up to 100000, and halts.
+        let program = vec![
+            1001, 13, 1, 13, 1007, 13, 100000, 14, 1005, 14, 0, 99, 0, 0, 0,
+        ];
+
+        let mut m = Machine::new(program, Dummy(0), Dummy(0)).detect_loops(1);
+
+        assert!(m.execute().is_ok());
+        assert_eq!(m.memory()[13], 100000);
+    }
+
+    #[test]
+    fn test_loops_with_io_are_not_infinite() {
+        // Outputs 1 forever, until the writer gives up.
+        struct Limited(usize);
+
+        impl Sink<isize> for Limited {
+            type Error = ();
+
+            fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+                Poll::Ready(if self.0 == 0 { Err(()) } else { Ok(()) })
+            }
+
+            fn send(&mut self, _: isize) -> Result<(), Self::Error> {
+                self.0 -= 1;
+                Ok(())
+            }
+        }
+
+        let mut m = Machine::new(vec![104, 1, 1105, 1, 0], Dummy(0), Limited(100)).detect_loops(1);
+
+        assert!(matches!(m.execute(), Err(MachineError::SinkPrepareError)));
+    }
 }
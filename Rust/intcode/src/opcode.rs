@@ -1,4 +1,4 @@
-use std::convert::TryFrom;
+use core::convert::TryFrom;
 
 #[derive(Debug)]
 pub struct InvalidMode<N>(N);
@@ -27,7 +27,7 @@ impl TryFrom<isize> for Mode {
 pub struct InvalidOpCode<N>(N);
 
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
-pub(crate) enum Mnemonic {
+pub enum Mnemonic {
     Add,
     Mul,
     Save,
@@ -40,6 +40,59 @@ pub(crate) enum Mnemonic {
     Halt,
 }
 
+impl Mnemonic {
+    /// Whether the instruction reads from, or writes to the outside world.
+    pub fn is_io(self) -> bool {
+        matches!(self, Self::Save | Self::Output)
+    }
+
+    pub fn is_jump(self) -> bool {
+        matches!(self, Self::JumpIfTrue | Self::JumpIfFalse)
+    }
+
+    /// The number of parameters that follow the opcode.
+    pub fn arity(self) -> usize {
+        match self {
+            Self::Add | Self::Mul | Self::LessThan | Self::Equals => 3,
+            Self::JumpIfTrue | Self::JumpIfFalse => 2,
+            Self::Save | Self::Output | Self::AdjustBase => 1,
+            Self::Halt => 0,
+        }
+    }
+
+    /// The opcode of the instruction, without any modes.
+    pub fn code(self) -> isize {
+        match self {
+            Self::Add => 1,
+            Self::Mul => 2,
+            Self::Save => 3,
+            Self::Output => 4,
+            Self::JumpIfTrue => 5,
+            Self::JumpIfFalse => 6,
+            Self::LessThan => 7,
+            Self::Equals => 8,
+            Self::AdjustBase => 9,
+            Self::Halt => 99,
+        }
+    }
+
+    /// The short name used in listings.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Mul => "mul",
+            Self::Save => "in",
+            Self::Output => "out",
+            Self::JumpIfTrue => "jt",
+            Self::JumpIfFalse => "jf",
+            Self::LessThan => "lt",
+            Self::Equals => "eq",
+            Self::AdjustBase => "arb",
+            Self::Halt => "hlt",
+        }
+    }
+}
+
 impl TryFrom<isize> for Mnemonic {
     type Error = InvalidOpCode<isize>;
 
@@ -60,7 +113,7 @@ impl TryFrom<isize> for Mnemonic {
     }
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub struct Opcode {
     pub(crate) mnemonic: Mnemonic,
     pub(crate) modes: [Mode; 3],
@@ -96,4 +149,12 @@ impl Opcode {
 
         Ok(Self { mnemonic, modes })
     }
+
+    pub fn mnemonic(&self) -> Mnemonic {
+        self.mnemonic
+    }
+
+    pub fn modes(&self) -> [Mode; 3] {
+        self.modes
+    }
 }
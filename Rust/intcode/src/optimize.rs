@@ -0,0 +1,469 @@
+//! A peephole optimizer for intcode programs.
+//!
+//! The optimized program keeps every instruction where it was, and does the
+//! same as the original on every input, in fewer steps:
+//!
+//! - values computed from constants are folded into the instructions that
+//!   read them, within a basic block, so chains of `add` and `mul` on
+//!   immediates collapse into a single constant,
+//! - jumps on a condition that is known become unconditional, or never jump,
+//! - jumps to unconditional jumps go straight to where those lead, and
+//!   unconditional jumps to a `hlt` halt right away,
+//! - jumps that go nowhere are removed, by moving the code after them back,
+//!   as long as that code ends in a jump or a halt.
+//!
+//! Only instructions in cells that the program never reads as data, nor
+//! writes to, are rewritten. That can only be proven when every address the
+//! program reads, writes or jumps to is known up front, so a program with
+//! relative parameters, jumps to computed addresses, or instructions that
+//! it writes to is left as it is.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+
+use crate::{
+    disasm::{Instruction, Operand},
+    opcode::Mnemonic,
+    transpile::discover,
+};
+
+pub fn optimize(words: &[isize]) -> Vec<isize> {
+    let mut words = words.to_vec();
+
+    // Every round leaves the program with fewer steps to take, or fewer
+    // cells to read, so this ends. The limit is only there to make sure.
+    for _ in 0..words.len() {
+        match Optimizer::new(&words).and_then(|optimizer| optimizer.round()) {
+            Some(optimized) => words = optimized,
+            None => break,
+        }
+    }
+
+    words
+}
+
+struct Optimizer<'a> {
+    words: &'a [isize],
+    /// The code reachable from address 0, by address.
+    instructions: BTreeMap<usize, Instruction>,
+    /// Where the code can be jumped to.
+    roots: BTreeSet<usize>,
+    /// The cells the program reads as data, or writes to.
+    data: BTreeSet<usize>,
+}
+
+impl<'a> Optimizer<'a> {
+    /// Analyzes the program, or returns `None` if it may access cells,
+    /// or jump to addresses, that aren't known up front.
+    fn new(words: &'a [isize]) -> Option<Self> {
+        let (instructions, roots) = discover(words);
+
+        let mut data = BTreeSet::new();
+        let mut written = BTreeSet::new();
+        let mut end = 0;
+
+        for instruction in instructions.values() {
+            // Two instructions that share cells.
+            if instruction.addr < end {
+                return None;
+            }
+            end = instruction.next();
+
+            for (index, operand) in instruction.operands().iter().enumerate() {
+                match *operand {
+                    Operand::Relative(_) => return None,
+                    Operand::Position(_) if instruction.mnemonic.is_jump() && index == 1 => {
+                        return None
+                    }
+                    Operand::Position(addr) if addr >= 0 => {
+                        data.insert(addr as usize);
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(Operand::Position(addr)) = instruction.written() {
+                written.insert(addr);
+            }
+        }
+
+        let patched = instructions.values().any(|instruction| {
+            (instruction.addr..instruction.next()).any(|addr| written.contains(&(addr as isize)))
+        });
+
+        if patched {
+            return None;
+        }
+
+        Some(Self {
+            words,
+            instructions,
+            roots,
+            data,
+        })
+    }
+
+    /// Returns the program with one more round of optimizations applied,
+    /// or `None` if there is nothing left to do.
+    fn round(&self) -> Option<Vec<isize>> {
+        let mut words = self.words.to_vec();
+
+        self.fold(&mut words);
+        self.thread(&mut words);
+
+        // Moving code around needs an analysis of the program as it is.
+        if words == self.words && !self.remove_jump(&mut words) {
+            return None;
+        }
+
+        Some(words)
+    }
+
+    /// Whether the cells of `instruction` are only ever executed.
+    fn is_rewritable(&self, instruction: &Instruction) -> bool {
+        (instruction.addr..instruction.next()).all(|addr| !self.data.contains(&addr))
+    }
+
+    fn rewrite(&self, words: &mut [isize], instruction: &Instruction) {
+        words[instruction.addr..instruction.next()].copy_from_slice(&instruction.encode());
+    }
+
+    /// Replaces the parameters that read cells of known value with the
+    /// value, within every basic block.
+    fn fold(&self, words: &mut [isize]) {
+        let mut known = BTreeMap::<usize, isize>::new();
+        let mut next = None;
+
+        for instruction in self.instructions.values() {
+            if self.roots.contains(&instruction.addr) || next != Some(instruction.addr) {
+                known.clear();
+            }
+            next = Some(instruction.next());
+
+            let operands = instruction.operands();
+            let value = |operand: Operand| match operand {
+                Operand::Immediate(value) => Some(value),
+                Operand::Position(addr) if addr >= 0 => known.get(&(addr as usize)).copied(),
+                _ => None,
+            };
+
+            let a = operands.first().and_then(|operand| value(*operand));
+            let b = operands.get(1).and_then(|operand| value(*operand));
+            let result = match (instruction.mnemonic, a, b) {
+                (Mnemonic::Add, Some(a), Some(b)) => Some(a + b),
+                (Mnemonic::Mul, Some(a), Some(b)) => Some(a * b),
+                (Mnemonic::LessThan, Some(a), Some(b)) => Some((a < b) as isize),
+                (Mnemonic::Equals, Some(a), Some(b)) => Some((a == b) as isize),
+                _ => None,
+            };
+
+            let folded = match result {
+                Some(result) => Instruction::new(
+                    instruction.addr,
+                    Mnemonic::Add,
+                    &[
+                        Operand::Immediate(result),
+                        Operand::Immediate(0),
+                        operands[2],
+                    ],
+                ),
+                None => {
+                    let mut operands = operands.to_vec();
+                    let reads = match instruction.written() {
+                        Some(_) if instruction.mnemonic != Mnemonic::Save => 2,
+                        Some(_) => 0,
+                        None => operands.len(),
+                    };
+
+                    for operand in operands[..reads].iter_mut() {
+                        if let Some(value) = value(*operand) {
+                            *operand = Operand::Immediate(value);
+                        }
+                    }
+
+                    Instruction::new(instruction.addr, instruction.mnemonic, &operands)
+                }
+            };
+
+            if let Some(Operand::Position(addr)) = instruction.written() {
+                if addr >= 0 && (addr as usize) < words.len() {
+                    match result {
+                        Some(result) => known.insert(addr as usize, result),
+                        None => known.remove(&(addr as usize)),
+                    };
+                }
+            }
+
+            if self.is_rewritable(instruction) {
+                self.rewrite(words, &folded);
+            }
+        }
+    }
+
+    /// Points jumps at where the code they jump to ends up going.
+    fn thread(&self, words: &mut [isize]) {
+        for jump in self.instructions.values() {
+            if !jump.mnemonic.is_jump() || !self.is_rewritable(jump) {
+                continue;
+            }
+
+            let target = match jump.operands()[1] {
+                Operand::Immediate(target) if target >= 0 => target as usize,
+                _ => continue,
+            };
+
+            let destination = self.destination(target);
+            let halts = self
+                .instructions
+                .get(&destination)
+                .map(|instruction| instruction.mnemonic == Mnemonic::Halt);
+
+            if halts == Some(true) && self.step(jump) == Some(target) {
+                words[jump.addr..jump.next()]
+                    .iter_mut()
+                    .for_each(|word| *word = 99);
+            } else if destination != target {
+                words[jump.addr + 2] = destination as isize;
+            }
+        }
+    }
+
+    /// Where the jump at `jump` goes, if that doesn't depend on the
+    /// state of the program.
+    fn step(&self, jump: &Instruction) -> Option<usize> {
+        let target = match jump.operands()[1] {
+            Operand::Immediate(target) if target >= 0 => target as usize,
+            _ => return None,
+        };
+
+        match jump.operands()[0] {
+            Operand::Immediate(cond) if (cond != 0) == (jump.mnemonic == Mnemonic::JumpIfTrue) => {
+                Some(target)
+            }
+            Operand::Immediate(_) => Some(jump.next()),
+            // Reading the condition can't fail, and it doesn't matter what it is.
+            Operand::Position(addr)
+                if target == jump.next() && addr >= 0 && (addr as usize) < self.words.len() =>
+            {
+                Some(target)
+            }
+            _ => None,
+        }
+    }
+
+    /// Where the code ends up, once it gets to `addr`, past any jumps
+    /// that go the same way every time.
+    fn destination(&self, mut addr: usize) -> usize {
+        let mut seen = BTreeSet::new();
+
+        while seen.insert(addr) {
+            match self.instructions.get(&addr) {
+                Some(jump) if jump.mnemonic.is_jump() => match self.step(jump) {
+                    Some(next) => addr = next,
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+
+        addr
+    }
+
+    /// Removes a jump to the very next instruction, by moving the code
+    /// after it up to the next unconditional jump or halt back onto it.
+    /// Returns whether there was one that could be removed.
+    fn remove_jump(&self, words: &mut [isize]) -> bool {
+        for jump in self.instructions.values() {
+            if !jump.mnemonic.is_jump()
+                || !self.is_rewritable(jump)
+                || self.step(jump) != Some(jump.next())
+            {
+                continue;
+            }
+
+            let start = jump.next();
+            let end = match self.tail(start) {
+                Some(end) => end,
+                None => continue,
+            };
+
+            let entries = self
+                .instructions
+                .values()
+                .filter(|other| {
+                    other.mnemonic.is_jump()
+                        && other.addr != jump.addr
+                        && other.operands()[1] == Operand::Immediate(start as isize)
+                })
+                .collect::<Vec<_>>();
+
+            if !entries.iter().all(|entry| self.is_rewritable(entry)) {
+                continue;
+            }
+
+            let size = jump.size();
+            let tail = self.words[start..end].to_vec();
+            words[jump.addr..jump.addr + tail.len()].copy_from_slice(&tail);
+            words[end - size..end]
+                .iter_mut()
+                .for_each(|word| *word = 99);
+
+            for entry in entries {
+                let mut param = entry.addr + 2;
+                if (start..end).contains(&param) {
+                    param -= size;
+                }
+
+                words[param] = jump.addr as isize;
+            }
+
+            return true;
+        }
+
+        false
+    }
+
+    /// If the code from `start` runs straight into an unconditional jump or
+    /// a halt, can only be entered at `start`, and can be moved, returns
+    /// the address right after it.
+    fn tail(&self, start: usize) -> Option<usize> {
+        let mut addr = start;
+
+        loop {
+            let instruction = self.instructions.get(&addr)?;
+
+            if !self.is_rewritable(instruction) || (addr != start && self.roots.contains(&addr)) {
+                return None;
+            }
+
+            match instruction.mnemonic {
+                Mnemonic::Halt => return Some(instruction.next()),
+                Mnemonic::JumpIfTrue | Mnemonic::JumpIfFalse => {
+                    let step = self.step(instruction)?;
+                    return if step == instruction.next() {
+                        None
+                    } else {
+                        Some(instruction.next())
+                    };
+                }
+                _ => addr = instruction.next(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        future::{sink::Stdout, stream::once, FutureExt},
+        hooks::{Hooks, State},
+        machine::Machine,
+        opcode::Opcode,
+        testing::compare_with_8,
+    };
+
+    /// The outputs of a run, and the number of instructions it took.
+    #[derive(Debug, Default, Eq, PartialEq)]
+    struct Run {
+        outputs: Vec<isize>,
+        steps: usize,
+    }
+
+    impl Hooks<isize> for Run {
+        fn after_instruction(&mut self, _ip: usize, _opcode: &Opcode, _state: &State<'_, isize>) {
+            self.steps += 1;
+        }
+
+        fn on_output(&mut self, value: &isize) {
+            self.outputs.push(*value);
+        }
+    }
+
+    fn run(words: &[isize], input: isize) -> Run {
+        let mut machine =
+            Machine::with_hooks(words.to_vec(), once(input), Stdout::new(), Run::default());
+        machine.execute().unwrap();
+        machine.into_hooks()
+    }
+
+    #[test]
+    fn constants_fold_into_their_readers() {
+        // add 2, 3, [13]; mul [13], 4, [14]; out [14]; hlt
+        let words = [1101, 2, 3, 13, 1002, 13, 4, 14, 4, 14, 99, 0, 0, 0, 0];
+
+        let optimized = optimize(&words);
+        assert_eq!(
+            optimized,
+            [1101, 5, 0, 13, 1101, 20, 0, 14, 104, 20, 99, 0, 0, 0, 0]
+        );
+        assert_eq!(run(&optimized, 0), run(&words, 0));
+    }
+
+    #[test]
+    fn jumps_are_threaded_and_removed() {
+        let mut words = vec![
+            3, 30, // in [30]
+            1005, 30, 12, // jt [30], 12
+            1105, 1, 8, // jt 1, 8
+            104, 0,  // out 0
+            99, // hlt
+            99, // hlt
+            1105, 1, 15, // jt 1, 15
+            104, 1, // out 1
+            1105, 1, 10, // jt 1, 10
+        ];
+        words.resize(31, 0);
+
+        let optimized = optimize(&words);
+
+        let mut expected = vec![
+            3, 30, // in [30]
+            1005, 30, 15, // jt [30], 15
+            104, 0,  // out 0
+            99, // hlt
+            99, 99, 99, 99, // unreachable
+            1105, 1, 15, // unreachable
+            104, 1, // out 1
+            99, 99, 99, // hlt
+        ];
+        expected.resize(31, 0);
+        assert_eq!(optimized, expected);
+
+        for input in 0..2 {
+            let (before, after) = (run(&words, input), run(&optimized, input));
+            assert_eq!(after.outputs, before.outputs);
+            assert!(after.steps < before.steps);
+        }
+    }
+
+    #[test]
+    fn unknown_accesses_are_left_alone() {
+        // Outputs a copy of itself, with relative parameters.
+        let quine = [
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(optimize(&quine), quine);
+
+        // Writes the parameter of the `out`.
+        let patched = [1101, 2, 3, 5, 104, 0, 99];
+        assert_eq!(optimize(&patched), patched);
+    }
+
+    #[test]
+    fn examples_behave_the_same() {
+        let compare = compare_with_8();
+
+        let optimized = optimize(&compare);
+        assert_ne!(optimized[..], compare[..]);
+
+        for input in 0..16 {
+            let (before, after) = (run(&compare, input), run(&optimized, input));
+            assert_eq!(after.outputs, before.outputs);
+            assert!(after.steps <= before.steps);
+        }
+    }
+}
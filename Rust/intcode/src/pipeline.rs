@@ -0,0 +1,371 @@
+//! Networks of machines that feed each other.
+//!
+//! A [`Pipeline`] is a graph of stages. Every stage first reads its own
+//! initial inputs, and then what its predecessors output, in the order it
+//! was sent. Everything a stage outputs goes to all of its successors.
+//!
+//! A stage that waits on input once all of its predecessors are done, or
+//! while every other stage is done or waiting too, reads the end of its
+//! input instead of waiting forever.
+
+use alloc::{collections::VecDeque, rc::Rc, vec, vec::Vec};
+use core::{
+    cell::{Cell, RefCell},
+    convert::Infallible,
+};
+
+use crate::{
+    cost::CostModel,
+    future::{join_all, sink::Sink, stream::Stream, Future, FutureExt, Poll},
+    image::Image,
+    machine::{Machine, MachineError},
+};
+
+/// How the stages are connected, and what they start with.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Pipeline {
+    inputs: Vec<Vec<isize>>,
+    edges: Vec<(usize, usize)>,
+    cost: Option<CostModel>,
+}
+
+impl Pipeline {
+    /// Stages without any connections, one for every list of initial inputs.
+    pub fn new(inputs: Vec<Vec<isize>>) -> Self {
+        Self {
+            inputs,
+            edges: Vec::new(),
+            cost: None,
+        }
+    }
+
+    /// Stages that each feed the next one.
+    pub fn chain(inputs: Vec<Vec<isize>>) -> Self {
+        let len = inputs.len();
+        (1..len).fold(Self::new(inputs), |pipeline, to| {
+            pipeline.connect(to - 1, to)
+        })
+    }
+
+    /// A chain, where the last stage feeds the first one.
+    pub fn ring(inputs: Vec<Vec<isize>>) -> Self {
+        let len = inputs.len();
+        let pipeline = Self::chain(inputs);
+
+        match len {
+            0 => pipeline,
+            len => pipeline.connect(len - 1, 0),
+        }
+    }
+
+    /// Makes the outputs of stage `from` inputs of stage `to`.
+    pub fn connect(mut self, from: usize, to: usize) -> Self {
+        assert!(from < self.len() && to < self.len(), "stage out of bounds");
+
+        self.edges.push((from, to));
+        self
+    }
+
+    /// Makes the machines [`run`](Self::run) starts keep a clock.
+    pub fn timed(mut self, model: CostModel) -> Self {
+        self.cost = Some(model);
+        self
+    }
+
+    /// The number of stages.
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Runs `program` on every stage, which all share it until they
+    /// write to it.
+    pub fn run<P: Into<Image>>(&self, program: P) -> Run<Result<(), MachineError>> {
+        let (cost, image) = (self.cost, program.into());
+
+        self.run_with(|_, input, output| {
+            let machine = Machine::from_image(&image, input, output);
+            match cost {
+                Some(model) => machine.timed(model),
+                None => machine,
+            }
+        })
+    }
+
+    /// Runs the future `spawn` makes for every stage, from its index, and
+    /// the ends it reads from and writes to.
+    pub fn run_with<F, S>(&self, mut spawn: S) -> Run<F::Output>
+    where
+        F: Future,
+        S: FnMut(usize, Inbox, Outbox) -> F,
+    {
+        let wires = Rc::new(Wires::new(self));
+
+        let stages = (0..self.len())
+            .map(|stage| {
+                let input = Inbox {
+                    wires: Rc::clone(&wires),
+                    stage,
+                };
+                let output = Outbox {
+                    wires: Rc::clone(&wires),
+                    stage,
+                };
+
+                Stage {
+                    future: spawn(stage, input, output),
+                    wires: Rc::clone(&wires),
+                    stage,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let results = join_all(stages).execute();
+        let outputs = wires.outputs.iter().map(|o| o.take()).collect();
+
+        Run { results, outputs }
+    }
+}
+
+/// What every stage of a pipeline ended with, and what it output.
+#[derive(Debug)]
+pub struct Run<T> {
+    pub results: Vec<T>,
+    pub outputs: Vec<Vec<isize>>,
+}
+
+impl<T> Run<T> {
+    /// The last value `stage` output.
+    pub fn last(&self, stage: usize) -> Option<isize> {
+        self.outputs[stage].last().copied()
+    }
+}
+
+/// The state the stages share.
+struct Wires {
+    inboxes: Vec<RefCell<VecDeque<isize>>>,
+    outputs: Vec<RefCell<Vec<isize>>>,
+    predecessors: Vec<Vec<usize>>,
+    successors: Vec<Vec<usize>>,
+    waiting: Vec<Cell<bool>>,
+    done: Vec<Cell<bool>>,
+}
+
+impl Wires {
+    fn new(pipeline: &Pipeline) -> Self {
+        let len = pipeline.len();
+        let (mut predecessors, mut successors) = (vec![Vec::new(); len], vec![Vec::new(); len]);
+
+        for &(from, to) in pipeline.edges.iter() {
+            predecessors[to].push(from);
+            successors[from].push(to);
+        }
+
+        Self {
+            inboxes: pipeline
+                .inputs
+                .iter()
+                .map(|inputs| RefCell::new(inputs.iter().copied().collect()))
+                .collect(),
+            outputs: vec![RefCell::default(); len],
+            predecessors,
+            successors,
+            waiting: vec![Cell::new(false); len],
+            done: vec![Cell::new(false); len],
+        }
+    }
+
+    /// Whether nothing will ever arrive for `stage`.
+    fn is_starved(&self, stage: usize) -> bool {
+        let done = self.predecessors[stage]
+            .iter()
+            .all(|&from| self.done[from].get());
+
+        let deadlocked = (0..self.done.len()).all(|other| {
+            self.done[other].get()
+                || (self.waiting[other].get() && self.inboxes[other].borrow().is_empty())
+        });
+
+        done || deadlocked
+    }
+}
+
+/// What a stage reads from.
+pub struct Inbox {
+    wires: Rc<Wires>,
+    stage: usize,
+}
+
+impl Stream for Inbox {
+    type Item = isize;
+
+    fn poll_next(&mut self) -> Poll<Option<Self::Item>> {
+        let wires = &self.wires;
+
+        if let Some(value) = wires.inboxes[self.stage].borrow_mut().pop_front() {
+            wires.waiting[self.stage].set(false);
+            return Poll::Ready(Some(value));
+        }
+
+        wires.waiting[self.stage].set(true);
+        if wires.is_starved(self.stage) {
+            Poll::Ready(None)
+        } else {
+            Poll::Running
+        }
+    }
+}
+
+/// What a stage writes to, which never fills up.
+pub struct Outbox {
+    wires: Rc<Wires>,
+    stage: usize,
+}
+
+impl Sink<isize> for Outbox {
+    type Error = Infallible;
+
+    fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn send(&mut self, value: isize) -> Result<(), Self::Error> {
+        let wires = &self.wires;
+
+        wires.outputs[self.stage].borrow_mut().push(value);
+        for &to in wires.successors[self.stage].iter() {
+            wires.inboxes[to].borrow_mut().push_back(value);
+        }
+
+        Ok(())
+    }
+}
+
+/// Marks its stage as done once the future is.
+struct Stage<F> {
+    future: F,
+    wires: Rc<Wires>,
+    stage: usize,
+}
+
+impl<F: Future> Future for Stage<F> {
+    type Output = F::Output;
+
+    fn poll(&mut self) -> Poll<Self::Output> {
+        let output = ready!(self.future.poll());
+        self.wires.done[self.stage].set(true);
+
+        Poll::Ready(output)
+    }
+
+    fn clock(&self) -> Option<u64> {
+        self.future.clock()
+    }
+
+    fn advance_to(&mut self, time: u64) {
+        self.future.advance_to(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::lang::compile;
+
+    fn phases(settings: &[isize]) -> Vec<Vec<isize>> {
+        let mut inputs = settings.iter().map(|&s| vec![s]).collect::<Vec<_>>();
+        inputs[0].push(0);
+        inputs
+    }
+
+    #[test]
+    fn chains_pass_signals_along() {
+        let program = [
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+
+        let run = Pipeline::chain(phases(&[4, 3, 2, 1, 0])).run(&program[..]);
+        assert!(run.results.iter().all(Result::is_ok));
+        assert_eq!(run.last(4), Some(43210));
+        assert_eq!(run.outputs[1], vec![43]);
+    }
+
+    #[test]
+    fn rings_feed_back() {
+        let program = [
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+
+        let run = Pipeline::ring(phases(&[9, 8, 7, 6, 5])).run(&program[..]);
+        assert!(run.results.iter().all(Result::is_ok));
+        assert_eq!(run.last(4), Some(139629729));
+
+        // Any number of stages works, and so does timing them.
+        let run = Pipeline::ring(phases(&[9, 8, 7, 6, 5, 9, 8]))
+            .timed(CostModel::new())
+            .run(&program[..]);
+        assert!(run.results.iter().all(Result::is_ok));
+        assert_eq!(run.outputs[6].len(), 5);
+    }
+
+    #[test]
+    fn graphs_fan_out_and_in() {
+        let source = compile("fn main() { output(input()); output(input()); }").unwrap();
+        let double =
+            compile("fn main() { let x = input(); while x != 0 { output(2 * x); x = input(); } }")
+                .unwrap();
+        let sum = compile(
+            "fn main() {
+                let sum = 0;
+                let n = 0;
+                while n < 4 {
+                    sum = sum + input();
+                    n = n + 1;
+                }
+                output(sum);
+            }",
+        )
+        .unwrap();
+
+        //     1
+        //   /   \
+        // 0       3
+        //   \   /
+        //     2
+        let pipeline = Pipeline::new(vec![vec![5, 7], vec![], vec![], vec![]])
+            .connect(0, 1)
+            .connect(0, 2)
+            .connect(1, 3)
+            .connect(2, 3);
+
+        let run = pipeline.run_with(|stage, input, output| {
+            let program = match stage {
+                0 => source.clone(),
+                3 => sum.clone(),
+                _ => double.clone(),
+            };
+            Machine::new(program, input, output)
+        });
+
+        // The doublers run out of input once the source is done.
+        assert!(run.results[0].is_ok() && run.results[3].is_ok());
+        assert!(matches!(run.results[1], Err(MachineError::ReaderExhausted)));
+        assert_eq!(run.outputs[1], vec![10, 14]);
+        assert_eq!(run.last(3), Some(48));
+    }
+
+    #[test]
+    fn deadlocks_end() {
+        // Every stage waits on the one before it.
+        let run = Pipeline::ring(vec![vec![]; 3]).run(vec![3, 0, 4, 0, 99]);
+        assert!(run
+            .results
+            .iter()
+            .all(|r| matches!(r, Err(MachineError::ReaderExhausted))));
+    }
+}
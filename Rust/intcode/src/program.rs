@@ -0,0 +1,492 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, str::FromStr};
+
+#[cfg(feature = "std")]
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Where the words of a [`Program`] were read from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Source {
+    Words,
+    Text,
+    #[cfg(feature = "std")]
+    File(PathBuf),
+}
+
+/// A single `addr=value` modification of a program.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Patch {
+    pub addr: usize,
+    pub value: isize,
+}
+
+impl Patch {
+    pub const fn new(addr: usize, value: isize) -> Self {
+        Self { addr, value }
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.addr, self.value)
+    }
+}
+
+impl FromStr for Patch {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut patches = parse_patches(s)?;
+
+        match (patches.pop(), patches.is_empty()) {
+            (Some(patch), true) => Ok(patch),
+            (None, _) => Err(ParseError::new(1, 1, ParseErrorKind::Empty)),
+            (Some(_), false) => Err(ParseError::new(1, 1, ParseErrorKind::TooManyPatches)),
+        }
+    }
+}
+
+/// The source of a program, together with every patch that
+/// has been applied to it since, in order.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Provenance {
+    pub source: Source,
+    pub patches: Vec<Patch>,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.source {
+            Source::Words => write!(f, "<words>")?,
+            Source::Text => write!(f, "<text>")?,
+            #[cfg(feature = "std")]
+            Source::File(path) => write!(f, "{}", path.display())?,
+        }
+
+        for patch in self.patches.iter() {
+            write!(f, " {}", patch)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    /// There is nothing but whitespace and comments.
+    Empty,
+    /// A comma that is not followed by a word.
+    MissingWord,
+    /// Two words without a comma in between.
+    MissingComma,
+    /// A word that is not a valid integer.
+    InvalidNumber(String),
+    /// A patch without an `=`.
+    MissingEquals,
+    /// More than one patch where exactly one was expected.
+    TooManyPatches,
+}
+
+/// An error while parsing a program or a patch list.
+/// Lines and columns both start at 1.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl ParseError {
+    const fn new(line: usize, column: usize, kind: ParseErrorKind) -> Self {
+        Self { line, column, kind }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+
+        match &self.kind {
+            ParseErrorKind::Empty => write!(f, "empty input"),
+            ParseErrorKind::MissingWord => write!(f, "expected a word"),
+            ParseErrorKind::MissingComma => write!(f, "expected a comma"),
+            ParseErrorKind::InvalidNumber(word) => write!(f, "invalid number `{}`", word),
+            ParseErrorKind::MissingEquals => write!(f, "expected `addr=value`"),
+            ParseErrorKind::TooManyPatches => write!(f, "expected a single patch"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PatchError {
+    pub patch: Patch,
+    pub len: usize,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "patch {} is out of bounds for a program of {} words",
+            self.patch, self.len
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum LoadError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { path: PathBuf, error: ParseError },
+    Patch(PatchError),
+}
+
+#[cfg(feature = "std")]
+impl From<PatchError> for LoadError {
+    fn from(e: PatchError) -> Self {
+        Self::Patch(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            Self::Parse { path, error } => write!(f, "{}:{}", path.display(), error),
+            Self::Patch(e) => e.fmt(f),
+        }
+    }
+}
+
+/// Strips a `#` comment from the end of a line.
+fn uncomment(line: &str) -> &str {
+    match line.find('#') {
+        Some(idx) => &line[..idx],
+        None => line,
+    }
+}
+
+enum Token<'a> {
+    Word(&'a str),
+    Comma,
+}
+
+/// Splits `s` into words and commas, skipping whitespace and comments.
+/// Every token is returned with the line and column it starts at.
+fn tokens(s: &str) -> Vec<(usize, usize, Token<'_>)> {
+    let mut tokens = Vec::new();
+
+    for (lineno, line) in s.lines().enumerate() {
+        let line = uncomment(line);
+        let mut chars = line.char_indices().peekable();
+
+        while let Some((idx, c)) = chars.next() {
+            let column = line[..idx].chars().count() + 1;
+
+            if c == ',' {
+                tokens.push((lineno + 1, column, Token::Comma));
+            } else if !c.is_whitespace() {
+                let mut end = idx + c.len_utf8();
+                while let Some(&(next, c)) = chars.peek() {
+                    if c == ',' || c.is_whitespace() {
+                        break;
+                    }
+                    end = next + c.len_utf8();
+                    chars.next();
+                }
+
+                tokens.push((lineno + 1, column, Token::Word(&line[idx..end])));
+            }
+        }
+    }
+
+    tokens
+}
+
+/// The position right after the last character of `s`.
+fn end_of(s: &str) -> (usize, usize) {
+    let lines = s.lines().count().max(1);
+    let column = s.lines().last().map_or(0, |line| line.chars().count());
+    (lines, column + 1)
+}
+
+fn number<N: FromStr>(line: usize, column: usize, word: &str) -> Result<N, ParseError> {
+    word.parse().map_err(|_| {
+        ParseError::new(
+            line,
+            column,
+            ParseErrorKind::InvalidNumber(word.to_string()),
+        )
+    })
+}
+
+/// Parses a list of `addr=value` patches. Patches are separated by commas
+/// or newlines, and `#` starts a comment that runs until the end of the line.
+pub fn parse_patches(s: &str) -> Result<Vec<Patch>, ParseError> {
+    let mut patches = Vec::new();
+
+    for (lineno, line) in s.lines().enumerate() {
+        let line = uncomment(line);
+        let mut offset = 0;
+
+        for part in line.split(',') {
+            let column = line[..offset + part.len() - part.trim_start().len()]
+                .chars()
+                .count()
+                + 1;
+            offset += part.len() + 1;
+
+            let part = part.trim();
+            if part.is_empty() {
+                continue;
+            }
+
+            let eq = part.find('=').ok_or_else(|| {
+                ParseError::new(lineno + 1, column, ParseErrorKind::MissingEquals)
+            })?;
+
+            let (addr, value) = (part[..eq].trim_end(), part[eq + 1..].trim_start());
+            let value_column = column + part[..part.len() - value.len()].chars().count();
+
+            patches.push(Patch {
+                addr: number(lineno + 1, column, addr)?,
+                value: number(lineno + 1, value_column, value)?,
+            });
+        }
+    }
+
+    Ok(patches)
+}
+
+/// An intcode program, that remembers where it came from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Program {
+    words: Vec<isize>,
+    provenance: Provenance,
+}
+
+impl Program {
+    pub fn new(words: Vec<isize>) -> Self {
+        Self::with_source(words, Source::Words)
+    }
+
+    fn with_source(words: Vec<isize>, source: Source) -> Self {
+        Self {
+            words,
+            provenance: Provenance {
+                source,
+                patches: Vec::new(),
+            },
+        }
+    }
+
+    /// Parses a comma separated list of words. Whitespace and
+    /// newlines around words are ignored, and `#` starts a comment
+    /// that runs until the end of the line.
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut words = Vec::new();
+        let mut expects_word = true;
+
+        for (line, column, token) in tokens(s) {
+            match (token, expects_word) {
+                (Token::Word(word), true) => words.push(number(line, column, word)?),
+                (Token::Comma, false) => {}
+                (Token::Comma, true) => {
+                    return Err(ParseError::new(line, column, ParseErrorKind::MissingWord))
+                }
+                (Token::Word(_), false) => {
+                    return Err(ParseError::new(line, column, ParseErrorKind::MissingComma))
+                }
+            }
+
+            expects_word = !expects_word;
+        }
+
+        if expects_word {
+            let (line, column) = end_of(s);
+            let kind = if words.is_empty() {
+                ParseErrorKind::Empty
+            } else {
+                ParseErrorKind::MissingWord
+            };
+
+            return Err(ParseError::new(line, column, kind));
+        }
+
+        Ok(Self::with_source(words, Source::Text))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let s = read(path)?;
+
+        let mut program = Self::parse(&s).map_err(|error| LoadError::Parse {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        program.provenance.source = Source::File(path.to_path_buf());
+
+        Ok(program)
+    }
+
+    /// Applies the patches in order. Either all patches are applied,
+    /// or none are.
+    pub fn apply(&mut self, patches: &[Patch]) -> Result<(), PatchError> {
+        let len = self.words.len();
+
+        if let Some(patch) = patches.iter().find(|patch| patch.addr >= len) {
+            return Err(PatchError { patch: *patch, len });
+        }
+
+        for patch in patches {
+            self.words[patch.addr] = patch.value;
+            self.provenance.patches.push(*patch);
+        }
+
+        Ok(())
+    }
+
+    pub fn patched(mut self, patches: &[Patch]) -> Result<Self, PatchError> {
+        self.apply(patches)?;
+        Ok(self)
+    }
+
+    /// Reads a patch list from `path`, and applies it.
+    #[cfg(feature = "std")]
+    pub fn apply_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), LoadError> {
+        let path = path.as_ref();
+        let s = read(path)?;
+
+        let patches = parse_patches(&s).map_err(|error| LoadError::Parse {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        Ok(self.apply(&patches)?)
+    }
+
+    pub fn words(&self) -> &[isize] {
+        &self.words
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn provenance(&self) -> &Provenance {
+        &self.provenance
+    }
+
+    pub fn into_vec(self) -> Vec<isize> {
+        self.words
+    }
+}
+
+#[cfg(feature = "std")]
+fn read(path: &Path) -> Result<String, LoadError> {
+    fs::read_to_string(path).map_err(|error| LoadError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+impl FromStr for Program {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl From<Vec<isize>> for Program {
+    fn from(words: Vec<isize>) -> Self {
+        Self::new(words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ignores_whitespace_and_comments() {
+        let program = Program::parse("# day02\n1, 0,0,3,\n  99 # halt\n\n").unwrap();
+
+        assert_eq!(program.words(), &[1, 0, 0, 3, 99]);
+        assert_eq!(program.provenance().source, Source::Text);
+    }
+
+    #[test]
+    fn parse_errors_are_positioned() {
+        let err = Program::parse("1,2,\n3,x4,5").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::new(2, 3, ParseErrorKind::InvalidNumber("x4".to_string()))
+        );
+
+        let err = Program::parse("1,,2").unwrap_err();
+        assert_eq!(err, ParseError::new(1, 3, ParseErrorKind::MissingWord));
+
+        let err = Program::parse("1,2\n3").unwrap_err();
+        assert_eq!(err, ParseError::new(2, 1, ParseErrorKind::MissingComma));
+
+        let err = Program::parse("1,2,\n").unwrap_err();
+        assert_eq!(err, ParseError::new(1, 5, ParseErrorKind::MissingWord));
+
+        let err = Program::parse(" # nothing\n").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::Empty);
+    }
+
+    #[test]
+    fn patches_are_recorded() {
+        let patches = parse_patches("1=12, 2 = 2 # noun and verb\n0=-1").unwrap();
+        assert_eq!(
+            patches,
+            [Patch::new(1, 12), Patch::new(2, 2), Patch::new(0, -1)]
+        );
+
+        let program = Program::new(vec![1, 0, 0, 3, 99])
+            .patched(&patches)
+            .unwrap();
+
+        assert_eq!(program.words(), &[-1, 12, 2, 3, 99]);
+        assert_eq!(program.provenance().to_string(), "<words> 1=12 2=2 0=-1");
+    }
+
+    #[test]
+    fn out_of_bounds_patch_applies_nothing() {
+        let mut program = Program::new(vec![1, 0, 0, 3, 99]);
+
+        let err = program
+            .apply(&[Patch::new(0, 2), Patch::new(5, 1)])
+            .unwrap_err();
+
+        assert_eq!(err.patch, Patch::new(5, 1));
+        assert_eq!(program.words(), &[1, 0, 0, 3, 99]);
+        assert!(program.provenance().patches.is_empty());
+    }
+
+    #[test]
+    fn patch_errors_are_positioned() {
+        let err = parse_patches("1=2,\n 3:4").unwrap_err();
+        assert_eq!(err, ParseError::new(2, 2, ParseErrorKind::MissingEquals));
+
+        let err = "7=q".parse::<Patch>().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::new(1, 3, ParseErrorKind::InvalidNumber("q".to_string()))
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Driving a machine by hand.
+//!
+//! A machine made with [`Machine::resumable`] runs until it needs
+//! something from whoever drives it, and hands back a [`Resume`] that
+//! says what: an input, to pass on an output, or nothing, because it
+//! halted or failed. It runs on the same core as a [`Future`] would.
+
+use alloc::vec::Vec;
+use core::{convert::Infallible, fmt, hash::Hash};
+
+use crate::{
+    future::{sink::Sink, stream::Stream, Future, Poll},
+    hooks::Hooks,
+    machine::{Intcode, Machine, MachineError},
+};
+
+/// Why [`Machine::resume`] returned.
+#[derive(Debug)]
+pub enum Resume<'a, T> {
+    /// The machine waits on an input, which goes in the slot.
+    NeedInput(Slot<'a, T>),
+    Output(T),
+    Halted,
+    Error(MachineError),
+}
+
+/// Where the input a machine waits on goes. Resuming without providing
+/// it waits on it again.
+#[derive(Debug)]
+pub struct Slot<'a, T> {
+    input: &'a mut PendingInput<T>,
+}
+
+impl<T> Slot<'_, T> {
+    pub fn provide(self, value: T) {
+        self.input.value = Some(value);
+    }
+}
+
+/// The stream a resumable machine reads from.
+#[derive(Debug)]
+pub struct PendingInput<T> {
+    value: Option<T>,
+    /// Whether the machine asked for a value it didn't get.
+    waiting: bool,
+}
+
+impl<T> Stream for PendingInput<T> {
+    type Item = T;
+
+    fn poll_next(&mut self) -> Poll<Option<Self::Item>> {
+        match self.value.take() {
+            Some(value) => Poll::Ready(Some(value)),
+            None => {
+                self.waiting = true;
+                Poll::Running
+            }
+        }
+    }
+}
+
+/// The sink a resumable machine writes to, which holds a single value.
+#[derive(Debug)]
+pub struct PendingOutput<T> {
+    value: Option<T>,
+}
+
+impl<T> Sink<T> for PendingOutput<T> {
+    type Error = Infallible;
+
+    fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+        match self.value {
+            Some(_) => Poll::Running,
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn send(&mut self, value: T) -> Result<(), Self::Error> {
+        self.value = Some(value);
+        Ok(())
+    }
+}
+
+impl<T> Machine<T, PendingInput<T>, PendingOutput<T>> {
+    /// A machine to drive with [`resume`](Self::resume).
+    pub fn resumable(memory: Vec<T>) -> Self {
+        Self::new(memory, PendingInput::new(), PendingOutput { value: None })
+    }
+}
+
+impl<T> PendingInput<T> {
+    fn new() -> Self {
+        Self {
+            value: None,
+            waiting: false,
+        }
+    }
+}
+
+impl<T, H> Machine<T, PendingInput<T>, PendingOutput<T>, H>
+where
+    T: Hash + Clone + Eq + fmt::Display,
+    H: Hooks<T>,
+    Self: Intcode<Output = Result<(), MachineError>>,
+{
+    /// Runs until the machine needs an input, has an output, or stops.
+    pub fn resume(&mut self) -> Resume<'_, T> {
+        loop {
+            let poll = self.poll();
+
+            if let Some(value) = self.writer_mut().value.take() {
+                return Resume::Output(value);
+            }
+
+            match poll {
+                Poll::Ready(Ok(())) => return Resume::Halted,
+                Poll::Ready(Err(e)) => return Resume::Error(e),
+                Poll::Running if self.reader().waiting => {
+                    let input = self.reader_mut();
+                    input.waiting = false;
+                    return Resume::NeedInput(Slot { input });
+                }
+                Poll::Running => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_until_halted() {
+        // Outputs the sum of two inputs, and then a zero.
+        let program = vec![3, 13, 3, 14, 1, 13, 14, 15, 4, 15, 104, 0, 99, 0, 0, 0];
+        let mut machine = Machine::resumable(program);
+
+        match machine.resume() {
+            Resume::NeedInput(slot) => slot.provide(2),
+            other => panic!("{:?}", other),
+        }
+
+        // It asks for the second input, which isn't provided, so it asks
+        // for it again.
+        assert!(matches!(machine.resume(), Resume::NeedInput(_)));
+        match machine.resume() {
+            Resume::NeedInput(slot) => slot.provide(40),
+            other => panic!("{:?}", other),
+        }
+
+        assert!(matches!(machine.resume(), Resume::Output(42)));
+        assert!(matches!(machine.resume(), Resume::Output(0)));
+        assert!(matches!(machine.resume(), Resume::Halted));
+    }
+
+    #[test]
+    fn resume_reports_errors() {
+        let mut machine = Machine::resumable(vec![1, 0, 0, 7, 99]);
+        assert!(matches!(
+            machine.resume(),
+            Resume::Error(MachineError::IndexOutOfBounds { len: 5, index: 7 })
+        ));
+    }
+}
@@ -0,0 +1,387 @@
+//! Reverse execution.
+//!
+//! A [`Debugger`] runs a machine while keeping an [`UndoLog`] of every
+//! instruction: where it started, what it overwrote, and how far the
+//! machine had read its input and written its output. Any number of
+//! instructions can then be taken back, and run again.
+//!
+//! Inputs are kept on an [`InputTape`], so they are read again after
+//! stepping back. Outputs can't be taken back, so an [`OutputTape`]
+//! drops the ones that were already sent when they are produced again.
+
+use alloc::vec::Vec;
+
+use crate::{
+    future::{sink::Sink, stream::Stream, Future, Poll},
+    hooks::{Hooks, State},
+    machine::{Machine, MachineError},
+    opcode::Opcode,
+};
+
+/// Where an instruction started.
+#[derive(Copy, Clone, Debug)]
+struct Step {
+    ip: usize,
+    base: isize,
+    inputs: usize,
+    outputs: usize,
+    /// The first of its entries in `UndoLog::writes`.
+    writes: usize,
+}
+
+/// Records what it takes to undo every instruction.
+#[derive(Clone, Debug)]
+pub struct UndoLog<T> {
+    steps: Vec<Step>,
+    /// Every write, as the address and the value it overwrote.
+    writes: Vec<(usize, T)>,
+    inputs: usize,
+    outputs: usize,
+}
+
+impl<T> Default for UndoLog<T> {
+    fn default() -> Self {
+        Self {
+            steps: Vec::new(),
+            writes: Vec::new(),
+            inputs: 0,
+            outputs: 0,
+        }
+    }
+}
+
+impl<T> UndoLog<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of instructions that can be undone.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Whether the last instruction wrote to `addr`.
+    fn last_wrote(&self, addr: usize) -> bool {
+        match self.steps.last() {
+            Some(step) => self.writes[step.writes..].iter().any(|&(a, _)| a == addr),
+            None => false,
+        }
+    }
+}
+
+impl<T: Clone> Hooks<T> for UndoLog<T> {
+    fn before_instruction(&mut self, _: &Opcode, state: &State<'_, T>) {
+        self.steps.push(Step {
+            ip: state.ip,
+            base: state.base,
+            inputs: self.inputs,
+            outputs: self.outputs,
+            writes: self.writes.len(),
+        });
+    }
+
+    fn on_write(&mut self, addr: usize, old: &T, _: &T) {
+        self.writes.push((addr, old.clone()));
+    }
+
+    fn on_input(&mut self, _: usize, _: &T) {
+        self.inputs += 1;
+    }
+
+    fn on_output(&mut self, _: &T) {
+        self.outputs += 1;
+    }
+
+    /// Halting changes nothing, so there is nothing to undo.
+    fn on_halt(&mut self, _: &State<'_, T>) {
+        self.steps.pop();
+    }
+}
+
+/// A stream that remembers everything it reads, so it can be read again.
+pub struct InputTape<R: Stream> {
+    inner: R,
+    values: Vec<R::Item>,
+    cursor: usize,
+}
+
+impl<R: Stream> InputTape<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            values: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    /// The values read so far, including any that will be read again.
+    pub fn values(&self) -> &[R::Item] {
+        &self.values
+    }
+}
+
+impl<R: Stream> Stream for InputTape<R>
+where
+    R::Item: Clone,
+{
+    type Item = R::Item;
+
+    fn poll_next(&mut self) -> Poll<Option<Self::Item>> {
+        if let Some(value) = self.values.get(self.cursor) {
+            self.cursor += 1;
+            return Poll::Ready(Some(value.clone()));
+        }
+
+        match ready!(self.inner.poll_next()) {
+            Some(value) => {
+                self.values.push(value.clone());
+                self.cursor += 1;
+                Poll::Ready(Some(value))
+            }
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+/// A sink that only passes on the values it has not sent before.
+pub struct OutputTape<W> {
+    inner: W,
+    sent: usize,
+    cursor: usize,
+}
+
+impl<W> OutputTape<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            sent: 0,
+            cursor: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<T, W: Sink<T>> Sink<T> for OutputTape<W> {
+    type Error = W::Error;
+
+    fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+        if self.cursor < self.sent {
+            Poll::Ready(Ok(()))
+        } else {
+            self.inner.poll_ready()
+        }
+    }
+
+    fn send(&mut self, item: T) -> Result<(), Self::Error> {
+        if self.cursor == self.sent {
+            self.inner.send(item)?;
+            self.sent += 1;
+        }
+
+        self.cursor += 1;
+        Ok(())
+    }
+}
+
+/// A point in the history of a [`Debugger`] to rewind to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Checkpoint(usize);
+
+/// A machine that can run backwards.
+///
+/// Stepping forward again after stepping back replays the same inputs,
+/// so it goes through the same states. Checkpoints stay valid, but
+/// [`Debugger::rewind`] only goes back in time.
+pub struct Debugger<R: Stream<Item = isize>, W: Sink<isize>> {
+    machine: Machine<isize, InputTape<R>, OutputTape<W>, UndoLog<isize>>,
+}
+
+impl<R: Stream<Item = isize>, W: Sink<isize>> Debugger<R, W> {
+    pub fn new(memory: Vec<isize>, reader: R, writer: W) -> Self {
+        let machine = Machine::with_hooks(
+            memory,
+            InputTape::new(reader),
+            OutputTape::new(writer),
+            UndoLog::new(),
+        );
+
+        Self { machine }
+    }
+
+    pub fn machine(&self) -> &Machine<isize, InputTape<R>, OutputTape<W>, UndoLog<isize>> {
+        &self.machine
+    }
+
+    /// The number of instructions run so far, and not taken back.
+    pub fn steps(&self) -> usize {
+        self.machine.hooks().len()
+    }
+
+    /// Runs a single instruction.
+    pub fn step(&mut self) -> Poll<Result<(), MachineError>> {
+        self.machine.poll()
+    }
+
+    /// Takes back the last instruction, and returns whether there was one.
+    pub fn step_back(&mut self) -> bool {
+        let log = self.machine.hooks_mut();
+        let step = match log.steps.pop() {
+            Some(step) => step,
+            None => return false,
+        };
+
+        let writes = log.writes.split_off(step.writes);
+        log.inputs = step.inputs;
+        log.outputs = step.outputs;
+
+        let memory = self.machine.memory_mut();
+        for (addr, old) in writes.into_iter().rev() {
+            memory[addr] = old;
+        }
+
+        self.machine.reader_mut().cursor = step.inputs;
+        self.machine.writer_mut().cursor = step.outputs;
+        self.machine.restore(step.ip, step.base);
+
+        true
+    }
+
+    /// Steps back until the instruction that last wrote to `addr` is
+    /// taken back, so it is the next one to run. Returns whether there
+    /// was one; otherwise the machine is back where it started.
+    pub fn run_back_to_write(&mut self, addr: usize) -> bool {
+        while !self.machine.hooks().is_empty() {
+            let wrote = self.machine.hooks().last_wrote(addr);
+            self.step_back();
+
+            if wrote {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.steps())
+    }
+
+    /// Steps back to `checkpoint`, and returns whether it got there.
+    /// A checkpoint that is ahead of the machine is left alone.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) -> bool {
+        if checkpoint.0 > self.steps() {
+            return false;
+        }
+
+        while self.steps() > checkpoint.0 {
+            self.step_back();
+        }
+
+        true
+    }
+
+    pub fn into_machine(self) -> Machine<isize, InputTape<R>, OutputTape<W>, UndoLog<isize>> {
+        self.machine
+    }
+}
+
+impl<R: Stream<Item = isize>, W: Sink<isize>> Future for Debugger<R, W> {
+    type Output = Result<(), MachineError>;
+
+    fn poll(&mut self) -> Poll<Self::Output> {
+        self.step()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::Infallible;
+
+    use crate::future::{stream::once, FutureExt};
+
+    struct Outputs(Vec<isize>);
+
+    impl Sink<isize> for Outputs {
+        type Error = Infallible;
+
+        fn poll_ready(&mut self) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn send(&mut self, value: isize) -> Result<(), Self::Error> {
+            self.0.push(value);
+            Ok(())
+        }
+    }
+
+    // Counts [13] up to 5.
+    const COUNTER: [isize; 15] = [1001, 13, 1, 13, 1007, 13, 5, 14, 1005, 14, 0, 99, 0, 0, 0];
+
+    #[test]
+    fn stepping_back_replays_io() {
+        let program = vec![109, 3, 3, 11, 1002, 11, 2, 11, 204, 8, 99, 0];
+        let mut outputs = Outputs(Vec::new());
+        let mut debugger = Debugger::new(program.clone(), once(21), &mut outputs);
+
+        assert!(debugger.execute().is_ok());
+        assert_eq!(debugger.steps(), 4);
+        assert_eq!(debugger.machine().memory()[11], 42);
+
+        while debugger.step_back() {}
+        assert_eq!(debugger.machine().memory(), &program[..]);
+        assert_eq!((debugger.machine().ip(), debugger.machine().base()), (0, 0));
+
+        // The input is long gone from `once`, and the output is not sent twice.
+        assert!(debugger.execute().is_ok());
+        assert_eq!(debugger.machine().memory()[11], 42);
+        drop(debugger);
+        assert_eq!(outputs.0, vec![42]);
+    }
+
+    #[test]
+    fn run_back_to_the_last_write() {
+        let mut debugger = Debugger::new(COUNTER.to_vec(), once(0), Outputs(Vec::new()));
+        assert!(debugger.execute().is_ok());
+        assert_eq!(debugger.machine().memory()[13], 5);
+
+        assert!(debugger.run_back_to_write(13));
+        assert_eq!(debugger.machine().ip(), 0);
+        assert_eq!(debugger.machine().memory()[13], 4);
+        assert_eq!(debugger.machine().memory()[14], 1);
+
+        assert!(debugger.run_back_to_write(13));
+        assert_eq!(debugger.machine().memory()[13], 3);
+
+        assert!(!debugger.run_back_to_write(12));
+        assert_eq!(debugger.steps(), 0);
+    }
+
+    #[test]
+    fn rewind_to_a_checkpoint() {
+        let mut debugger = Debugger::new(COUNTER.to_vec(), once(0), Outputs(Vec::new()));
+        for _ in 0..7 {
+            assert!(!debugger.step().is_ready());
+        }
+
+        let checkpoint = debugger.checkpoint();
+        let memory = debugger.machine().memory().to_vec();
+        let ip = debugger.machine().ip();
+
+        assert!(debugger.execute().is_ok());
+        assert!(debugger.rewind(checkpoint));
+        assert_eq!(debugger.machine().memory(), &memory[..]);
+        assert_eq!(debugger.machine().ip(), ip);
+
+        assert!(debugger.rewind(Checkpoint(2)));
+        assert!(!debugger.rewind(checkpoint));
+    }
+}
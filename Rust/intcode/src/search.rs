@@ -0,0 +1,222 @@
+//! Brute force searches, spread over threads.
+//!
+//! A search tries every candidate from an iterator, such as the patches or
+//! inputs to run a program with, on a fixed number of threads. The threads
+//! take candidates one at a time, so slow and fast ones even out.
+//!
+//! Results don't depend on the number of threads: [`Search::find_first`]
+//! finds the earliest candidate that matches, and [`Search::best`] the
+//! earliest of the ones that score highest.
+
+use std::{
+    cmp::Ordering,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering::Relaxed},
+        Mutex,
+    },
+    thread,
+};
+
+/// How to run a search.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Search {
+    threads: usize,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self { threads }
+    }
+}
+
+impl Search {
+    /// A search on as many threads as the machine runs at once.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of threads, which is at least one.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Finds the first candidate `test` returns something for, and what it
+    /// returned. Stops trying candidates once every earlier one is tried.
+    pub fn find_first<I, F, T>(&self, candidates: I, test: F) -> Option<(I::Item, T)>
+    where
+        I: IntoIterator,
+        I::IntoIter: Send,
+        I::Item: Send,
+        F: Fn(&I::Item) -> Option<T> + Sync,
+        T: Send,
+    {
+        // The index of the first match so far.
+        let found = AtomicUsize::new(usize::MAX);
+        let first = Mutex::new(None);
+
+        self.run(candidates, |index, candidate| {
+            // Candidates come in order, so every later one comes after it too.
+            if index > found.load(Relaxed) {
+                return false;
+            }
+
+            match test(&candidate) {
+                Some(result) => {
+                    let mut first = first.lock().unwrap();
+                    if index < found.fetch_min(index, Relaxed) {
+                        *first = Some((candidate, result));
+                    }
+                    false
+                }
+                None => true,
+            }
+        });
+
+        first.into_inner().unwrap()
+    }
+
+    /// Finds the candidate `objective` scores highest, and its score.
+    /// Candidates without a score are left out.
+    pub fn best<I, F, K>(&self, candidates: I, objective: F) -> Option<(I::Item, K)>
+    where
+        I: IntoIterator,
+        I::IntoIter: Send,
+        I::Item: Send,
+        F: Fn(&I::Item) -> Option<K> + Sync,
+        K: Ord + Send,
+    {
+        self.best_by(candidates, objective, Ord::cmp)
+    }
+
+    /// Like [`best`](Self::best), for scores compared by `compare`.
+    pub fn best_by<I, F, K, C>(
+        &self,
+        candidates: I,
+        objective: F,
+        compare: C,
+    ) -> Option<(I::Item, K)>
+    where
+        I: IntoIterator,
+        I::IntoIter: Send,
+        I::Item: Send,
+        F: Fn(&I::Item) -> Option<K> + Sync,
+        K: Send,
+        C: Fn(&K, &K) -> Ordering + Sync,
+    {
+        let best = Mutex::new(None::<(usize, I::Item, K)>);
+
+        self.run(candidates, |index, candidate| {
+            let score = match objective(&candidate) {
+                Some(score) => score,
+                None => return true,
+            };
+
+            // Ties go to the earliest candidate, whichever thread got there.
+            let mut best = best.lock().unwrap();
+            let better = match &*best {
+                Some((i, _, high)) => match compare(&score, high) {
+                    Ordering::Greater => true,
+                    Ordering::Equal => index < *i,
+                    Ordering::Less => false,
+                },
+                None => true,
+            };
+
+            if better {
+                *best = Some((index, candidate, score));
+            }
+
+            true
+        });
+
+        best.into_inner()
+            .unwrap()
+            .map(|(_, candidate, score)| (candidate, score))
+    }
+
+    /// Hands out `candidates` to the threads, which call `f` with each one
+    /// and its index until it returns false.
+    fn run<I, F>(&self, candidates: I, f: F)
+    where
+        I: IntoIterator,
+        I::IntoIter: Send,
+        I::Item: Send,
+        F: Fn(usize, I::Item) -> bool + Sync,
+    {
+        let candidates = Mutex::new(candidates.into_iter().enumerate());
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| loop {
+                    let next = candidates.lock().unwrap().next();
+                    let (index, candidate) = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+
+                    if !f(index, candidate) {
+                        break;
+                    }
+                });
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        future::{sink::Stdout, stream::once, FutureExt},
+        machine::Machine,
+        program::{Patch, Program},
+    };
+
+    /// Runs `program` with `patches` on `input`, and returns its output.
+    fn run(program: &Program, input: isize, patches: &[Patch]) -> Option<isize> {
+        let memory = program.clone().patched(patches).ok()?.into_vec();
+        let mut stdout = Stdout::new();
+        Machine::new(memory, once(input), &mut stdout)
+            .execute()
+            .ok()?;
+        stdout.into_inner()
+    }
+
+    #[test]
+    fn find_the_first_match() {
+        let program = Program::parse("3,14,2,13,14,15,4,15,99,0,0,0,0,0,0,0").unwrap();
+        let patches = |a| vec![Patch::new(13, a)];
+
+        for threads in 1..=4 {
+            let search = Search::new().threads(threads);
+
+            let found = search.find_first(0..100, |&a| {
+                run(&program, 6, &patches(a)).filter(|&out| out > 40)
+            });
+            assert_eq!(found, Some((7, 42)));
+
+            let found = search.find_first(0..100, |&a| {
+                run(&program, 6, &patches(a)).filter(|&out| out < 0)
+            });
+            assert_eq!(found, None);
+        }
+    }
+
+    #[test]
+    fn ties_go_to_the_earliest_candidate() {
+        for threads in 1..=4 {
+            let search = Search::new().threads(threads);
+
+            assert_eq!(search.best(0..20, |&n| Some(n % 5)), Some((4, 4)));
+            assert_eq!(
+                search.best_by(0..20, |&n| Some(n % 5), |a, b| b.cmp(a)),
+                Some((0, 0))
+            );
+            assert_eq!(search.best(0..20, |_| None::<isize>), None);
+        }
+    }
+}
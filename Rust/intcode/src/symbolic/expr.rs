@@ -0,0 +1,281 @@
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+/// An unknown value.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Symbol(pub(crate) usize);
+
+impl Symbol {
+    /// Where the value of this symbol is found in a solution.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "x{}", self.0)
+    }
+}
+
+/// A constant plus a sum of symbols, each multiplied by a coefficient.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Linear {
+    constant: isize,
+    /// Never holds a coefficient of 0.
+    terms: BTreeMap<Symbol, isize>,
+}
+
+impl Linear {
+    pub fn constant(value: isize) -> Self {
+        Self {
+            constant: value,
+            terms: BTreeMap::new(),
+        }
+    }
+
+    pub fn symbol(symbol: Symbol) -> Self {
+        let mut terms = BTreeMap::new();
+        terms.insert(symbol, 1);
+
+        Self { constant: 0, terms }
+    }
+
+    pub fn as_constant(&self) -> Option<isize> {
+        if self.terms.is_empty() {
+            Some(self.constant)
+        } else {
+            None
+        }
+    }
+
+    pub fn constant_term(&self) -> isize {
+        self.constant
+    }
+
+    pub fn coefficient(&self, symbol: Symbol) -> isize {
+        self.terms.get(&symbol).copied().unwrap_or(0)
+    }
+
+    /// The symbols with a non-zero coefficient, in order.
+    pub fn symbols(&self) -> impl Iterator<Item = Symbol> + '_ {
+        self.terms.keys().copied()
+    }
+
+    pub(crate) fn terms(&self) -> impl Iterator<Item = (Symbol, isize)> + '_ {
+        self.terms
+            .iter()
+            .map(|(symbol, coefficient)| (*symbol, *coefficient))
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let mut sum = self.clone();
+        sum.constant += other.constant;
+
+        for (symbol, coefficient) in other.terms() {
+            let entry = sum.terms.entry(symbol).or_insert(0);
+            *entry += coefficient;
+
+            if *entry == 0 {
+                sum.terms.remove(&symbol);
+            }
+        }
+
+        sum
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.scale(-1))
+    }
+
+    pub fn scale(&self, factor: isize) -> Self {
+        if factor == 0 {
+            return Self::constant(0);
+        }
+
+        Self {
+            constant: self.constant * factor,
+            terms: self
+                .terms()
+                .map(|(symbol, coefficient)| (symbol, coefficient * factor))
+                .collect(),
+        }
+    }
+
+    /// The product, if it is still linear.
+    pub fn mul(&self, other: &Self) -> Option<Self> {
+        match (self.as_constant(), other.as_constant()) {
+            (Some(factor), _) => Some(other.scale(factor)),
+            (_, Some(factor)) => Some(self.scale(factor)),
+            (None, None) => None,
+        }
+    }
+
+    /// Replaces `symbol` by `value`.
+    pub fn substitute(&self, symbol: Symbol, value: &Self) -> Self {
+        match self.terms.get(&symbol) {
+            None => self.clone(),
+            Some(coefficient) => {
+                let mut rest = self.clone();
+                rest.terms.remove(&symbol);
+                rest.add(&value.scale(*coefficient))
+            }
+        }
+    }
+
+    /// The value when every symbol takes its value from `values`.
+    pub fn eval(&self, values: &[isize]) -> isize {
+        self.terms()
+            .fold(self.constant, |sum, (symbol, coefficient)| {
+                sum + coefficient * values[symbol.0]
+            })
+    }
+}
+
+impl From<isize> for Linear {
+    fn from(value: isize) -> Self {
+        Self::constant(value)
+    }
+}
+
+impl From<Symbol> for Linear {
+    fn from(symbol: Symbol) -> Self {
+        Self::symbol(symbol)
+    }
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+
+        for (symbol, coefficient) in self.terms() {
+            let sign = match (first, coefficient < 0) {
+                (true, false) => "",
+                (true, true) => "-",
+                (false, false) => " + ",
+                (false, true) => " - ",
+            };
+
+            match coefficient.abs() {
+                1 => write!(f, "{}{}", sign, symbol)?,
+                n => write!(f, "{}{}*{}", sign, n, symbol)?,
+            }
+
+            first = false;
+        }
+
+        match (first, self.constant) {
+            (true, constant) => write!(f, "{}", constant),
+            (false, 0) => Ok(()),
+            (false, constant) if constant < 0 => write!(f, " - {}", -constant),
+            (false, constant) => write!(f, " + {}", constant),
+        }
+    }
+}
+
+/// How an expression compares to 0.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Relation {
+    Zero,
+    NonZero,
+    Negative,
+    NonNegative,
+}
+
+impl Relation {
+    pub fn holds(self, value: isize) -> bool {
+        match self {
+            Self::Zero => value == 0,
+            Self::NonZero => value != 0,
+            Self::Negative => value < 0,
+            Self::NonNegative => value >= 0,
+        }
+    }
+
+    pub fn negate(self) -> Self {
+        match self {
+            Self::Zero => Self::NonZero,
+            Self::NonZero => Self::Zero,
+            Self::Negative => Self::NonNegative,
+            Self::NonNegative => Self::Negative,
+        }
+    }
+}
+
+/// A condition on the symbols of a path.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Constraint {
+    pub expr: Linear,
+    pub relation: Relation,
+}
+
+impl Constraint {
+    pub fn new(expr: Linear, relation: Relation) -> Self {
+        Self { expr, relation }
+    }
+
+    /// `lhs == rhs`
+    pub fn equal(lhs: &Linear, rhs: &Linear) -> Self {
+        Self::new(lhs.sub(rhs), Relation::Zero)
+    }
+
+    /// `lhs != rhs`
+    pub fn not_equal(lhs: &Linear, rhs: &Linear) -> Self {
+        Self::new(lhs.sub(rhs), Relation::NonZero)
+    }
+
+    /// `lhs < rhs`
+    pub fn less(lhs: &Linear, rhs: &Linear) -> Self {
+        Self::new(lhs.sub(rhs), Relation::Negative)
+    }
+
+    /// `lhs >= rhs`
+    pub fn at_least(lhs: &Linear, rhs: &Linear) -> Self {
+        Self::new(lhs.sub(rhs), Relation::NonNegative)
+    }
+
+    pub fn negate(&self) -> Self {
+        Self::new(self.expr.clone(), self.relation.negate())
+    }
+
+    pub fn holds(&self, values: &[isize]) -> bool {
+        self.relation.holds(self.expr.eval(values))
+    }
+
+    pub fn substitute(&self, symbol: Symbol, value: &Linear) -> Self {
+        Self::new(self.expr.substitute(symbol, value), self.relation)
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.relation {
+            Relation::Zero => "==",
+            Relation::NonZero => "!=",
+            Relation::Negative => "<",
+            Relation::NonNegative => ">=",
+        };
+
+        write!(f, "{} {} 0", self.expr, op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_stays_normalized() {
+        let (x, y) = (Linear::symbol(Symbol(0)), Linear::symbol(Symbol(1)));
+
+        let expr = x.scale(3).add(&y).add(&Linear::constant(-4));
+        assert_eq!(expr.to_string(), "3*x0 + x1 - 4");
+
+        let expr = expr.sub(&y);
+        assert_eq!(expr.symbols().collect::<Vec<_>>(), vec![Symbol(0)]);
+        assert_eq!(expr.substitute(Symbol(0), &Linear::constant(2)), 2.into());
+
+        assert_eq!(x.mul(&y), None);
+        assert_eq!(x.mul(&Linear::constant(0)), Some(0.into()));
+    }
+}
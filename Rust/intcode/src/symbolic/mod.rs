@@ -0,0 +1,644 @@
+//! Symbolic execution of intcode programs.
+//!
+//! Memory cells and inputs can be linear expressions over [`Symbol`]s, each
+//! with a finite domain. The [`Executor`] follows every way the program can
+//! go for those symbols, and reports each path with the constraints that
+//! lead down it, and its outputs and final memory as expressions.
+//!
+//! Whenever a value has to be concrete (an opcode, a jump target, a write
+//! address, or either side of a non-linear product), the path is split
+//! on every value of one of the symbols it depends on, which is only done for
+//! domains of at most [`CONCRETIZE_LIMIT`] values. A read from an address that
+//! depends on symbols gives a placeholder instead, which is only resolved
+//! when the value is used in one of the above ways, compared, or still
+//! around when the program halts.
+
+mod expr;
+mod solver;
+
+pub use self::{
+    expr::{Constraint, Linear, Relation, Symbol},
+    solver::solve,
+};
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+use core::{convert::TryFrom, ops::RangeInclusive};
+
+use crate::{
+    machine::MachineError,
+    opcode::{Mnemonic, Mode, Opcode},
+};
+
+/// The default number of instructions a single path may execute.
+pub const STEP_LIMIT: usize = 1_000_000;
+
+/// The largest domain that is enumerated when a symbol needs a concrete value.
+pub const CONCRETIZE_LIMIT: usize = 1024;
+
+#[derive(Debug)]
+pub enum SymbolicError {
+    Machine(MachineError),
+    /// An address that is out of bounds for some values of its symbols.
+    OutOfBounds {
+        len: usize,
+        addr: Linear,
+    },
+    /// `symbol` needed a concrete value, but has too large a domain.
+    TooManyValues(Symbol),
+}
+
+impl From<MachineError> for SymbolicError {
+    fn from(e: MachineError) -> Self {
+        Self::Machine(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum End {
+    /// The program halted, with this memory.
+    Halted(Vec<Linear>),
+    Error(SymbolicError),
+    StepLimit,
+}
+
+/// One way through a program.
+#[derive(Debug)]
+pub struct Path {
+    /// What has to hold for the program to go this way.
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<Linear>,
+    pub end: End,
+}
+
+fn oob(len: usize, index: usize) -> MachineError {
+    MachineError::IndexOutOfBounds { len, index }
+}
+
+/// Why a path stopped executing an instruction.
+enum Stop {
+    Error(SymbolicError),
+    /// A symbol was fixed to a value, the instruction has to be tried again.
+    Retry,
+    /// No values of the symbols lead down this path.
+    Infeasible,
+}
+
+impl From<SymbolicError> for Stop {
+    fn from(e: SymbolicError) -> Self {
+        Self::Error(e)
+    }
+}
+
+impl From<MachineError> for Stop {
+    fn from(e: MachineError) -> Self {
+        Self::Error(e.into())
+    }
+}
+
+/// A read from an address that still depends on symbols.
+#[derive(Clone)]
+struct Read {
+    symbol: Symbol,
+    addr: Linear,
+    /// The memory at the time of the read.
+    memory: Vec<Linear>,
+}
+
+#[derive(Clone)]
+struct State {
+    ip: usize,
+    base: isize,
+    steps: usize,
+    memory: Vec<Linear>,
+    inputs: VecDeque<Linear>,
+    outputs: Vec<Linear>,
+    constraints: Vec<Constraint>,
+    /// The number of symbols of the executor. Any symbol after those
+    /// stands for one of the `reads`.
+    free: usize,
+    reads: Vec<Read>,
+    next_symbol: usize,
+}
+
+impl State {
+    fn has_reads(&self, value: &Linear) -> bool {
+        value.symbols().any(|symbol| symbol.0 >= self.free)
+    }
+
+    /// A symbol of the executor that `value` depends on.
+    fn pick(&self, value: &Linear) -> Symbol {
+        match value.symbols().find(|symbol| symbol.0 < self.free) {
+            Some(symbol) => symbol,
+            None => {
+                let symbol = value.symbols().next().expect("Constants have no symbols");
+                let read = self
+                    .reads
+                    .iter()
+                    .find(|read| read.symbol == symbol)
+                    .expect("Reads are only removed once resolved");
+
+                self.pick(&read.addr)
+            }
+        }
+    }
+
+    fn read(&mut self, addr: Linear) -> Linear {
+        let symbol = Symbol(self.next_symbol);
+        self.next_symbol += 1;
+
+        self.reads.push(Read {
+            symbol,
+            addr,
+            memory: self.memory.clone(),
+        });
+
+        symbol.into()
+    }
+
+    fn substitute(&mut self, symbol: Symbol, value: &Linear) {
+        let values = self
+            .memory
+            .iter_mut()
+            .chain(self.inputs.iter_mut())
+            .chain(self.outputs.iter_mut());
+
+        for cell in values {
+            *cell = cell.substitute(symbol, value);
+        }
+
+        for constraint in self.constraints.iter_mut() {
+            *constraint = constraint.substitute(symbol, value);
+        }
+
+        for read in self.reads.iter_mut() {
+            read.addr = read.addr.substitute(symbol, value);
+
+            for cell in read.memory.iter_mut() {
+                *cell = cell.substitute(symbol, value);
+            }
+        }
+    }
+
+    /// Fixes `symbol` to `value`, and resolves every read that pins down.
+    fn assign(&mut self, symbol: Symbol, value: isize) {
+        self.substitute(symbol, &value.into());
+        self.constraints
+            .push(Constraint::equal(&symbol.into(), &value.into()));
+
+        while let Some(idx) = self
+            .reads
+            .iter()
+            .position(|read| read.addr.as_constant().is_some())
+        {
+            let read = self.reads.remove(idx);
+            // Addresses are bounds checked when they are read from.
+            let addr = read.addr.as_constant().unwrap() as usize;
+            self.substitute(read.symbol, &read.memory[addr]);
+        }
+    }
+}
+
+/// Explores the paths through a program, for symbolic memory and inputs.
+pub struct Executor {
+    memory: Vec<Linear>,
+    inputs: Vec<Linear>,
+    domains: Vec<RangeInclusive<isize>>,
+    step_limit: usize,
+}
+
+impl Executor {
+    pub fn new(words: &[isize]) -> Self {
+        Self {
+            memory: words.iter().map(|word| Linear::constant(*word)).collect(),
+            inputs: Vec::new(),
+            domains: Vec::new(),
+            step_limit: STEP_LIMIT,
+        }
+    }
+
+    pub fn with_step_limit(mut self, limit: usize) -> Self {
+        self.step_limit = limit;
+        self
+    }
+
+    /// A new unknown, which takes a value within `domain`.
+    pub fn symbol(&mut self, domain: RangeInclusive<isize>) -> Symbol {
+        self.domains.push(domain);
+        Symbol(self.domains.len() - 1)
+    }
+
+    /// Sets the memory at `addr`, which must be within the program.
+    pub fn set<V: Into<Linear>>(&mut self, addr: usize, value: V) {
+        self.memory[addr] = value.into();
+    }
+
+    /// Adds a value to be read by the program, after any earlier ones.
+    pub fn input<V: Into<Linear>>(&mut self, value: V) {
+        self.inputs.push(value.into());
+    }
+
+    pub fn domains(&self) -> &[RangeInclusive<isize>] {
+        &self.domains
+    }
+
+    /// Follows every feasible path through the program.
+    pub fn explore(&self) -> Vec<Path> {
+        let mut pending = vec![State {
+            ip: 0,
+            base: 0,
+            steps: 0,
+            memory: self.memory.clone(),
+            inputs: self.inputs.iter().cloned().collect(),
+            outputs: Vec::new(),
+            constraints: Vec::new(),
+            free: self.domains.len(),
+            reads: Vec::new(),
+            next_symbol: self.domains.len(),
+        }];
+
+        let mut paths = Vec::new();
+
+        'paths: while let Some(mut state) = pending.pop() {
+            let end = loop {
+                if state.steps >= self.step_limit {
+                    break End::StepLimit;
+                }
+
+                match self.step(&mut state, &mut pending) {
+                    Ok(true) | Err(Stop::Retry) => {}
+                    Ok(false) => break End::Halted(state.memory),
+                    Err(Stop::Error(e)) => break End::Error(e),
+                    Err(Stop::Infeasible) => continue 'paths,
+                }
+            };
+
+            paths.push(Path {
+                constraints: state.constraints,
+                outputs: state.outputs,
+                end,
+            });
+        }
+
+        paths
+    }
+
+    /// Values for the symbols that lead down `path`, and for which
+    /// every one of the `goals` holds as well.
+    pub fn solve(&self, path: &Path, goals: &[Constraint]) -> Option<Vec<isize>> {
+        let constraints = path
+            .constraints
+            .iter()
+            .chain(goals.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        solve(&self.domains, &constraints)
+    }
+
+    fn feasible(&self, constraints: &[Constraint], extra: Constraint) -> bool {
+        let mut constraints = constraints.to_vec();
+        constraints.push(extra);
+
+        solve(&self.domains, &constraints).is_some()
+    }
+
+    /// Continues with `symbol` fixed to each of its feasible values,
+    /// the first of which in `state`, and the others in new paths.
+    fn split(&self, state: &mut State, symbol: Symbol, pending: &mut Vec<State>) -> Stop {
+        let domain = &self.domains[symbol.0];
+        let size = *domain.end() as i128 - *domain.start() as i128 + 1;
+
+        if size > CONCRETIZE_LIMIT as i128 {
+            return SymbolicError::TooManyValues(symbol).into();
+        }
+
+        let values = domain
+            .clone()
+            .filter(|value| {
+                let fixed = Constraint::equal(&symbol.into(), &(*value).into());
+                self.feasible(&state.constraints, fixed)
+            })
+            .collect::<Vec<_>>();
+
+        let (first, rest) = match values.split_first() {
+            Some(split) => split,
+            None => return Stop::Infeasible,
+        };
+
+        for value in rest.iter().rev() {
+            let mut other = state.clone();
+            other.assign(symbol, *value);
+            pending.push(other);
+        }
+
+        state.assign(symbol, *first);
+        Stop::Retry
+    }
+
+    fn concrete(
+        &self,
+        state: &mut State,
+        value: &Linear,
+        pending: &mut Vec<State>,
+    ) -> Result<isize, Stop> {
+        match value.as_constant() {
+            Some(value) => Ok(value),
+            None => {
+                let symbol = state.pick(value);
+                Err(self.split(state, symbol, pending))
+            }
+        }
+    }
+
+    /// Whether `constraint` holds. If it can go either way, a new path
+    /// is added for when it does not.
+    fn decide(
+        &self,
+        state: &mut State,
+        constraint: Constraint,
+        pending: &mut Vec<State>,
+    ) -> Result<bool, Stop> {
+        if let Some(value) = constraint.expr.as_constant() {
+            return Ok(constraint.relation.holds(value));
+        }
+
+        // Constraints are only ever on the symbols of the executor.
+        if state.has_reads(&constraint.expr) {
+            let symbol = state.pick(&constraint.expr);
+            return Err(self.split(state, symbol, pending));
+        }
+
+        let negated = constraint.negate();
+
+        match (
+            self.feasible(&state.constraints, constraint.clone()),
+            self.feasible(&state.constraints, negated.clone()),
+        ) {
+            (true, true) => {
+                // The other path decides the other way when it tries again.
+                let mut other = state.clone();
+                other.constraints.push(negated);
+                pending.push(other);
+
+                state.constraints.push(constraint);
+                Ok(true)
+            }
+            (true, false) => Ok(true),
+            (false, true) => Ok(false),
+            (false, false) => Err(Stop::Infeasible),
+        }
+    }
+
+    fn address(&self, state: &State, index: usize, mode: Mode) -> Result<Linear, Stop> {
+        let len = state.memory.len();
+        let offset = state.ip + index;
+        let param = state.memory.get(offset).ok_or(oob(len, offset))?;
+
+        Ok(match mode {
+            Mode::Immediate => Linear::constant(offset as isize),
+            Mode::Position => param.clone(),
+            Mode::Relative => param.add(&state.base.into()),
+        })
+    }
+
+    fn read_operand(
+        &self,
+        state: &mut State,
+        index: usize,
+        mode: Mode,
+        pending: &mut Vec<State>,
+    ) -> Result<Linear, Stop> {
+        let addr = self.address(state, index, mode)?;
+        let len = state.memory.len();
+
+        if let Some(addr) = addr.as_constant() {
+            let addr = usize::try_from(addr).map_err(MachineError::from)?;
+            return Ok(state.memory.get(addr).ok_or(oob(len, addr))?.clone());
+        }
+
+        let in_bounds = self.decide(state, Constraint::at_least(&addr, &0.into()), pending)?
+            && self.decide(
+                state,
+                Constraint::less(&addr, &(len as isize).into()),
+                pending,
+            )?;
+
+        if in_bounds {
+            Ok(state.read(addr))
+        } else {
+            Err(SymbolicError::OutOfBounds { len, addr }.into())
+        }
+    }
+
+    fn write_operand(
+        &self,
+        state: &mut State,
+        index: usize,
+        mode: Mode,
+        value: Linear,
+        pending: &mut Vec<State>,
+    ) -> Result<(), Stop> {
+        if mode == Mode::Immediate {
+            return Err(MachineError::WriteInImmediateMode.into());
+        }
+
+        let addr = self.address(state, index, mode)?;
+        let addr = self.concrete(state, &addr, pending)?;
+        let addr = usize::try_from(addr).map_err(MachineError::from)?;
+
+        let len = state.memory.len();
+        *state.memory.get_mut(addr).ok_or(oob(len, addr))? = value;
+
+        Ok(())
+    }
+
+    /// Executes a single instruction, and returns whether the program
+    /// continues afterwards.
+    fn step(&self, state: &mut State, pending: &mut Vec<State>) -> Result<bool, Stop> {
+        let len = state.memory.len();
+        let code = state
+            .memory
+            .get(state.ip)
+            .ok_or(oob(len, state.ip))?
+            .clone();
+        let code = self.concrete(state, &code, pending)?;
+
+        let Opcode { mnemonic, modes } = Opcode::parse(code).map_err(MachineError::from)?;
+
+        match mnemonic {
+            Mnemonic::Add | Mnemonic::Mul => {
+                let lhs = self.read_operand(state, 1, modes[0], pending)?;
+                let rhs = self.read_operand(state, 2, modes[1], pending)?;
+
+                let value = match mnemonic {
+                    Mnemonic::Add => lhs.add(&rhs),
+                    _ => match lhs.mul(&rhs) {
+                        Some(value) => value,
+                        None => {
+                            let symbol = state.pick(&lhs);
+                            return Err(self.split(state, symbol, pending));
+                        }
+                    },
+                };
+
+                self.write_operand(state, 3, modes[2], value, pending)?;
+            }
+            Mnemonic::Save => {
+                // Taking the input last, as the instruction may be tried again.
+                let value = state
+                    .inputs
+                    .front()
+                    .cloned()
+                    .ok_or(MachineError::ReaderExhausted)?;
+
+                self.write_operand(state, 1, modes[0], value, pending)?;
+                state.inputs.pop_front();
+            }
+            Mnemonic::Output => {
+                let value = self.read_operand(state, 1, modes[0], pending)?;
+                state.outputs.push(value);
+            }
+            Mnemonic::JumpIfTrue | Mnemonic::JumpIfFalse => {
+                let cond = self.read_operand(state, 1, modes[0], pending)?;
+                let target = self.read_operand(state, 2, modes[1], pending)?;
+
+                let relation = match mnemonic {
+                    Mnemonic::JumpIfTrue => Relation::NonZero,
+                    _ => Relation::Zero,
+                };
+
+                if self.decide(state, Constraint::new(cond, relation), pending)? {
+                    let target = self.concrete(state, &target, pending)?;
+
+                    state.ip = usize::try_from(target).map_err(MachineError::from)?;
+                    state.steps += 1;
+                    return Ok(true);
+                }
+            }
+            Mnemonic::LessThan | Mnemonic::Equals => {
+                let lhs = self.read_operand(state, 1, modes[0], pending)?;
+                let rhs = self.read_operand(state, 2, modes[1], pending)?;
+
+                let constraint = match mnemonic {
+                    Mnemonic::LessThan => Constraint::less(&lhs, &rhs),
+                    _ => Constraint::equal(&lhs, &rhs),
+                };
+
+                let value = self.decide(state, constraint, pending)? as isize;
+                self.write_operand(state, 3, modes[2], value.into(), pending)?;
+            }
+            Mnemonic::AdjustBase => {
+                let offset = self.read_operand(state, 1, modes[0], pending)?;
+                state.base += self.concrete(state, &offset, pending)?;
+            }
+            Mnemonic::Halt => {
+                let unresolved = state
+                    .memory
+                    .iter()
+                    .chain(state.outputs.iter())
+                    .find(|value| state.has_reads(value))
+                    .cloned();
+
+                if let Some(value) = unresolved {
+                    let symbol = state.pick(&value);
+                    return Err(self.split(state, symbol, pending));
+                }
+
+                return Ok(false);
+            }
+        }
+
+        state.ip += 1 + mnemonic.arity();
+        state.steps += 1;
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::compare_with_8;
+
+    #[test]
+    fn solves_for_an_output() {
+        // [0] = 100 * [1] + [2], after first adding [[1]] and [[2]] into [3].
+        let mut words = vec![1, 0, 0, 3, 1, 1, 2, 3, 2, 1, 17, 0, 1, 0, 2, 0, 99, 100];
+        words.resize(128, 0);
+
+        let mut executor = Executor::new(&words);
+        let noun = executor.symbol(0..=99);
+        let verb = executor.symbol(0..=99);
+        executor.set(1, noun);
+        executor.set(2, verb);
+
+        let paths = executor.explore();
+        assert_eq!(paths.len(), 1);
+
+        let memory = match &paths[0].end {
+            End::Halted(memory) => memory,
+            end => panic!("expected the program to halt, got {:?}", end),
+        };
+
+        assert_eq!(memory[0].to_string(), "100*x0 + x1");
+        assert_eq!(memory[3].to_string(), "x0 + x1");
+
+        let goal = Constraint::equal(&memory[0], &1234.into());
+        assert_eq!(executor.solve(&paths[0], &[goal]), Some(vec![12, 34]));
+    }
+
+    #[test]
+    fn forks_on_comparisons() {
+        let words = compare_with_8();
+
+        let mut executor = Executor::new(&words);
+        let input = executor.symbol(-100..=100);
+        executor.input(input);
+
+        let mut outputs = executor
+            .explore()
+            .iter()
+            .map(|path| {
+                assert!(matches!(path.end, End::Halted(_)));
+
+                let values = executor.solve(path, &[]).unwrap();
+                (path.outputs[0].eval(&values), path.outputs[0].to_string())
+            })
+            .collect::<Vec<_>>();
+
+        outputs.sort();
+
+        assert_eq!(
+            outputs,
+            vec![
+                (999, "999".to_string()),
+                (1000, "125*x0".to_string()),
+                (1001, "1001".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn products_of_symbols_are_made_concrete() {
+        // Outputs the product of two inputs.
+        let words = [3, 13, 3, 14, 2, 13, 14, 15, 4, 15, 99, 0, 0, 0, 0, 0];
+
+        let mut executor = Executor::new(&words);
+        let x = executor.symbol(0..=3);
+        let y = executor.symbol(0..=3);
+        executor.input(x);
+        executor.input(y);
+
+        let paths = executor.explore();
+
+        let outputs = paths
+            .iter()
+            .map(|path| path.outputs[0].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(outputs, vec!["0", "x1", "2*x1", "3*x1"]);
+
+        let fixed = paths[2].constraints[0].to_string();
+        assert_eq!(fixed, "x0 - 2 == 0");
+    }
+}
@@ -0,0 +1,263 @@
+use alloc::{vec, vec::Vec};
+use core::ops::RangeInclusive;
+
+use super::expr::{Constraint, Relation, Symbol};
+
+/// Finds values within `domains` for which every constraint holds,
+/// with `domains[n]` being the domain of the symbol with index `n`.
+///
+/// Symbols that share no constraints are solved for separately, each group
+/// by a plain backtracking search. Before trying values for a symbol, its
+/// domain is narrowed down by the bounds the constraints put on it, so
+/// equalities and comparisons are cheap, but many `!=` constraints on
+/// large domains can still make it slow.
+pub fn solve(domains: &[RangeInclusive<isize>], constraints: &[Constraint]) -> Option<Vec<isize>> {
+    let contradiction = constraints.iter().any(|constraint| {
+        matches!(constraint.expr.as_constant(), Some(value) if !constraint.relation.holds(value))
+    });
+
+    if contradiction || domains.iter().any(|domain| domain.is_empty()) {
+        return None;
+    }
+
+    let mut values = vec![None; domains.len()];
+
+    for group in groups(domains.len(), constraints) {
+        let constraints = constraints
+            .iter()
+            .filter(|constraint| {
+                constraint
+                    .expr
+                    .symbols()
+                    .any(|symbol| group.contains(&symbol.0))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if !search(domains, &constraints, &group, &mut values) {
+            return None;
+        }
+    }
+
+    Some(
+        values
+            .iter()
+            .zip(domains)
+            .map(|(value, domain)| value.unwrap_or(*domain.start()))
+            .collect(),
+    )
+}
+
+/// The symbols that are connected through constraints, in order.
+/// Symbols without any constraints are left out.
+fn groups(len: usize, constraints: &[Constraint]) -> Vec<Vec<usize>> {
+    let mut group = (0..len).collect::<Vec<_>>();
+    let mut constrained = vec![false; len];
+
+    for constraint in constraints.iter() {
+        let mut symbols = constraint.expr.symbols();
+
+        if let Some(first) = symbols.next() {
+            constrained[first.0] = true;
+
+            for symbol in symbols {
+                constrained[symbol.0] = true;
+
+                let (from, to) = (group[symbol.0], group[first.0]);
+                for label in group.iter_mut().filter(|label| **label == from) {
+                    *label = to;
+                }
+            }
+        }
+    }
+
+    let mut groups = Vec::<Vec<usize>>::new();
+    for symbol in (0..len).filter(|symbol| constrained[*symbol]) {
+        match groups
+            .iter_mut()
+            .find(|other| group[other[0]] == group[symbol])
+        {
+            Some(other) => other.push(symbol),
+            None => groups.push(vec![symbol]),
+        }
+    }
+
+    groups
+}
+
+/// Assigns the symbols in `order`, starting at the first unassigned one.
+fn search(
+    domains: &[RangeInclusive<isize>],
+    constraints: &[Constraint],
+    order: &[usize],
+    values: &mut [Option<isize>],
+) -> bool {
+    let (symbol, rest) = match order.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+
+    let candidates = match narrow(domains, constraints, Symbol(*symbol), values) {
+        Some(candidates) => candidates,
+        None => return false,
+    };
+
+    for value in candidates {
+        values[*symbol] = Some(value);
+
+        if constraints
+            .iter()
+            .all(|constraint| satisfiable(domains, constraint, values))
+            && search(domains, constraints, rest, values)
+        {
+            return true;
+        }
+    }
+
+    values[*symbol] = None;
+    false
+}
+
+/// The bounds of `expr` without the symbol `skip`, given the assigned `values`,
+/// and anything within the domains for the rest.
+fn bounds(
+    domains: &[RangeInclusive<isize>],
+    constraint: &Constraint,
+    values: &[Option<isize>],
+    skip: Option<Symbol>,
+) -> (i128, i128) {
+    let constant = constraint.expr.constant_term() as i128;
+    let (mut min, mut max) = (constant, constant);
+
+    for (symbol, coefficient) in constraint.expr.terms() {
+        let coefficient = coefficient as i128;
+
+        if Some(symbol) == skip {
+            continue;
+        }
+
+        match values[symbol.0] {
+            Some(value) => {
+                min += coefficient * value as i128;
+                max += coefficient * value as i128;
+            }
+            None => {
+                let lo = coefficient * *domains[symbol.0].start() as i128;
+                let hi = coefficient * *domains[symbol.0].end() as i128;
+                min += lo.min(hi);
+                max += lo.max(hi);
+            }
+        }
+    }
+
+    (min, max)
+}
+
+/// Whether `constraint` can still hold, given the assigned `values`,
+/// and anything within the domains for the rest.
+fn satisfiable(
+    domains: &[RangeInclusive<isize>],
+    constraint: &Constraint,
+    values: &[Option<isize>],
+) -> bool {
+    let (min, max) = bounds(domains, constraint, values, None);
+
+    match constraint.relation {
+        Relation::Zero => min <= 0 && 0 <= max,
+        Relation::NonZero => min != 0 || max != 0,
+        Relation::Negative => min < 0,
+        Relation::NonNegative => max >= 0,
+    }
+}
+
+/// The domain of `symbol`, restricted to the values for which
+/// every constraint can still hold. `None` if there are no such values.
+fn narrow(
+    domains: &[RangeInclusive<isize>],
+    constraints: &[Constraint],
+    symbol: Symbol,
+    values: &[Option<isize>],
+) -> Option<RangeInclusive<isize>> {
+    let domain = &domains[symbol.0];
+    let (mut lo, mut hi) = (*domain.start() as i128, *domain.end() as i128);
+
+    for constraint in constraints.iter() {
+        let coefficient = constraint.expr.coefficient(symbol) as i128;
+        if coefficient == 0 {
+            continue;
+        }
+
+        // `coefficient * symbol + rest` with `min <= rest <= max`.
+        let (min, max) = bounds(domains, constraint, values, Some(symbol));
+
+        // `at_least <= coefficient * symbol <= at_most`
+        let (at_least, at_most) = match constraint.relation {
+            Relation::Zero => (Some(-max), Some(-min)),
+            Relation::Negative => (None, Some(-min - 1)),
+            Relation::NonNegative => (Some(-max), None),
+            Relation::NonZero => continue,
+        };
+
+        let (at_least, at_most) = if coefficient > 0 {
+            (at_least, at_most)
+        } else {
+            (at_most, at_least)
+        };
+
+        if let Some(bound) = at_least {
+            lo = lo.max(ceil_div(bound, coefficient));
+        }
+
+        if let Some(bound) = at_most {
+            hi = hi.min(floor_div(bound, coefficient));
+        }
+    }
+
+    if lo <= hi {
+        Some(lo as isize..=hi as isize)
+    } else {
+        None
+    }
+}
+
+fn floor_div(a: i128, b: i128) -> i128 {
+    let quotient = a / b;
+
+    if a % b != 0 && (a < 0) != (b < 0) {
+        quotient - 1
+    } else {
+        quotient
+    }
+}
+
+fn ceil_div(a: i128, b: i128) -> i128 {
+    -floor_div(-a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::symbolic::Linear;
+
+    #[test]
+    fn solves_through_bounds() {
+        let (x, y) = (Linear::symbol(Symbol(0)), Linear::symbol(Symbol(1)));
+        let domains = [isize::MIN..=isize::MAX, 0..=10];
+
+        // x + y == 0, y >= 3, x != -3
+        let constraints = [
+            Constraint::equal(&x.add(&y), &0.into()),
+            Constraint::at_least(&y, &3.into()),
+            Constraint::not_equal(&x, &(-3).into()),
+        ];
+
+        let values = solve(&domains, &constraints).unwrap();
+        assert_eq!(values, vec![-10, 10]);
+        assert!(constraints.iter().all(|c| c.holds(&values)));
+
+        // 2 * y == 7
+        let constraints = [Constraint::equal(&y.scale(2), &7.into())];
+        assert_eq!(solve(&domains, &constraints), None);
+    }
+}
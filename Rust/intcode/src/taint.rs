@@ -0,0 +1,333 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{fmt, mem};
+
+use crate::{
+    hooks::{Hooks, State},
+    opcode::{Mnemonic, Mode, Opcode},
+};
+
+/// Where a value came from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Label {
+    /// The `n`th input, counting from 0.
+    Input(usize),
+    /// The value a memory cell held when it was labelled.
+    Cell(usize),
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Input(n) => write!(f, "input {}", n),
+            Self::Cell(addr) => write!(f, "[{}]", addr),
+        }
+    }
+}
+
+/// How a value depends on a label.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Dependence {
+    /// Through sums, and products with untainted values.
+    Linear,
+    /// Through anything else: products of tainted values, comparisons,
+    /// addresses and, when tracked, control flow.
+    NonLinear,
+}
+
+/// The labels a value depends on.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Labels(BTreeMap<Label, Dependence>);
+
+impl Labels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn single(label: Label) -> Self {
+        let mut labels = Self::new();
+        labels.0.insert(label, Dependence::Linear);
+        labels
+    }
+
+    pub fn get(&self, label: Label) -> Option<Dependence> {
+        self.0.get(&label).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Label, Dependence)> + '_ {
+        self.0
+            .iter()
+            .map(|(label, dependence)| (*label, *dependence))
+    }
+
+    fn union(mut self, other: &Self) -> Self {
+        for (label, dependence) in other.iter() {
+            let entry = self.0.entry(label).or_insert(dependence);
+            *entry = (*entry).max(dependence);
+        }
+
+        self
+    }
+
+    fn non_linear(mut self) -> Self {
+        for dependence in self.0.values_mut() {
+            *dependence = Dependence::NonLinear;
+        }
+
+        self
+    }
+}
+
+impl fmt::Display for Labels {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+
+        for (idx, (label, dependence)) in self.iter().enumerate() {
+            let sep = if idx == 0 { "" } else { ", " };
+            match dependence {
+                Dependence::Linear => write!(f, "{}{}", sep, label)?,
+                Dependence::NonLinear => write!(f, "{}{} (non-linear)", sep, label)?,
+            }
+        }
+
+        write!(f, "}}")
+    }
+}
+
+/// Tracks how inputs and labelled memory cells flow into memory and outputs.
+///
+/// Labels propagate through the values an instruction reads, and the
+/// addresses it reads from and writes to. Jumps only propagate them when
+/// [`Taint::with_control_flow`] is set, and then very coarsely: every value
+/// written or output after a jump on a tainted condition depends on that
+/// condition, for the rest of the run.
+#[derive(Clone, Debug, Default)]
+pub struct Taint {
+    /// The labels of every tainted cell.
+    cells: BTreeMap<usize, Labels>,
+    base: Labels,
+    inputs: usize,
+    outputs: Vec<Labels>,
+    /// The labels of the value the current instruction writes or outputs.
+    pending: Labels,
+    control: Option<Labels>,
+}
+
+impl Taint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_control_flow(mut self) -> Self {
+        self.control = Some(Labels::new());
+        self
+    }
+
+    /// Labels the cell at `addr`, with [`Label::Cell`].
+    pub fn label(&mut self, addr: usize) {
+        self.cells.insert(addr, Labels::single(Label::Cell(addr)));
+    }
+
+    /// The labels the cell at `addr` currently depends on.
+    pub fn cell(&self, addr: usize) -> Labels {
+        self.cells.get(&addr).cloned().unwrap_or_default()
+    }
+
+    /// The labels of every output so far, in order.
+    pub fn outputs(&self) -> &[Labels] {
+        &self.outputs
+    }
+
+    fn at(&self, addr: isize) -> Labels {
+        if addr < 0 {
+            return Labels::new();
+        }
+
+        self.cell(addr as usize)
+    }
+
+    /// The labels of the address of the `index`th parameter.
+    fn address(&self, state: &State<'_, isize>, index: usize, mode: Mode) -> Labels {
+        let param = self.cell(state.ip + index);
+
+        match mode {
+            Mode::Immediate => Labels::new(),
+            Mode::Position => param,
+            Mode::Relative => param.union(&self.base),
+        }
+    }
+
+    /// The labels of the value of the `index`th parameter, including those of its address.
+    fn operand(&self, state: &State<'_, isize>, index: usize, mode: Mode) -> Labels {
+        let param = match state.memory.get(state.ip + index) {
+            Some(param) => *param,
+            None => return Labels::new(),
+        };
+
+        let value = match mode {
+            Mode::Immediate => self.cell(state.ip + index),
+            Mode::Position => self.at(param),
+            Mode::Relative => self.at(param + state.base),
+        };
+
+        value.union(&self.address(state, index, mode).non_linear())
+    }
+}
+
+impl Hooks<isize> for Taint {
+    fn before_instruction(&mut self, opcode: &Opcode, state: &State<'_, isize>) {
+        let modes = opcode.modes();
+        let operand = |index: usize| self.operand(state, index, modes[index - 1]);
+
+        let value = match opcode.mnemonic() {
+            Mnemonic::Add => operand(1).union(&operand(2)),
+            Mnemonic::Mul => {
+                let (lhs, rhs) = (operand(1), operand(2));
+
+                if lhs.is_empty() || rhs.is_empty() {
+                    lhs.union(&rhs)
+                } else {
+                    lhs.union(&rhs).non_linear()
+                }
+            }
+            Mnemonic::LessThan | Mnemonic::Equals => operand(1).union(&operand(2)).non_linear(),
+            Mnemonic::Save => Labels::single(Label::Input(self.inputs)),
+            Mnemonic::Output => operand(1),
+            Mnemonic::JumpIfTrue | Mnemonic::JumpIfFalse => {
+                let cond = operand(1).union(&operand(2)).non_linear();
+
+                if let Some(control) = self.control.take() {
+                    self.control = Some(control.union(&cond));
+                }
+
+                Labels::new()
+            }
+            Mnemonic::AdjustBase => {
+                self.base = self.base.clone().union(&operand(1));
+                Labels::new()
+            }
+            Mnemonic::Halt => Labels::new(),
+        };
+
+        // Where a value is written to depends on the address as well.
+        let written = match opcode.mnemonic() {
+            Mnemonic::Add | Mnemonic::Mul | Mnemonic::LessThan | Mnemonic::Equals => {
+                self.address(state, 3, modes[2]).non_linear()
+            }
+            Mnemonic::Save => self.address(state, 1, modes[0]).non_linear(),
+            _ => Labels::new(),
+        };
+
+        self.pending = value.union(&written);
+
+        if let Some(control) = &self.control {
+            self.pending = self.pending.clone().union(control);
+        }
+    }
+
+    fn on_write(&mut self, addr: usize, _: &isize, _: &isize) {
+        let labels = mem::take(&mut self.pending);
+
+        if labels.is_empty() {
+            self.cells.remove(&addr);
+        } else {
+            self.cells.insert(addr, labels);
+        }
+    }
+
+    fn on_input(&mut self, _: usize, _: &isize) {
+        self.inputs += 1;
+    }
+
+    fn on_output(&mut self, _: &isize) {
+        let labels = mem::take(&mut self.pending);
+        self.outputs.push(labels);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        future::{
+            sink::Stdout,
+            stream::{empty, once, Stream},
+            FutureExt,
+        },
+        machine::Machine,
+    };
+
+    fn run<R: Stream<Item = isize>>(words: Vec<isize>, inputs: R, taint: &mut Taint) {
+        let mut machine = Machine::with_hooks(words, inputs, Stdout::new(), taint);
+        machine.execute().unwrap();
+    }
+
+    #[test]
+    fn tracks_linear_dependence_on_cells() {
+        // [0] = 100 * [1] + [2], after first adding [[1]] and [[2]] into [3].
+        let mut words = vec![1, 0, 0, 3, 1, 1, 2, 3, 2, 1, 17, 0, 1, 0, 2, 0, 99, 100];
+        words[1] = 12;
+        words[2] = 2;
+        words.resize(20, 0);
+
+        let mut taint = Taint::new();
+        taint.label(1);
+        taint.label(2);
+        run(words, empty(), &mut taint);
+
+        let labels = taint.cell(0);
+        assert_eq!(labels.get(Label::Cell(1)), Some(Dependence::Linear));
+        assert_eq!(labels.get(Label::Cell(2)), Some(Dependence::Linear));
+        assert_eq!(labels.to_string(), "{[1], [2]}");
+
+        assert_eq!(taint.cell(17), Labels::new());
+    }
+
+    #[test]
+    fn products_and_comparisons_are_non_linear() {
+        // Outputs the square of its input, 5 times its input,
+        // and whether the input is less than 8.
+        let words = vec![
+            3, 21, 2, 21, 21, 22, 4, 22, 1002, 21, 5, 22, 4, 22, 1007, 21, 8, 22, 4, 22, 99, 0, 0,
+        ];
+
+        let mut taint = Taint::new();
+        run(words, once(3), &mut taint);
+
+        let outputs = taint
+            .outputs()
+            .iter()
+            .map(|labels| labels.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            outputs,
+            [
+                "{input 0 (non-linear)}",
+                "{input 0}",
+                "{input 0 (non-linear)}"
+            ]
+        );
+    }
+
+    #[test]
+    fn control_flow_is_only_tracked_when_asked_for() {
+        // Outputs 1 if the input is non-zero, and 2 otherwise.
+        let words = vec![3, 11, 1005, 11, 8, 104, 2, 99, 104, 1, 99, 0];
+
+        let mut taint = Taint::new();
+        run(words.clone(), once(5), &mut taint);
+        assert_eq!(taint.outputs(), [Labels::new()]);
+
+        let mut taint = Taint::new().with_control_flow();
+        run(words, once(5), &mut taint);
+        assert_eq!(
+            taint.outputs()[0].get(Label::Input(0)),
+            Some(Dependence::NonLinear)
+        );
+    }
+}
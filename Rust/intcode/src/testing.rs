@@ -0,0 +1,621 @@
+//! Declarative test cases for intcode programs.
+//!
+//! A case file holds any number of cases, each of which starts with its
+//! name in brackets, followed by `key: value` lines:
+//!
+//! ```text
+//! # Comparisons with 8, from day 5.
+//! [equal to 8]
+//! program: 3,9,8,9,10,9,4,9,99,-1,8
+//! input: 8
+//! output: 1
+//!
+//! [first part]
+//! file: ../../../Inputs/day02.txt
+//! patch: 1=12, 2=2
+//! memory: 0=5098658
+//! ```
+//!
+//! The program is either given inline with `program`, or read from `file`,
+//! relative to the case file. `patch` modifies it before it runs, and
+//! `size` pads its memory with zeros. `input` lists the values it reads.
+//!
+//! What is expected is the `output`, the values of some `memory` cells
+//! once it stops, and the `error` it fails with, as the name of the
+//! [`MachineError`] variant. Without an `error`, the program must halt.
+//!
+//! A value continues on the lines below that start with whitespace, and
+//! `#` starts a comment that runs until the end of the line.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    future::{sink::Stdout, stream::Stream, FutureExt, Poll},
+    hooks::Hooks,
+    machine::{Machine, MachineError},
+    program::{parse_patches, ParseError, Patch, PatchError, Program},
+};
+
+/// How often a running case checks whether it is stuck in a loop.
+const LOOP_INTERVAL: usize = 1 << 12;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CaseErrorKind {
+    /// A key before the name of the first case.
+    ExpectedCase,
+    /// A line that is neither a name, nor `key: value`.
+    ExpectedKey,
+    UnknownKey(String),
+    DuplicateKey(String),
+    /// A case with neither `program` nor `file`, or both.
+    ExpectedProgram,
+    InvalidSize(String),
+    Parse(ParseError),
+    Patch(PatchError),
+    /// The file of a case could not be loaded.
+    Load(String),
+}
+
+/// An error while parsing a case file.
+/// Lines and columns both start at 1.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CaseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: CaseErrorKind,
+}
+
+impl CaseError {
+    const fn new(line: usize, column: usize, kind: CaseErrorKind) -> Self {
+        Self { line, column, kind }
+    }
+}
+
+impl fmt::Display for CaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+
+        match &self.kind {
+            CaseErrorKind::ExpectedCase => write!(f, "expected `[name]`"),
+            CaseErrorKind::ExpectedKey => write!(f, "expected `key: value`"),
+            CaseErrorKind::UnknownKey(key) => write!(f, "unknown key `{}`", key),
+            CaseErrorKind::DuplicateKey(key) => write!(f, "`{}` is given twice", key),
+            CaseErrorKind::ExpectedProgram => write!(f, "expected either `program` or `file`"),
+            CaseErrorKind::InvalidSize(size) => write!(f, "invalid size `{}`", size),
+            CaseErrorKind::Parse(e) => {
+                // Drops the position, which is already there.
+                let e = e.to_string();
+                match e.split_once(": ") {
+                    Some((_, message)) => write!(f, "{}", message),
+                    None => write!(f, "{}", e),
+                }
+            }
+            CaseErrorKind::Patch(e) => e.fmt(f),
+            CaseErrorKind::Load(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadCasesError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { path: PathBuf, error: CaseError },
+}
+
+impl fmt::Display for LoadCasesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            Self::Parse { path, error } => write!(f, "{}:{}", path.display(), error),
+        }
+    }
+}
+
+/// A program, what to run it on, and what it should do.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Case {
+    pub name: String,
+    /// The line the case starts on.
+    pub line: usize,
+    pub program: Program,
+    pub input: Vec<isize>,
+    pub output: Option<Vec<isize>>,
+    pub memory: Vec<Patch>,
+    pub error: Option<String>,
+}
+
+/// The value of a key, and where it starts.
+struct Value {
+    line: usize,
+    column: usize,
+    text: String,
+}
+
+impl Value {
+    /// Moves an error from within the value to where it is in the file.
+    fn locate(&self, e: ParseError) -> CaseError {
+        let column = if e.line == 1 {
+            self.column + e.column - 1
+        } else {
+            e.column
+        };
+
+        CaseError::new(self.line + e.line - 1, column, CaseErrorKind::Parse(e))
+    }
+
+    fn words(&self) -> Result<Vec<isize>, CaseError> {
+        if self.text.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        Program::parse(&self.text)
+            .map(Program::into_vec)
+            .map_err(|e| self.locate(e))
+    }
+
+    fn patches(&self) -> Result<Vec<Patch>, CaseError> {
+        parse_patches(&self.text).map_err(|e| self.locate(e))
+    }
+}
+
+const KEYS: [&str; 8] = [
+    "program", "file", "patch", "size", "input", "output", "memory", "error",
+];
+
+fn case(
+    name: String,
+    line: usize,
+    mut values: HashMap<&'static str, Value>,
+    dir: &Path,
+) -> Result<Case, CaseError> {
+    let mut program = match (values.remove("program"), values.remove("file")) {
+        (Some(value), None) => Program::new(value.words()?),
+        (None, Some(value)) => Program::load(dir.join(value.text.trim())).map_err(|e| {
+            CaseError::new(value.line, value.column, CaseErrorKind::Load(e.to_string()))
+        })?,
+        _ => return Err(CaseError::new(line, 1, CaseErrorKind::ExpectedProgram)),
+    };
+
+    if let Some(value) = values.remove("patch") {
+        program = program
+            .patched(&value.patches()?)
+            .map_err(|e| CaseError::new(value.line, value.column, CaseErrorKind::Patch(e)))?;
+    }
+
+    if let Some(value) = values.remove("size") {
+        let size = value.text.trim();
+        let size = size.parse::<usize>().map_err(|_| {
+            let kind = CaseErrorKind::InvalidSize(size.to_string());
+            CaseError::new(value.line, value.column, kind)
+        })?;
+
+        let mut words = program.into_vec();
+        words.resize(words.len().max(size), 0);
+        program = Program::new(words);
+    }
+
+    Ok(Case {
+        name,
+        line,
+        program,
+        input: match values.remove("input") {
+            Some(value) => value.words()?,
+            None => Vec::new(),
+        },
+        output: values
+            .remove("output")
+            .map(|value| value.words())
+            .transpose()?,
+        memory: match values.remove("memory") {
+            Some(value) => value.patches()?,
+            None => Vec::new(),
+        },
+        error: values
+            .remove("error")
+            .map(|value| value.text.trim().to_string()),
+    })
+}
+
+/// Parses the cases in `s`. Files are looked up relative to `dir`.
+pub fn parse_cases(s: &str, dir: &Path) -> Result<Vec<Case>, CaseError> {
+    let mut cases = Vec::new();
+    let mut current: Option<(String, usize, HashMap<&'static str, Value>)> = None;
+    let mut last_key = None;
+
+    for (n, line) in s.lines().enumerate() {
+        let n = n + 1;
+        let line = match line.find('#') {
+            Some(idx) => &line[..idx],
+            None => line,
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // A value that goes on.
+        if line.starts_with(char::is_whitespace) {
+            match (current.as_mut(), last_key) {
+                (Some((_, _, values)), Some(key)) => {
+                    let value: &mut Value = values.get_mut(key).unwrap();
+                    value.text.push('\n');
+                    value.text.push_str(line);
+                    continue;
+                }
+                _ => return Err(CaseError::new(n, 1, CaseErrorKind::ExpectedKey)),
+            }
+        }
+
+        let trimmed = line.trim_end();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            if let Some((name, line, values)) = current.take() {
+                cases.push(case(name, line, values, dir)?);
+            }
+
+            let name = trimmed[1..trimmed.len() - 1].trim().to_string();
+            current = Some((name, n, HashMap::new()));
+            last_key = None;
+            continue;
+        }
+
+        let values = match current.as_mut() {
+            Some((_, _, values)) => values,
+            None => return Err(CaseError::new(n, 1, CaseErrorKind::ExpectedCase)),
+        };
+
+        let colon = line
+            .find(':')
+            .ok_or_else(|| CaseError::new(n, 1, CaseErrorKind::ExpectedKey))?;
+        let name = line[..colon].trim();
+        let key = match KEYS.iter().find(|&&key| key == name) {
+            Some(&key) => key,
+            None => {
+                let kind = CaseErrorKind::UnknownKey(name.to_string());
+                return Err(CaseError::new(n, 1, kind));
+            }
+        };
+
+        if values.contains_key(key) {
+            let kind = CaseErrorKind::DuplicateKey(key.to_string());
+            return Err(CaseError::new(n, 1, kind));
+        }
+
+        let value = Value {
+            line: n,
+            column: line[..colon + 1].chars().count() + 1,
+            text: line[colon + 1..].to_string(),
+        };
+        values.insert(key, value);
+        last_key = Some(key);
+    }
+
+    if let Some((name, line, values)) = current.take() {
+        cases.push(case(name, line, values, dir)?);
+    }
+
+    Ok(cases)
+}
+
+pub fn load_cases<P: AsRef<Path>>(path: P) -> Result<Vec<Case>, LoadCasesError> {
+    let path = path.as_ref();
+
+    let s = fs::read_to_string(path).map_err(|error| LoadCasesError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    parse_cases(&s, dir).map_err(|error| LoadCasesError::Parse {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+struct Inputs(VecDeque<isize>);
+
+impl Stream for Inputs {
+    type Item = isize;
+
+    fn poll_next(&mut self) -> Poll<Option<Self::Item>> {
+        Poll::Ready(self.0.pop_front())
+    }
+}
+
+#[derive(Default)]
+struct Outputs(Vec<isize>);
+
+impl Hooks<isize> for Outputs {
+    fn on_output(&mut self, value: &isize) {
+        self.0.push(*value);
+    }
+}
+
+/// What a program did.
+#[derive(Debug)]
+pub struct Outcome {
+    pub output: Vec<isize>,
+    pub memory: Vec<isize>,
+    pub result: Result<(), MachineError>,
+}
+
+/// The name of the variant of an error.
+fn variant(e: &MachineError) -> String {
+    format!("{:?}", e)
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect()
+}
+
+/// A way in which a case did not do what was expected.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Mismatch {
+    Output {
+        expected: Vec<isize>,
+        found: Vec<isize>,
+    },
+    Memory {
+        addr: usize,
+        expected: isize,
+        found: Option<isize>,
+    },
+    Error {
+        expected: Option<String>,
+        found: Option<String>,
+    },
+}
+
+fn list(words: &[isize]) -> String {
+    words
+        .iter()
+        .map(|word| word.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Output { expected, found } => {
+                let at = expected
+                    .iter()
+                    .zip(found)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+
+                writeln!(
+                    f,
+                    "output differs at {} ({} values expected, {} found)",
+                    at,
+                    expected.len(),
+                    found.len()
+                )?;
+                writeln!(f, "  - {}", list(expected))?;
+                write!(f, "  + {}", list(found))
+            }
+            Self::Memory {
+                addr,
+                expected,
+                found: Some(found),
+            } => write!(f, "[{}] is {}, expected {}", addr, found, expected),
+            Self::Memory { addr, expected, .. } => {
+                write!(f, "[{}] is out of bounds, expected {}", addr, expected)
+            }
+            Self::Error { expected, found } => {
+                let describe = |e: &Option<String>| match e {
+                    Some(e) => format!("error `{}`", e),
+                    None => "halt".to_string(),
+                };
+
+                write!(
+                    f,
+                    "expected {}, found {}",
+                    describe(expected),
+                    describe(found)
+                )
+            }
+        }
+    }
+}
+
+impl Case {
+    pub fn run(&self) -> Outcome {
+        let inputs = Inputs(self.input.iter().copied().collect());
+        let mut machine = Machine::with_hooks(
+            self.program.words().to_vec(),
+            inputs,
+            Stdout::new(),
+            Outputs::default(),
+        )
+        .detect_loops(LOOP_INTERVAL);
+
+        let result = machine.execute();
+        let output = machine.hooks().0.clone();
+
+        Outcome {
+            output,
+            memory: machine.into_memory(),
+            result,
+        }
+    }
+
+    /// Runs the case, and returns every way in which it went wrong.
+    pub fn check(&self) -> Vec<Mismatch> {
+        let outcome = self.run();
+        let mut mismatches = Vec::new();
+
+        let found = outcome.result.as_ref().err().map(variant);
+        if found != self.error {
+            mismatches.push(Mismatch::Error {
+                expected: self.error.clone(),
+                found,
+            });
+        }
+
+        if let Some(expected) = &self.output {
+            if *expected != outcome.output {
+                mismatches.push(Mismatch::Output {
+                    expected: expected.clone(),
+                    found: outcome.output,
+                });
+            }
+        }
+
+        for patch in self.memory.iter() {
+            let found = outcome.memory.get(patch.addr).copied();
+            if found != Some(patch.value) {
+                mismatches.push(Mismatch::Memory {
+                    addr: patch.addr,
+                    expected: patch.value,
+                    found,
+                });
+            }
+        }
+
+        mismatches
+    }
+}
+
+/// How many cases passed, and how many failed.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+/// Checks every case, and reports on each to `out`, with what went wrong.
+pub fn run_cases<W: io::Write>(cases: &[Case], out: &mut W) -> io::Result<Summary> {
+    let mut summary = Summary::default();
+
+    for case in cases {
+        let mismatches = case.check();
+
+        if mismatches.is_empty() {
+            summary.passed += 1;
+            writeln!(out, "case {} ... ok", case.name)?;
+        } else {
+            summary.failed += 1;
+            writeln!(out, "case {} (line {}) ... FAILED", case.name, case.line)?;
+
+            for mismatch in mismatches {
+                for line in mismatch.to_string().lines() {
+                    writeln!(out, "    {}", line)?;
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// The example from day 5 that outputs 999 if its input is below 8, 1000
+/// if it is equal to 8, and 1001 if it is greater than 8.
+#[cfg(test)]
+pub(crate) fn compare_with_8() -> Vec<isize> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("cases")
+        .join("day05.cases");
+    let cases = load_cases(&path).unwrap_or_else(|e| panic!("{}", e));
+
+    cases
+        .into_iter()
+        .find(|case| case.name == "below 8")
+        .expect("No such case")
+        .program
+        .into_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cases_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("cases")
+    }
+
+    #[test]
+    fn example_cases_pass() {
+        let mut paths = fs::read_dir(cases_dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert!(!paths.is_empty());
+
+        for path in paths {
+            let cases = load_cases(&path).unwrap_or_else(|e| panic!("{}", e));
+            let mut report = Vec::new();
+            let summary = run_cases(&cases, &mut report).unwrap();
+
+            let report = String::from_utf8(report).unwrap();
+            assert_eq!(summary.failed, 0, "{}:\n{}", path.display(), report);
+            assert_eq!(summary.passed, cases.len());
+        }
+    }
+
+    #[test]
+    fn failures_are_reported_with_diffs() {
+        let s = "
+            [wrong]
+            program: 104,1,104,2,
+                     1101,2,2,0,
+                     99
+            output: 1, 3
+            memory: 0=5, 99=1
+            error: ReaderExhausted
+        ";
+        let s = s
+            .lines()
+            .map(|line| line.trim_start_matches("            "));
+        let s = s.collect::<Vec<_>>().join("\n");
+
+        let cases = parse_cases(&s, Path::new(".")).unwrap();
+        assert_eq!(cases[0].program.len(), 9);
+
+        let mut report = Vec::new();
+        let summary = run_cases(&cases, &mut report).unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                passed: 0,
+                failed: 1
+            }
+        );
+
+        let report = String::from_utf8(report).unwrap();
+        let expected = "\
+case wrong (line 2) ... FAILED
+    expected error `ReaderExhausted`, found halt
+    output differs at 1 (2 values expected, 2 found)
+      - 1,3
+      + 1,2
+    [0] is 4, expected 5
+    [99] is out of bounds, expected 1
+";
+        assert_eq!(report, expected);
+    }
+
+    #[test]
+    fn parse_errors_are_positioned() {
+        let dir = Path::new(".");
+
+        let err = parse_cases("program: 99", dir).unwrap_err();
+        assert_eq!(err, CaseError::new(1, 1, CaseErrorKind::ExpectedCase));
+
+        let err = parse_cases("[a]\nprogram: 1,,99", dir).unwrap_err();
+        assert_eq!(err.to_string(), "2:12: expected a word");
+
+        let err = parse_cases("[a]\noutput: 1", dir).unwrap_err();
+        assert_eq!(err.to_string(), "1:1: expected either `program` or `file`");
+
+        let err = parse_cases("[a]\nprogram: 99\ninputs: 1", dir).unwrap_err();
+        assert_eq!(err.kind, CaseErrorKind::UnknownKey("inputs".to_string()));
+
+        let err = parse_cases("[a]\nprogram: 99\npatch: 3=1", dir).unwrap_err();
+        assert_eq!((err.line, err.column), (3, 7));
+        assert!(matches!(err.kind, CaseErrorKind::Patch(_)));
+    }
+}
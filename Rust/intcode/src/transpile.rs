@@ -0,0 +1,672 @@
+//! Compilation of intcode programs into Rust.
+//!
+//! The generated module holds a copy of the program, and runs it as a
+//! `match` on the instruction pointer, with an arm of straight-line code
+//! for every run of instructions that can only be entered at its start.
+//! Only the code reachable from address 0 is compiled, following jumps to
+//! constant addresses, and stepping over calls. Instructions that the
+//! program writes to, and anything the compiled code doesn't cover, run on
+//! an embedded [`Machine`](crate::machine::Machine) instead, which hands
+//! back to the compiled code once it reaches the start of an arm. If the
+//! program writes to compiled code after all, through an address it
+//! computes, it runs on the `Machine` from then on.
+//!
+//! The module exposes the same interface as a `Machine`:
+//!
+//! ```ignore
+//! pub fn new(input: impl Stream<Item = isize>, output: impl Sink<isize>) -> Compiled<_, _>;
+//! pub fn run(input: impl Stream<Item = isize>, output: impl Sink<isize>) -> Result<(), MachineError>;
+//! ```
+//!
+//! where `Compiled` is a [`Future`](crate::future::Future), which is
+//! `Running` whenever it waits on I/O.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{
+    disasm::{Instruction, Operand},
+    opcode::Mnemonic,
+};
+
+/// A program, compiled into the source of a Rust module.
+#[derive(Clone, Debug)]
+pub struct Transpiled {
+    words: Vec<isize>,
+    /// The instructions that are compiled, by address.
+    instructions: BTreeMap<usize, Instruction>,
+    /// The addresses the compiled code can be entered at.
+    states: BTreeSet<usize>,
+}
+
+pub fn transpile(words: &[isize]) -> Transpiled {
+    let (discovered, roots) = discover(words);
+
+    let written = discovered
+        .values()
+        .filter_map(|instruction| match instruction.written()? {
+            Operand::Position(addr) if addr >= 0 => Some(addr as usize),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+
+    let (instructions, interpreted): (BTreeMap<_, _>, BTreeMap<_, _>) =
+        discovered.into_iter().partition(|(_, instruction)| {
+            (instruction.addr..instruction.next()).all(|addr| !written.contains(&addr))
+                && is_compilable(instruction)
+        });
+
+    // Wherever the code may jump to, every instruction that does I/O, so
+    // it can wait on it, and whatever follows an instruction that isn't
+    // compiled, where the `Machine` hands back.
+    let mut states = roots;
+    states.extend(
+        instructions
+            .values()
+            .filter(|instruction| instruction.mnemonic.is_io())
+            .map(|instruction| instruction.addr),
+    );
+    states.extend(interpreted.values().map(Instruction::next));
+    states.retain(|addr| instructions.contains_key(addr));
+
+    Transpiled {
+        words: words.to_vec(),
+        instructions,
+        states,
+    }
+}
+
+/// Follows every path from 0, assuming that any conditional jump can go
+/// either way, and that calls return right after the jump. An unconditional
+/// jump counts as a call if its return address was used since the last jump,
+/// and a jump to an address read from memory is taken to go through a table
+/// of addresses that follows it. Returns the instructions, and the addresses
+/// the paths start at.
+pub(crate) fn discover(words: &[isize]) -> (BTreeMap<usize, Instruction>, BTreeSet<usize>) {
+    let mut instructions = BTreeMap::new();
+    let mut roots = BTreeSet::new();
+    let mut todo = vec![0];
+
+    while let Some(start) = todo.pop() {
+        if !roots.insert(start) {
+            continue;
+        }
+
+        let mut addr = start;
+        let mut trace = Vec::new();
+
+        while let Some(instruction) = Instruction::decode(words, addr) {
+            if instructions.insert(addr, instruction).is_some() {
+                break;
+            }
+
+            trace.push(instruction);
+            let next = instruction.next();
+
+            let (cond, target) = match instruction.mnemonic {
+                Mnemonic::JumpIfTrue | Mnemonic::JumpIfFalse => {
+                    (instruction.operands()[0], instruction.operands()[1])
+                }
+                Mnemonic::Halt => break,
+                _ => {
+                    addr = next;
+                    continue;
+                }
+            };
+
+            let always = match cond {
+                Operand::Immediate(cond) if (cond != 0) != jumps_if(&instruction) => {
+                    addr = next;
+                    continue;
+                }
+                Operand::Immediate(_) => true,
+                _ => false,
+            };
+
+            match target {
+                Operand::Immediate(target) if target >= 0 => todo.push(target as usize),
+                Operand::Position(_) => todo.extend(
+                    words[next..]
+                        .iter()
+                        .take_while(|&&word| word >= 0 && (word as usize) < words.len())
+                        .map(|&word| word as usize),
+                ),
+                _ => {}
+            }
+
+            let call = trace.iter().any(|instruction| {
+                instruction
+                    .operands()
+                    .contains(&Operand::Immediate(next as isize))
+            });
+
+            if !always || call {
+                todo.push(next);
+            }
+
+            break;
+        }
+    }
+
+    (instructions, roots)
+}
+
+fn jumps_if(jump: &Instruction) -> bool {
+    jump.mnemonic == Mnemonic::JumpIfTrue
+}
+
+/// Whether the compiled code can run `instruction`. Writing to an immediate,
+/// and jumping to a negative address, are left to the `Machine` to fail on.
+fn is_compilable(instruction: &Instruction) -> bool {
+    match (instruction.written(), instruction.mnemonic) {
+        (Some(Operand::Immediate(_)), _) => false,
+        (_, Mnemonic::JumpIfTrue) | (_, Mnemonic::JumpIfFalse) => {
+            !matches!(instruction.operands()[1], Operand::Immediate(target) if target < 0)
+        }
+        _ => true,
+    }
+}
+
+impl Transpiled {
+    /// The address ranges of the compiled instructions.
+    fn code(&self) -> Vec<(usize, usize)> {
+        let mut ranges = Vec::<(usize, usize)>::new();
+
+        for instruction in self.instructions.values() {
+            match ranges.last_mut() {
+                Some((_, end)) if *end == instruction.addr => *end = instruction.next(),
+                _ => ranges.push((instruction.addr, instruction.next())),
+            }
+        }
+
+        ranges
+    }
+
+    /// The value of `operand`, as an expression.
+    fn value(&self, operand: Operand) -> Value {
+        match operand {
+            Operand::Immediate(value) => Value::Const(value),
+            Operand::Position(addr) if addr >= 0 && (addr as usize) < self.words.len() => {
+                Value::Expr(format!("self.memory[{}]", addr))
+            }
+            Operand::Position(addr) => Value::Expr(format!("self.read({})?", addr)),
+            Operand::Relative(offset) => Value::Expr(format!("self.read({})?", base(offset))),
+        }
+    }
+
+    /// The statement that writes `value` to `operand`, where the code goes
+    /// on at `next`.
+    fn write(&self, operand: Operand, value: &str, next: usize) -> String {
+        match operand {
+            Operand::Position(addr) if addr >= 0 && (addr as usize) < self.words.len() => {
+                assign(&format!("self.memory[{}]", addr), value)
+            }
+            Operand::Position(addr) => format!(
+                "if self.write({}, {})? {{ self.ip = {}; continue; }}",
+                addr, value, next
+            ),
+            Operand::Relative(offset) => format!(
+                "if self.write({}, {})? {{ self.ip = {}; continue; }}",
+                base(offset),
+                value,
+                next
+            ),
+            Operand::Immediate(_) => unreachable!(),
+        }
+    }
+
+    /// Writes the arm for the state at `start`.
+    fn write_state(&self, f: &mut fmt::Formatter, start: usize) -> fmt::Result {
+        const INDENT: &str = "                    ";
+
+        writeln!(f, "                {} => {{", start)?;
+
+        let mut addr = start;
+        loop {
+            let instruction = match self.instructions.get(&addr) {
+                Some(instruction) if addr == start || !self.states.contains(&addr) => instruction,
+                _ => {
+                    writeln!(f, "{}self.ip = {};", INDENT, addr)?;
+                    break;
+                }
+            };
+
+            writeln!(f, "{}// {}: {}", INDENT, addr, instruction)?;
+
+            let operands = instruction.operands();
+            let next = instruction.next();
+            let value = |index: usize| self.value(operands[index]);
+
+            let stmt = match instruction.mnemonic {
+                Mnemonic::Add => {
+                    let sum = value(0).add(value(1));
+                    self.write(operands[2], &sum, next)
+                }
+                Mnemonic::Mul => {
+                    let product = value(0).mul(value(1));
+                    self.write(operands[2], &product, next)
+                }
+                Mnemonic::LessThan => {
+                    let cmp = value(0).cmp("<", value(1), |a, b| a < b);
+                    self.write(operands[2], &cmp, next)
+                }
+                Mnemonic::Equals => {
+                    let cmp = value(0).cmp("==", value(1), |a, b| a == b);
+                    self.write(operands[2], &cmp, next)
+                }
+                Mnemonic::Save => {
+                    writeln!(
+                        f,
+                        "{}let value = match self.receive()? {{ Some(value) => value, None => return Ok(false) }};",
+                        INDENT
+                    )?;
+                    self.write(operands[0], "value", next)
+                }
+                Mnemonic::Output => {
+                    format!("if !self.send({})? {{ return Ok(false); }}", value(0))
+                }
+                Mnemonic::AdjustBase => match value(0) {
+                    Value::Const(offset) if offset < 0 => format!("self.base -= {};", -offset),
+                    offset => format!("self.base += {};", offset),
+                },
+                Mnemonic::JumpIfTrue | Mnemonic::JumpIfFalse => {
+                    let target = match value(1) {
+                        Value::Const(target) => target.to_string(),
+                        Value::Expr(target) => format!("usize::try_from({})?", target),
+                    };
+
+                    let cmp = if jumps_if(instruction) { "!=" } else { "==" };
+                    match value(0) {
+                        Value::Const(cond) if (cond != 0) != jumps_if(instruction) => {
+                            addr = next;
+                            continue;
+                        }
+                        Value::Const(_) => writeln!(f, "{}self.ip = {};", INDENT, target)?,
+                        Value::Expr(cond) => writeln!(
+                            f,
+                            "{}self.ip = if {} {} 0 {{ {} }} else {{ {} }};",
+                            INDENT, cond, cmp, target, next
+                        )?,
+                    }
+
+                    break;
+                }
+                Mnemonic::Halt => {
+                    writeln!(f, "{}self.ip = {};", INDENT, addr)?;
+                    writeln!(f, "{}return Ok(true);", INDENT)?;
+                    break;
+                }
+            };
+
+            if !stmt.is_empty() {
+                writeln!(f, "{}{}", INDENT, stmt)?;
+            }
+            addr = next;
+        }
+
+        writeln!(f, "                }}")
+    }
+}
+
+/// The statement `place = value`, using an operator assignment where
+/// `value` is an operation on `place` itself.
+fn assign(place: &str, value: &str) -> String {
+    if value == place {
+        return String::new();
+    }
+
+    for op in ["+", "-", "*"].iter() {
+        if let Some(rhs) = value.strip_prefix(&format!("{} {} ", place, op)) {
+            return format!("{} {}= {};", place, op, rhs);
+        }
+
+        if *op != "-" {
+            if let Some(lhs) = value.strip_suffix(&format!(" {} {}", op, place)) {
+                return format!("{} {}= {};", place, op, lhs);
+            }
+        }
+    }
+
+    format!("{} = {};", place, value)
+}
+
+/// The address `offset` away from the relative base.
+fn base(offset: isize) -> String {
+    match offset {
+        0 => "self.base".to_string(),
+        offset if offset < 0 => format!("self.base - {}", -offset),
+        offset => format!("self.base + {}", offset),
+    }
+}
+
+/// The value of an operand, folded where it is constant.
+enum Value {
+    Const(isize),
+    Expr(String),
+}
+
+impl Value {
+    fn add(self, rhs: Self) -> String {
+        match (self, rhs) {
+            (Self::Const(a), Self::Const(b)) => (a + b).to_string(),
+            (Self::Const(0), other) | (other, Self::Const(0)) => other.to_string(),
+            (other, Self::Const(b)) | (Self::Const(b), other) if b < 0 => {
+                format!("{} - {}", other, -b)
+            }
+            (lhs, rhs) => format!("{} + {}", lhs, rhs),
+        }
+    }
+
+    fn mul(self, rhs: Self) -> String {
+        match (self, rhs) {
+            (Self::Const(a), Self::Const(b)) => (a * b).to_string(),
+            (Self::Const(1), other) | (other, Self::Const(1)) => other.to_string(),
+            (Self::Const(-1), other) | (other, Self::Const(-1)) => format!("-{}", other),
+            // Still reads the other operand, which may fail.
+            (Self::Const(0), other) | (other, Self::Const(0)) => format!("{{ {}; 0 }}", other),
+            (lhs, rhs) => format!("{} * {}", lhs, rhs),
+        }
+    }
+
+    fn cmp(self, symbol: &str, rhs: Self, eval: impl Fn(isize, isize) -> bool) -> String {
+        match (self, rhs) {
+            (Self::Const(a), Self::Const(b)) => (eval(a, b) as isize).to_string(),
+            (lhs, rhs) => format!("isize::from({} {} {})", lhs, symbol, rhs),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Const(value) => write!(f, "{}", value),
+            Self::Expr(expr) => write!(f, "{}", expr),
+        }
+    }
+}
+
+/// Writes a comma separated list, a few items per line.
+fn write_list<T: fmt::Display>(
+    f: &mut fmt::Formatter,
+    items: &[T],
+    per_line: usize,
+) -> fmt::Result {
+    for line in items.chunks(per_line) {
+        write!(f, "   ")?;
+        for item in line {
+            write!(f, " {},", item)?;
+        }
+        writeln!(f)?;
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for Transpiled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = self.code();
+        let states = self.states.iter().collect::<Vec<_>>();
+        let ranges = code
+            .iter()
+            .map(|(start, end)| format!("({}, {})", start, end))
+            .collect::<Vec<_>>();
+
+        f.write_str(PRELUDE)?;
+
+        writeln!(f)?;
+        writeln!(f, "const PROGRAM: [isize; {}] = [", self.words.len())?;
+        write_list(f, &self.words, 16)?;
+        writeln!(f, "];")?;
+
+        writeln!(f)?;
+        writeln!(f, "/// The address ranges of the compiled instructions.")?;
+        writeln!(f, "const CODE: [(usize, usize); {}] = [", code.len())?;
+        write_list(f, &ranges, 8)?;
+        writeln!(f, "];")?;
+
+        writeln!(f)?;
+        writeln!(f, "/// Where the compiled code can be entered.")?;
+        writeln!(f, "const STATES: [usize; {}] = [", states.len())?;
+        write_list(f, &states, 16)?;
+        writeln!(f, "];")?;
+
+        f.write_str(RUNTIME)?;
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "    /// Runs the program until it halts, or waits on I/O."
+        )?;
+        writeln!(
+            f,
+            "    fn resume(&mut self) -> Result<bool, MachineError> {{"
+        )?;
+        writeln!(f, "        loop {{")?;
+        writeln!(f, "            if !self.compiled {{")?;
+        writeln!(f, "                return self.interpret();")?;
+        writeln!(f, "            }}")?;
+        writeln!(f)?;
+        writeln!(f, "            match self.ip {{")?;
+
+        for &start in self.states.iter() {
+            self.write_state(f, start)?;
+        }
+
+        writeln!(f, "                _ => return self.interpret(),")?;
+        writeln!(f, "            }}")?;
+        writeln!(f, "        }}")?;
+        writeln!(f, "    }}")?;
+        writeln!(f, "}}")
+    }
+}
+
+const PRELUDE: &str = "\
+// Generated by `intcode::transpile`.
+
+use std::{convert::TryFrom, mem};
+
+use intcode::{
+    future::{sink::Sink, stream::Stream, Future, FutureExt, Poll},
+    hooks::Hooks,
+    machine::{Machine, MachineError},
+};
+";
+
+const RUNTIME: &str = "
+fn contains(ranges: &[(usize, usize)], addr: usize) -> bool {
+    match ranges.binary_search_by_key(&addr, |&(start, _)| start) {
+        Ok(_) => true,
+        Err(0) => false,
+        Err(idx) => addr < ranges[idx - 1].1,
+    }
+}
+
+/// Notices when the interpreted code writes to compiled code.
+struct Watch<'a>(&'a mut bool);
+
+impl Hooks<isize> for Watch<'_> {
+    fn on_write(&mut self, addr: usize, _old: &isize, _new: &isize) {
+        if contains(&CODE, addr) {
+            *self.0 = false;
+        }
+    }
+}
+
+pub struct Compiled<R, W> {
+    ip: usize,
+    base: isize,
+    memory: Vec<isize>,
+    input: R,
+    output: W,
+    /// Whether the compiled code is still the program in memory.
+    compiled: bool,
+}
+
+pub fn new<R: Stream<Item = isize>, W: Sink<isize>>(input: R, output: W) -> Compiled<R, W> {
+    Compiled {
+        ip: 0,
+        base: 0,
+        memory: PROGRAM.to_vec(),
+        input,
+        output,
+        compiled: true,
+    }
+}
+
+pub fn run(
+    input: impl Stream<Item = isize>,
+    output: impl Sink<isize>,
+) -> Result<(), MachineError> {
+    new(input, output).execute()
+}
+
+impl<R: Stream<Item = isize>, W: Sink<isize>> Future for Compiled<R, W> {
+    type Output = Result<(), MachineError>;
+
+    fn poll(&mut self) -> Poll<Self::Output> {
+        match self.resume() {
+            Ok(true) => Poll::Ready(Ok(())),
+            Ok(false) => Poll::Running,
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+// Not every program needs all of these.
+#[allow(dead_code)]
+impl<R: Stream<Item = isize>, W: Sink<isize>> Compiled<R, W> {
+    fn read(&self, addr: isize) -> Result<isize, MachineError> {
+        let index = usize::try_from(addr)?;
+        let len = self.memory.len();
+        self.memory
+            .get(index)
+            .copied()
+            .ok_or(MachineError::IndexOutOfBounds { len, index })
+    }
+
+    /// Returns whether the write changed compiled code.
+    fn write(&mut self, addr: isize, value: isize) -> Result<bool, MachineError> {
+        let index = usize::try_from(addr)?;
+        let len = self.memory.len();
+        *self
+            .memory
+            .get_mut(index)
+            .ok_or(MachineError::IndexOutOfBounds { len, index })? = value;
+
+        let changed = contains(&CODE, index);
+        if changed {
+            self.compiled = false;
+        }
+
+        Ok(changed)
+    }
+
+    fn receive(&mut self) -> Result<Option<isize>, MachineError> {
+        match self.input.poll_next() {
+            Poll::Ready(Some(value)) => Ok(Some(value)),
+            Poll::Ready(None) => Err(MachineError::ReaderExhausted),
+            Poll::Running => Ok(None),
+        }
+    }
+
+    /// Returns whether the output was sent.
+    fn send(&mut self, value: isize) -> Result<bool, MachineError> {
+        match self.output.poll_ready() {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(_)) => return Err(MachineError::SinkPrepareError),
+            Poll::Running => return Ok(false),
+        }
+
+        self.output
+            .send(value)
+            .map_err(|_| MachineError::SinkSendError)?;
+        Ok(true)
+    }
+
+    /// Runs the program on a `Machine` until it halts, waits on I/O,
+    /// or reaches compiled code.
+    fn interpret(&mut self) -> Result<bool, MachineError> {
+        let memory = mem::take(&mut self.memory);
+        let watch = Watch(&mut self.compiled);
+        let mut machine = Machine::with_hooks(memory, &mut self.input, &mut self.output, watch)
+            .at(self.ip, self.base);
+
+        let result = loop {
+            let ip = machine.ip();
+
+            match machine.poll() {
+                Poll::Ready(result) => break result.map(|()| true),
+                // Waiting on I/O.
+                Poll::Running if machine.ip() == ip => break Ok(false),
+                Poll::Running if *machine.hooks().0 && STATES.binary_search(&machine.ip()).is_ok() => {
+                    break Ok(false);
+                }
+                Poll::Running => {}
+            }
+        };
+
+        self.ip = machine.ip();
+        self.base = machine.base();
+        self.memory = machine.into_memory();
+        result
+    }
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn straight_line_code_is_compiled() {
+        // The first example of day 7: outputs ten times its second input, plus the first.
+        let words = [
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+
+        let source = transpile(&words).to_string();
+
+        assert!(source.contains("const CODE: [(usize, usize); 1] = [\n    (0, 15),\n];"));
+        assert!(source.contains(
+            "\
+                2 => {
+                    // 2: in [16]
+                    let value = match self.receive()? { Some(value) => value, None => return Ok(false) };
+                    self.memory[16] = value;
+                    // 4: mul [16], 10, [16]
+                    self.memory[16] *= 10;
+                    // 8: add [16], [15], [15]
+                    self.memory[15] += self.memory[16];
+                    self.ip = 12;
+                }
+                12 => {
+                    // 12: out [15]
+                    if !self.send(self.memory[15])? { return Ok(false); }
+                    // 14: hlt
+                    self.ip = 14;
+                    return Ok(true);
+                }
+"
+        ));
+    }
+
+    #[test]
+    fn patched_instructions_are_interpreted() {
+        // Reads a value into the first parameter of the `out` at 4,
+        // and jumps back to it, until it reads 0.
+        let words = [3, 5, 104, 0, 104, 0, 1005, 5, 0, 99];
+
+        let transpiled = transpile(&words);
+        let source = transpiled.to_string();
+
+        assert!(!transpiled.instructions.contains_key(&4));
+        assert!(!source.contains("                4 => {"));
+        assert!(source.contains("                6 => {"));
+        assert!(source.contains("self.ip = if self.memory[5] != 0 { 0 } else { 9 };"));
+    }
+}